@@ -0,0 +1,23 @@
+/* Flash layout shared with the application, keep both `memory.x` in sync */
+MEMORY {
+    BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+    FLASH : ORIGIN = 0x10000100, LENGTH = 24K - 0x100
+    BOOTLOADER_STATE : ORIGIN = 0x10006000, LENGTH = 4K
+    ACTIVE : ORIGIN = 0x10007000, LENGTH = 1000K
+    /* One sector larger than ACTIVE, needed by the swap */
+    DFU : ORIGIN = 0x10101000, LENGTH = 1004K
+    /* Of the 16K left at the end, 0x1FC000..0x1FE000 is free, the sector at
+       0x1FE000 holds the settings set on the server and the last one, at
+       0x1FF000, the device configuration. The last panic of the application
+       is kept in RAM, in SCRATCH_X, see PANIC_RECORD in its `memory.x` */
+    RAM : ORIGIN = 0x20000000, LENGTH = 256K
+}
+
+__bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+__bootloader_state_end = ORIGIN(BOOTLOADER_STATE) + LENGTH(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+
+__bootloader_active_start = ORIGIN(ACTIVE) - ORIGIN(BOOT2);
+__bootloader_active_end = ORIGIN(ACTIVE) + LENGTH(ACTIVE) - ORIGIN(BOOT2);
+
+__bootloader_dfu_start = ORIGIN(DFU) - ORIGIN(BOOT2);
+__bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOT2);
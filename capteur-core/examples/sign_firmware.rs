@@ -0,0 +1,74 @@
+//! Sign a capteur firmware image for the over-the-air updates.
+//!
+//! ```text
+//! cargo run --example sign_firmware -- keygen
+//! FIRMWARE_SIGNING_KEY=<private key> cargo run --example sign_firmware -- sign <version> <image.bin>
+//! ```
+//!
+//! `keygen` prints a new private key, to keep offline, and its public key, to
+//! set as `FIRMWARE_PUBLIC_KEY` in the `.env` of the firmware. `sign` prints
+//! the signature of a raw image, as produced by
+//! `arm-none-eabi-objcopy -O binary`, to upload along with it.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::process::ExitCode;
+
+use capteur_core::ota::parse_key;
+use ed25519_dalek::{Signer, SigningKey};
+use protocol::firmware::signed_header;
+use protocol::FirmwareVersion;
+use sha2::{Digest, Sha512};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn keygen() -> Result<(), String> {
+    let mut seed = [0; 32];
+    File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut seed))
+        .map_err(|err| format!("Unable to generate a key: {err}"))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    println!("FIRMWARE_SIGNING_KEY={}", hex(&seed));
+    println!(
+        "FIRMWARE_PUBLIC_KEY={}",
+        hex(key.verifying_key().as_bytes())
+    );
+    Ok(())
+}
+
+fn sign(version: &str, path: &str) -> Result<(), String> {
+    let seed = env::var("FIRMWARE_SIGNING_KEY")
+        .map_err(|_| "FIRMWARE_SIGNING_KEY is not set".to_string())?;
+    let seed = parse_key(&seed).map_err(|_| "Invalid FIRMWARE_SIGNING_KEY".to_string())?;
+    let version = FirmwareVersion::parse(version).ok_or(format!("Invalid version {version}"))?;
+    let image = fs::read(path).map_err(|err| format!("Unable to read {path}: {err}"))?;
+    let size = u32::try_from(image.len()).map_err(|_| "Image too large".to_string())?;
+
+    let mut hasher = Sha512::new();
+    hasher.update(signed_header(version, size));
+    hasher.update(&image);
+    let signature = SigningKey::from_bytes(&seed).sign(&hasher.finalize());
+
+    println!("{}", hex(&signature.to_bytes()));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["keygen"] => keygen(),
+        ["sign", version, path] => sign(version, path),
+        _ => Err("Usage: sign_firmware keygen | sign <version> <image.bin>".to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/// Exponential backoff between retries, in seconds.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    min: u64,
+    max: u64,
+    next: u64,
+}
+
+impl Backoff {
+    pub const fn new(min: u64, max: u64) -> Self {
+        Self {
+            min,
+            max,
+            next: min,
+        }
+    }
+
+    /// Delay to wait before the next attempt, doubling on every call up to `max`.
+    pub fn next_delay(&mut self) -> u64 {
+        let delay = self.next;
+        self.next = self.next.saturating_mul(2).clamp(self.min, self.max);
+        delay
+    }
+
+    /// Start again from the minimum delay, typically after a success.
+    pub fn reset(&mut self) {
+        self.next = self.min;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let mut backoff = Backoff::new(5, 60);
+
+        let delays = [(); 6].map(|_| backoff.next_delay());
+
+        assert_eq!(delays, [5, 10, 20, 40, 60, 60]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut backoff = Backoff::new(5, 60);
+        backoff.next_delay();
+        backoff.next_delay();
+
+        backoff.reset();
+
+        assert_eq!(backoff.next_delay(), 5);
+    }
+
+    #[test]
+    fn test_zero_min_delay() {
+        let mut backoff = Backoff::new(0, 10);
+
+        assert_eq!(backoff.next_delay(), 0);
+        assert_eq!(backoff.next_delay(), 0);
+    }
+}
@@ -0,0 +1,73 @@
+//! Framing of the blocks stored in flash:
+//!
+//! | offset      | size | content                                   |
+//! |-------------|------|-------------------------------------------|
+//! | 0           | 4    | magic                                     |
+//! | 4           | 1    | format version                            |
+//! | 5           | 1    | reserved, 0                               |
+//! | 6           | 2    | payload length `n`, little endian         |
+//! | 8           | n    | payload                                   |
+//! | 8 + n       | 4    | CRC-32 of bytes `0..8 + n`, little endian |
+
+use crate::config::ConfigError;
+use crate::crc::crc32;
+
+pub const HEADER_LEN: usize = 8;
+pub const CRC_LEN: usize = 4;
+
+/// Frame the `payload_len` bytes already written at `HEADER_LEN` in
+/// `buffer`, returning the block length.
+pub fn seal(
+    buffer: &mut [u8],
+    magic: &[u8; 4],
+    version: u8,
+    payload_len: usize,
+) -> Result<usize, ConfigError> {
+    let len = HEADER_LEN + payload_len;
+    if len + CRC_LEN > buffer.len() {
+        return Err(ConfigError::BufferTooSmall);
+    }
+
+    buffer[0..4].copy_from_slice(magic);
+    buffer[4] = version;
+    buffer[5] = 0;
+    buffer[6..8].copy_from_slice(&(payload_len as u16).to_le_bytes());
+    let crc = crc32(&buffer[..len]);
+    buffer[len..len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(len + CRC_LEN)
+}
+
+/// Check the framing of `block`, returning its format version, one of
+/// `versions`, and payload.
+pub fn open<'a>(
+    block: &'a [u8],
+    magic: &[u8; 4],
+    versions: &[u8],
+) -> Result<(u8, &'a [u8]), ConfigError> {
+    if block.len() < HEADER_LEN {
+        return Err(ConfigError::InvalidLength);
+    }
+    if block[..HEADER_LEN].iter().all(|b| *b == 0xff) {
+        return Err(ConfigError::Empty);
+    }
+    if &block[0..4] != magic {
+        return Err(ConfigError::InvalidMagic);
+    }
+    let version = block[4];
+    if !versions.contains(&version) {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    let payload_len = u16::from_le_bytes([block[6], block[7]]) as usize;
+    let end = HEADER_LEN + payload_len;
+    if end + CRC_LEN > block.len() {
+        return Err(ConfigError::InvalidLength);
+    }
+    let crc = u32::from_le_bytes([block[end], block[end + 1], block[end + 2], block[end + 3]]);
+    if crc != crc32(&block[..end]) {
+        return Err(ConfigError::InvalidChecksum);
+    }
+
+    Ok((version, &block[HEADER_LEN..end]))
+}
@@ -0,0 +1,277 @@
+//! Device configuration, stored in a reserved flash sector.
+//!
+//! The block is framed as described in [`crate::block`], with the magic
+//! `ENVC`. The payload is a sequence of strings, each one prefixed by its length on
+//! one byte:
+//!
+//! - version 1: Wi-Fi network, Wi-Fi password, capteur id and API URL;
+//! - version 2: the number of Wi-Fi networks on one byte, then the SSID and
+//!   password of each network in priority order, the capteur id and API URL.
+//!
+//! Version 1 blocks are still decoded, as a single network configuration.
+
+use heapless::{String, Vec};
+use protocol::connection::Ssid;
+use protocol::CapteurId;
+
+use crate::block::{self, HEADER_LEN};
+
+pub const WIFI_PASSWORD_LEN: usize = 64;
+pub const API_URL_LEN: usize = 64;
+/// Maximum number of Wi-Fi networks a capteur can be configured with.
+pub const MAX_NETWORKS: usize = 3;
+
+/// Size of the buffer needed to encode any [`DeviceConfig`].
+pub const CONFIG_BLOCK_LEN: usize = 512;
+
+const MAGIC: &[u8; 4] = b"ENVC";
+const VERSION: u8 = 2;
+
+// Not `defmt::Format` on purpose, to keep the Wi-Fi password out of the logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiNetwork {
+    pub ssid: Ssid,
+    /// Empty for open networks.
+    pub password: String<WIFI_PASSWORD_LEN>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// Networks to join, by decreasing priority. Never empty.
+    pub networks: Vec<WifiNetwork, MAX_NETWORKS>,
+    pub capteur_id: CapteurId,
+    /// Base URL of the web server, without trailing slash.
+    pub api_url: String<API_URL_LEN>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The block is erased, the device has never been provisioned.
+    Empty,
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    InvalidLength,
+    InvalidChecksum,
+    /// A field is not valid UTF-8 or is too long.
+    InvalidField,
+    /// The buffer is too small to hold the encoded configuration.
+    BufferTooSmall,
+}
+
+impl DeviceConfig {
+    /// Encode the configuration into `buffer`, returning the block length.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ConfigError> {
+        if buffer.len() <= HEADER_LEN {
+            return Err(ConfigError::BufferTooSmall);
+        }
+        buffer[HEADER_LEN] = self.networks.len() as u8;
+        let mut len = HEADER_LEN + 1;
+        let networks = self
+            .networks
+            .iter()
+            .flat_map(|network| [network.ssid.as_str(), network.password.as_str()]);
+        for field in networks.chain([self.capteur_id.as_str(), self.api_url.as_str()]) {
+            let end = len + 1 + field.len();
+            if end > buffer.len() {
+                return Err(ConfigError::BufferTooSmall);
+            }
+            buffer[len] = field.len() as u8;
+            buffer[len + 1..end].copy_from_slice(field.as_bytes());
+            len = end;
+        }
+
+        block::seal(buffer, MAGIC, VERSION, len - HEADER_LEN)
+    }
+
+    /// Whether the API is reached over TLS.
+    pub fn api_uses_tls(&self) -> bool {
+        self.api_url.starts_with("https://")
+    }
+
+    /// Host name of the API server, used to verify its certificate.
+    pub fn api_host(&self) -> &str {
+        let url = self
+            .api_url
+            .split_once("://")
+            .map_or(self.api_url.as_str(), |(_, rest)| rest);
+        let authority = url.split('/').next().unwrap_or_default();
+        authority.split(':').next().unwrap_or_default()
+    }
+
+    pub fn decode(block: &[u8]) -> Result<Self, ConfigError> {
+        let (version, mut payload) = block::open(block, MAGIC, &[1, VERSION])?;
+        let network_count = match version {
+            1 => 1,
+            _ => {
+                let (count, rest) = payload.split_first().ok_or(ConfigError::InvalidLength)?;
+                payload = rest;
+                *count as usize
+            }
+        };
+        if network_count == 0 || network_count > MAX_NETWORKS {
+            return Err(ConfigError::InvalidField);
+        }
+        let mut networks = Vec::new();
+        for _ in 0..network_count {
+            let network = WifiNetwork {
+                ssid: read_field(&mut payload)?,
+                password: read_field(&mut payload)?,
+            };
+            // Cannot fail, the count is checked above
+            let _ = networks.push(network);
+        }
+        let config = Self {
+            networks,
+            capteur_id: read_field(&mut payload)?,
+            api_url: read_field(&mut payload)?,
+        };
+        if !payload.is_empty() {
+            return Err(ConfigError::InvalidLength);
+        }
+
+        Ok(config)
+    }
+}
+
+fn read_field<const N: usize>(payload: &mut &[u8]) -> Result<String<N>, ConfigError> {
+    let (len, rest) = payload.split_first().ok_or(ConfigError::InvalidLength)?;
+    let len = *len as usize;
+    if rest.len() < len {
+        return Err(ConfigError::InvalidLength);
+    }
+    let (field, rest) = rest.split_at(len);
+    *payload = rest;
+
+    let field = core::str::from_utf8(field).map_err(|_| ConfigError::InvalidField)?;
+    String::try_from(field).map_err(|_| ConfigError::InvalidField)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc::crc32;
+
+    fn network(ssid: &str, password: &str) -> WifiNetwork {
+        WifiNetwork {
+            ssid: Ssid::try_from(ssid).unwrap(),
+            password: String::try_from(password).unwrap(),
+        }
+    }
+
+    fn config() -> DeviceConfig {
+        DeviceConfig {
+            networks: Vec::from_slice(&[network("maison", "hunter22"), network("guest", "")])
+                .unwrap(),
+            capteur_id: CapteurId::try_from("salon").unwrap(),
+            api_url: String::try_from("https://envirometer.local").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        let len = config().encode(&mut block).unwrap();
+
+        assert_eq!(len, 8 + 1 + 7 + 9 + 6 + 1 + 6 + 26 + 4);
+        assert_eq!(DeviceConfig::decode(&block), Ok(config()));
+    }
+
+    #[test]
+    fn test_encode_layout() {
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        config().encode(&mut block).unwrap();
+
+        assert_eq!(&block[..11], b"ENVC\x02\x00\x38\x00\x02\x06m");
+    }
+
+    #[test]
+    fn test_decode_version_1() {
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        let payload = b"\x06maison\x08hunter22\x05salon\x19https://envirometer.local";
+        block[..8].copy_from_slice(b"ENVC\x01\x00\x30\x00");
+        block[8..8 + payload.len()].copy_from_slice(payload);
+        let crc = crc32(&block[..8 + payload.len()]);
+        block[8 + payload.len()..12 + payload.len()].copy_from_slice(&crc.to_le_bytes());
+
+        let config = DeviceConfig::decode(&block).unwrap();
+
+        assert_eq!(config.networks.as_slice(), &[network("maison", "hunter22")]);
+        assert_eq!(config.capteur_id, "salon");
+        assert_eq!(config.api_url, "https://envirometer.local");
+    }
+
+    #[test]
+    fn test_longest_config_fits_in_block() {
+        let longest_network = network(
+            &"n".repeat(protocol::connection::SSID_LEN),
+            &"p".repeat(WIFI_PASSWORD_LEN),
+        );
+        let config = DeviceConfig {
+            networks: Vec::from_slice(&[
+                longest_network.clone(),
+                longest_network.clone(),
+                longest_network,
+            ])
+            .unwrap(),
+            capteur_id: CapteurId::try_from("c".repeat(protocol::measure::CAPTEUR_ID_LEN).as_str())
+                .unwrap(),
+            api_url: String::try_from("u".repeat(API_URL_LEN).as_str()).unwrap(),
+        };
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        let len = config.encode(&mut block).unwrap();
+
+        assert_eq!(DeviceConfig::decode(&block[..len]), Ok(config));
+    }
+
+    #[test]
+    fn test_api_host() {
+        let mut config = config();
+        assert_eq!(config.api_host(), "envirometer.local");
+        assert!(config.api_uses_tls());
+
+        config.api_url = String::try_from("http://192.168.1.2:3000/api").unwrap();
+        assert_eq!(config.api_host(), "192.168.1.2");
+        assert!(!config.api_uses_tls());
+    }
+
+    #[test]
+    fn test_decode_erased_flash() {
+        assert_eq!(
+            DeviceConfig::decode(&[0xff; CONFIG_BLOCK_LEN]),
+            Err(ConfigError::Empty)
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        config().encode(&mut block).unwrap();
+
+        let mut invalid_magic = block;
+        invalid_magic[0] = b'X';
+        assert_eq!(
+            DeviceConfig::decode(&invalid_magic),
+            Err(ConfigError::InvalidMagic)
+        );
+
+        let mut future_version = block;
+        future_version[4] = 3;
+        assert_eq!(
+            DeviceConfig::decode(&future_version),
+            Err(ConfigError::UnsupportedVersion(3))
+        );
+
+        let mut corrupted = block;
+        corrupted[12] ^= 0x01;
+        assert_eq!(
+            DeviceConfig::decode(&corrupted),
+            Err(ConfigError::InvalidChecksum)
+        );
+
+        assert_eq!(
+            DeviceConfig::decode(&block[..20]),
+            Err(ConfigError::InvalidLength)
+        );
+    }
+}
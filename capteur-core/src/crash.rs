@@ -0,0 +1,229 @@
+//! Panic information kept across the reset, in a RAM section left alone by
+//! the bootloader and the startup code, until it is sent to the server.
+//!
+//! The record is framed as described in [`crate::block`], with the magic
+//! `ENVP`, so that the random content of the RAM after a power-up is not
+//! mistaken for a crash, nor a flash block for a crash record. The payload is
+//! the crash report, as JSON.
+
+use core::fmt::{self, Write};
+
+use heapless::String;
+use protocol::crash::CRASH_JSON_LEN;
+use protocol::{CrashReport, FirmwareVersion};
+
+use crate::block::{self, CRC_LEN, HEADER_LEN};
+use crate::config::ConfigError;
+
+/// Size of the buffer needed to encode any [`CrashReport`].
+pub const CRASH_BLOCK_LEN: usize = HEADER_LEN + CRASH_JSON_LEN + CRC_LEN;
+
+const MAGIC: &[u8; 4] = b"ENVP";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrashError {
+    /// The record holds no report: the RAM was just powered up, or the
+    /// report was cleared.
+    NoReport,
+    /// The record is damaged, or was written by another firmware version.
+    Corrupted,
+    /// The report is not valid JSON, or is too long.
+    InvalidReport,
+    /// The buffer is too small to hold the encoded report.
+    BufferTooSmall,
+}
+
+impl From<ConfigError> for CrashError {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::Empty | ConfigError::InvalidMagic => Self::NoReport,
+            ConfigError::UnsupportedVersion(_)
+            | ConfigError::InvalidLength
+            | ConfigError::InvalidChecksum => Self::Corrupted,
+            ConfigError::InvalidField => Self::InvalidReport,
+            ConfigError::BufferTooSmall => Self::BufferTooSmall,
+        }
+    }
+}
+
+/// Appends to a string until it is full, dropping the rest.
+struct Truncating<'a, const N: usize>(&'a mut String<N>);
+
+impl<const N: usize> Write for Truncating<'_, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            // Stop the formatting as soon as the string is full
+            self.0.push(c).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+/// The last `N` bytes of `path`, the end of a path telling the most.
+fn path_tail<const N: usize>(path: &str) -> String<N> {
+    let mut start = path.len().saturating_sub(N);
+    while !path.is_char_boundary(start) {
+        start += 1;
+    }
+    String::try_from(&path[start..]).unwrap_or_default()
+}
+
+/// Report of a panic at `location`, if known, with `message` cut to fit.
+pub fn crash_report(
+    firmware_version: FirmwareVersion,
+    uptime: u64,
+    location: Option<(&str, u32)>,
+    message: impl fmt::Display,
+) -> CrashReport {
+    let (file, line) = location.unwrap_or_default();
+    let mut report = CrashReport {
+        firmware_version,
+        uptime,
+        file: path_tail(file),
+        line,
+        message: String::new(),
+    };
+    let _ = write!(Truncating(&mut report.message), "{}", message);
+    report
+}
+
+pub fn encode(report: &CrashReport, buffer: &mut [u8]) -> Result<usize, CrashError> {
+    if buffer.len() < HEADER_LEN + CRC_LEN {
+        return Err(CrashError::BufferTooSmall);
+    }
+    let payload_end = buffer.len() - CRC_LEN;
+    let payload_len = serde_json_core::to_slice(report, &mut buffer[HEADER_LEN..payload_end])
+        .map_err(|_| CrashError::BufferTooSmall)?;
+
+    Ok(block::seal(buffer, MAGIC, VERSION, payload_len)?)
+}
+
+/// Decode a report saved by [`encode`].
+pub fn decode(block: &[u8]) -> Result<CrashReport, CrashError> {
+    let (_, payload) = block::open(block, MAGIC, &[VERSION])?;
+
+    serde_json_core::from_slice::<CrashReport>(payload)
+        .map(|(report, _)| report)
+        .map_err(|_| CrashError::InvalidReport)
+}
+
+/// Invalidate a block, once its report reached the server.
+pub fn clear(block: &mut [u8]) {
+    let len = block.len().min(HEADER_LEN);
+    block[..len].fill(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::crash::{CRASH_FILE_LEN, CRASH_MESSAGE_LEN};
+    use std::string::ToString;
+
+    const VERSION: FirmwareVersion = FirmwareVersion {
+        major: 0,
+        minor: 2,
+        patch: 0,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let report = crash_report(
+            VERSION,
+            3600,
+            Some(("src/web.rs", 42)),
+            format_args!(
+                "index out of bounds: the len is {} but the index is {}",
+                3, 7
+            ),
+        );
+        let mut buffer = [0; CRASH_BLOCK_LEN];
+        let len = encode(&report, &mut buffer).unwrap();
+
+        let decoded = decode(&buffer[..len]).unwrap();
+        assert_eq!(decoded, report);
+        assert_eq!(decoded.file, "src/web.rs");
+        assert_eq!(decoded.line, 42);
+        assert_eq!(
+            decoded.message,
+            "index out of bounds: the len is 3 but the index is 7"
+        );
+    }
+
+    #[test]
+    fn test_long_path_keeps_its_end() {
+        let path = "/home/builder/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f/embassy-net-0.4.0/src/tcp.rs";
+        let report = crash_report(VERSION, 0, Some((path, 1)), "");
+
+        assert_eq!(report.file.len(), CRASH_FILE_LEN);
+        assert!(report.file.ends_with("embassy-net-0.4.0/src/tcp.rs"));
+    }
+
+    #[test]
+    fn test_long_message_is_cut() {
+        let message = "é".repeat(CRASH_MESSAGE_LEN);
+        let report = crash_report(VERSION, 0, None, &message);
+
+        assert!(report.message.len() <= CRASH_MESSAGE_LEN);
+        assert!(message.starts_with(report.message.as_str()));
+        assert_eq!(report.file, "");
+    }
+
+    #[test]
+    fn test_path_tail_respects_char_boundaries() {
+        let tail: String<4> = path_tail("src/é.rs");
+
+        assert_eq!(tail, ".rs");
+        assert_eq!(path_tail::<5>("src/é.rs"), "é.rs");
+        assert_eq!(path_tail::<5>("a.rs"), "a.rs");
+    }
+
+    #[test]
+    fn test_largest_report_fits_in_block() {
+        let report = crash_report(
+            FirmwareVersion::parse("65535.65535.65535").unwrap(),
+            u64::MAX,
+            Some((&"\"".repeat(CRASH_FILE_LEN), u32::MAX)),
+            "\u{1}".repeat(CRASH_MESSAGE_LEN),
+        );
+        let mut buffer = [0; CRASH_BLOCK_LEN];
+
+        assert!(encode(&report, &mut buffer).is_ok());
+    }
+
+    #[test]
+    fn test_random_ram_is_not_a_report() {
+        let garbage: std::vec::Vec<u8> = (0..CRASH_BLOCK_LEN).map(|i| (i * 7 + 13) as u8).collect();
+
+        assert_eq!(decode(&garbage), Err(CrashError::NoReport));
+    }
+
+    #[test]
+    fn test_flash_block_is_not_a_report() {
+        let mut block = [0; CRASH_BLOCK_LEN];
+        let len = block::seal(&mut block, b"ENVC", 1, 0).unwrap();
+
+        assert_eq!(decode(&block[..len]), Err(CrashError::NoReport));
+    }
+
+    #[test]
+    fn test_corrupted_report() {
+        let report = crash_report(VERSION, 1, None, "boom");
+        let mut buffer = [0; CRASH_BLOCK_LEN];
+        encode(&report, &mut buffer).unwrap();
+        buffer[HEADER_LEN] ^= 0x01;
+
+        assert_eq!(decode(&buffer), Err(CrashError::Corrupted));
+    }
+
+    #[test]
+    fn test_cleared_block() {
+        let report = crash_report(VERSION, 1, None, "boom".to_string());
+        let mut buffer = [0; CRASH_BLOCK_LEN];
+        encode(&report, &mut buffer).unwrap();
+        clear(&mut buffer);
+
+        assert_eq!(decode(&buffer), Err(CrashError::NoReport));
+    }
+}
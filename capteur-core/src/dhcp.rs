@@ -0,0 +1,242 @@
+//! Minimal DHCP server, so that phones and laptops joining the provisioning
+//! access point get an address without any manual network setup.
+//!
+//! The server is stateless: the address offered to a client is derived from
+//! its hardware address, which is good enough for the handful of clients of a
+//! provisioning session.
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+/// Size of the buffer needed to hold a reply (the BOOTP minimum size).
+pub const DHCP_REPLY_LEN: usize = 300;
+
+/// Duration of the leases, in seconds.
+const LEASE_TIME: u32 = 3600;
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const OPTIONS_OFFSET: usize = 240;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_END: u8 = 255;
+
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+/// Address leased to the client with hardware address `mac`, in the /24
+/// network of `server`.
+pub fn client_address(server: [u8; 4], mac: &[u8]) -> [u8; 4] {
+    let last = mac.last().copied().unwrap_or_default();
+    [server[0], server[1], server[2], 2 + last % 250]
+}
+
+/// Build the reply to the client message `request` into `out`, returning its
+/// length, or `None` when the message must be ignored.
+pub fn reply(request: &[u8], server: [u8; 4], out: &mut [u8; DHCP_REPLY_LEN]) -> Option<usize> {
+    if request.len() < OPTIONS_OFFSET
+        || request[0] != BOOTREQUEST
+        || request[236..240] != MAGIC_COOKIE
+    {
+        return None;
+    }
+    let hlen = (request[2] as usize).min(16);
+    let mac = &request[28..28 + hlen];
+    let options = &request[OPTIONS_OFFSET..];
+    let address = client_address(server, mac);
+
+    let message_type = match find_option(options, OPTION_MESSAGE_TYPE)? {
+        [DHCPDISCOVER] => DHCPOFFER,
+        [DHCPREQUEST] => {
+            let requested = find_option(options, OPTION_REQUESTED_IP).unwrap_or(&request[12..16]);
+            if requested == address {
+                DHCPACK
+            } else {
+                DHCPNAK
+            }
+        }
+        _ => return None,
+    };
+
+    out.fill(0);
+    out[0] = BOOTREPLY;
+    // htype, hlen, hops and xid
+    out[1..8].copy_from_slice(&request[1..8]);
+    // flags
+    out[10..12].copy_from_slice(&request[10..12]);
+    if message_type != DHCPNAK {
+        out[16..20].copy_from_slice(&address);
+        out[20..24].copy_from_slice(&server);
+    }
+    // giaddr and chaddr
+    out[24..44].copy_from_slice(&request[24..44]);
+    out[236..240].copy_from_slice(&MAGIC_COOKIE);
+
+    let mut options = OptionWriter {
+        out,
+        len: OPTIONS_OFFSET,
+    };
+    options.write(OPTION_MESSAGE_TYPE, &[message_type]);
+    options.write(OPTION_SERVER_ID, &server);
+    if message_type != DHCPNAK {
+        options.write(OPTION_LEASE_TIME, &LEASE_TIME.to_be_bytes());
+        options.write(OPTION_SUBNET_MASK, &[255, 255, 255, 0]);
+        options.write(OPTION_ROUTER, &server);
+    }
+    options.out[options.len] = OPTION_END;
+
+    Some(DHCP_REPLY_LEN)
+}
+
+fn find_option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    loop {
+        match options {
+            [OPTION_END, ..] | [] => return None,
+            [OPTION_PAD, rest @ ..] => options = rest,
+            [option, len, rest @ ..] => {
+                let len = *len as usize;
+                if rest.len() < len {
+                    return None;
+                }
+                if *option == code {
+                    return Some(&rest[..len]);
+                }
+                options = &rest[len..];
+            }
+            [_] => return None,
+        }
+    }
+}
+
+struct OptionWriter<'a> {
+    out: &'a mut [u8; DHCP_REPLY_LEN],
+    len: usize,
+}
+
+impl OptionWriter<'_> {
+    fn write(&mut self, code: u8, value: &[u8]) {
+        self.out[self.len] = code;
+        self.out[self.len + 1] = value.len() as u8;
+        self.out[self.len + 2..self.len + 2 + value.len()].copy_from_slice(value);
+        self.len += 2 + value.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: [u8; 4] = [192, 168, 4, 1];
+    const MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x2a];
+
+    fn request(message_type: u8, requested_ip: Option<[u8; 4]>) -> [u8; 300] {
+        let mut packet = [0; 300];
+        packet[0] = BOOTREQUEST;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        packet[10] = 0x80;
+        packet[28..34].copy_from_slice(&MAC);
+        packet[236..240].copy_from_slice(&MAGIC_COOKIE);
+        packet[240..243].copy_from_slice(&[OPTION_MESSAGE_TYPE, 1, message_type]);
+        let mut end = 243;
+        if let Some(ip) = requested_ip {
+            packet[243..245].copy_from_slice(&[OPTION_REQUESTED_IP, 4]);
+            packet[245..249].copy_from_slice(&ip);
+            end = 249;
+        }
+        packet[end] = OPTION_END;
+        packet
+    }
+
+    #[test]
+    fn test_client_address() {
+        assert_eq!(client_address(SERVER, &MAC), [192, 168, 4, 44]);
+        assert_eq!(client_address(SERVER, &[0xff]), [192, 168, 4, 7]);
+    }
+
+    #[test]
+    fn test_offer() {
+        let mut out = [0; DHCP_REPLY_LEN];
+        let len = reply(&request(DHCPDISCOVER, None), SERVER, &mut out).unwrap();
+
+        assert_eq!(len, DHCP_REPLY_LEN);
+        assert_eq!(out[0], BOOTREPLY);
+        assert_eq!(&out[4..8], &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(out[10], 0x80);
+        assert_eq!(&out[16..20], &[192, 168, 4, 44]);
+        assert_eq!(&out[28..34], &MAC);
+        assert_eq!(
+            find_option(&out[240..], OPTION_MESSAGE_TYPE),
+            Some(&[DHCPOFFER][..])
+        );
+        assert_eq!(
+            find_option(&out[240..], OPTION_SERVER_ID),
+            Some(&SERVER[..])
+        );
+        assert_eq!(
+            find_option(&out[240..], OPTION_SUBNET_MASK),
+            Some(&[255, 255, 255, 0][..])
+        );
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut out = [0; DHCP_REPLY_LEN];
+        reply(
+            &request(DHCPREQUEST, Some([192, 168, 4, 44])),
+            SERVER,
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_option(&out[240..], OPTION_MESSAGE_TYPE),
+            Some(&[DHCPACK][..])
+        );
+        assert_eq!(&out[16..20], &[192, 168, 4, 44]);
+    }
+
+    #[test]
+    fn test_nak_on_unknown_address() {
+        let mut out = [0; DHCP_REPLY_LEN];
+        reply(
+            &request(DHCPREQUEST, Some([192, 168, 1, 12])),
+            SERVER,
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_option(&out[240..], OPTION_MESSAGE_TYPE),
+            Some(&[DHCPNAK][..])
+        );
+        assert_eq!(&out[16..20], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_ignored_messages() {
+        let mut out = [0; DHCP_REPLY_LEN];
+
+        // DHCPRELEASE
+        assert_eq!(reply(&request(7, None), SERVER, &mut out), None);
+        // Truncated packet
+        assert_eq!(
+            reply(&request(DHCPDISCOVER, None)[..200], SERVER, &mut out),
+            None
+        );
+        // Server message
+        let mut offer = request(DHCPDISCOVER, None);
+        offer[0] = BOOTREPLY;
+        assert_eq!(reply(&offer, SERVER, &mut out), None);
+    }
+}
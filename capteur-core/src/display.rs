@@ -0,0 +1,289 @@
+//! Layout of the optional 128×64 monochrome display: the status and time on
+//! the top line, the latest measure below in a large font.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::iso_8859_1::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+use heapless::String;
+use protocol::Timestamp;
+
+use crate::sampling::Filtered;
+use crate::status::Status;
+
+/// Size of the display, in pixels.
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+
+/// Height of the top line, the divider included.
+const HEADER_HEIGHT: i32 = 12;
+const TEMPERATURE_TOP: i32 = 16;
+const HUMIDITY_TOP: i32 = 40;
+
+/// What is shown on the display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Screen {
+    /// Latest measure, `None` until the sensor was first read.
+    pub measure: Option<Filtered>,
+    /// Current UTC time, `None` while the RTC is not set.
+    pub time: Option<Timestamp>,
+    pub status: Status,
+}
+
+/// Short description of `status`, fitting the top line next to the time.
+pub fn label(status: Status) -> &'static str {
+    match status {
+        Status::Booting => "Starting",
+        Status::JoiningWifi => "Joining Wi-Fi",
+        Status::WaitingDhcp => "Waiting DHCP",
+        Status::Connected => "Connected",
+        Status::Provisioning => "Setup mode",
+        Status::RtcSyncFailed => "No time",
+        Status::PostOk => "Sent",
+        Status::PostFailing => "Send failed",
+    }
+}
+
+/// Draw `screen` on `target`, after clearing it.
+pub fn draw<D>(screen: &Screen, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let large = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let top_left = TextStyleBuilder::new().baseline(Baseline::Top).build();
+    let top_right = TextStyleBuilder::new()
+        .baseline(Baseline::Top)
+        .alignment(Alignment::Right)
+        .build();
+
+    target.clear(BinaryColor::Off)?;
+
+    Text::with_text_style(label(screen.status), Point::zero(), small, top_left).draw(target)?;
+    let mut time = String::<5>::new();
+    match screen.time {
+        Some(now) => write!(time, "{:02}:{:02}", now.hour, now.minute),
+        None => write!(time, "--:--"),
+    }
+    .ok();
+    Text::with_text_style(&time, Point::new(WIDTH as i32 - 1, 0), small, top_right).draw(target)?;
+    Line::new(
+        Point::new(0, HEADER_HEIGHT - 2),
+        Point::new(WIDTH as i32 - 1, HEADER_HEIGHT - 2),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+    .draw(target)?;
+
+    let mut temperature = String::<12>::new();
+    let mut humidity = String::<12>::new();
+    match screen.measure {
+        Some(measure) => {
+            write!(temperature, "{:.1}°C", measure.temperature).ok();
+            write!(humidity, "{:.1} %", measure.humidity).ok();
+        }
+        None => {
+            write!(temperature, "--.-°C").ok();
+            write!(humidity, "--.- %").ok();
+        }
+    }
+    Text::with_text_style(
+        &temperature,
+        Point::new(0, TEMPERATURE_TOP),
+        large,
+        top_left,
+    )
+    .draw(target)?;
+    Text::with_text_style(&humidity, Point::new(0, HUMIDITY_TOP), large, top_left).draw(target)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embedded_graphics::primitives::Rectangle;
+
+    use super::*;
+
+    /// Frame buffer of the size of the display, failing the test when drawn
+    /// outside of it.
+    #[derive(PartialEq)]
+    struct Frame([[bool; WIDTH as usize]; HEIGHT as usize]);
+
+    impl Frame {
+        fn new() -> Self {
+            Self([[false; WIDTH as usize]; HEIGHT as usize])
+        }
+
+        fn render(screen: &Screen) -> Self {
+            let mut frame = Self::new();
+            draw(screen, &mut frame).unwrap();
+            frame
+        }
+
+        /// Whether any pixel of `area` is lit.
+        fn is_lit(&self, area: Rectangle) -> bool {
+            area.points()
+                .any(|point| self.0[point.y as usize][point.x as usize])
+        }
+
+        /// Whether the pixels of `area` are the same in both frames.
+        fn same_in(&self, other: &Self, area: Rectangle) -> bool {
+            area.points().all(|point| {
+                self.0[point.y as usize][point.x as usize]
+                    == other.0[point.y as usize][point.x as usize]
+            })
+        }
+    }
+
+    impl OriginDimensions for Frame {
+        fn size(&self) -> Size {
+            Size::new(WIDTH, HEIGHT)
+        }
+    }
+
+    impl DrawTarget for Frame {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                assert!(
+                    self.bounding_box().contains(point),
+                    "{point:?} is off the display"
+                );
+                self.0[point.y as usize][point.x as usize] = color.is_on();
+            }
+            Ok(())
+        }
+    }
+
+    fn area(top: i32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(0, top), Size::new(WIDTH, height))
+    }
+
+    fn header() -> Rectangle {
+        area(0, HEADER_HEIGHT as u32)
+    }
+
+    fn temperature() -> Rectangle {
+        area(TEMPERATURE_TOP, 20)
+    }
+
+    fn humidity() -> Rectangle {
+        area(HUMIDITY_TOP, 20)
+    }
+
+    const SCREEN: Screen = Screen {
+        measure: Some(Filtered {
+            temperature: -12.34,
+            humidity: 100.,
+            samples: 3,
+        }),
+        time: Some(Timestamp {
+            year: 2025,
+            month: 1,
+            day: 5,
+            hour: 23,
+            minute: 59,
+            second: 30,
+        }),
+        status: Status::PostFailing,
+    };
+
+    #[test]
+    fn test_everything_fits_on_the_display() {
+        let statuses = [
+            Status::Booting,
+            Status::JoiningWifi,
+            Status::WaitingDhcp,
+            Status::Connected,
+            Status::Provisioning,
+            Status::RtcSyncFailed,
+            Status::PostOk,
+            Status::PostFailing,
+        ];
+        for status in statuses {
+            // The label and the time do not overlap
+            assert!(
+                label(status).len() + 1 + 5 <= WIDTH as usize / 6,
+                "{status:?}"
+            );
+            Frame::render(&Screen { status, ..SCREEN });
+        }
+        Frame::render(&Screen {
+            measure: None,
+            time: None,
+            ..SCREEN
+        });
+    }
+
+    #[test]
+    fn test_each_value_in_its_area() {
+        let frame = Frame::render(&SCREEN);
+        for area in [header(), temperature(), humidity()] {
+            assert!(frame.is_lit(area), "{area:?}");
+        }
+
+        let other_measure = Frame::render(&Screen {
+            measure: Some(Filtered {
+                temperature: 21.5,
+                ..SCREEN.measure.unwrap()
+            }),
+            ..SCREEN
+        });
+        assert!(!frame.same_in(&other_measure, temperature()));
+        assert!(frame.same_in(&other_measure, humidity()));
+        assert!(frame.same_in(&other_measure, header()));
+
+        let other_status = Frame::render(&Screen {
+            status: Status::PostOk,
+            ..SCREEN
+        });
+        assert!(!frame.same_in(&other_status, header()));
+        assert!(frame.same_in(
+            &other_status,
+            area(HEADER_HEIGHT, HEIGHT - HEADER_HEIGHT as u32)
+        ));
+    }
+
+    #[test]
+    fn test_placeholders_without_measure_nor_time() {
+        let empty = Frame::render(&Screen {
+            measure: None,
+            time: None,
+            ..SCREEN
+        });
+        assert!(empty.is_lit(temperature()));
+        assert!(empty.is_lit(humidity()));
+
+        let time = Rectangle::new(Point::new(WIDTH as i32 - 30, 0), Size::new(30, 10));
+        assert!(empty.is_lit(time));
+        assert!(!empty.same_in(&Frame::render(&SCREEN), time));
+    }
+
+    #[test]
+    fn test_previous_screen_is_cleared() {
+        let mut frame = Frame::render(&SCREEN);
+        let screen = Screen {
+            measure: Some(Filtered {
+                temperature: 1.,
+                humidity: 1.,
+                samples: 1,
+            }),
+            time: None,
+            status: Status::Booting,
+        };
+        draw(&screen, &mut frame).unwrap();
+
+        assert!(frame == Frame::render(&screen));
+    }
+}
@@ -0,0 +1,90 @@
+use protocol::{ClockDrift, Timestamp};
+
+/// Keep track of RTC synchronisations to measure how much the RTC drifted
+/// between two of them.
+#[derive(Debug, Default)]
+pub struct DriftTracker {
+    /// Uptime, in seconds, of the last synchronisation.
+    last_sync: Option<u64>,
+}
+
+impl DriftTracker {
+    pub const fn new() -> Self {
+        Self { last_sync: None }
+    }
+
+    /// Record a synchronisation of the RTC to `reference`, happening at
+    /// `uptime` seconds. `rtc_now` is the RTC time just before it is
+    /// corrected, if it was running.
+    ///
+    /// Returns the drift since the previous synchronisation, if any.
+    pub fn record(
+        &mut self,
+        rtc_now: Option<Timestamp>,
+        reference: Timestamp,
+        uptime: u64,
+    ) -> Option<ClockDrift> {
+        let last_sync = self.last_sync.replace(uptime)?;
+        let rtc_now = rtc_now?;
+        let drift = rtc_now.to_unix() - reference.to_unix();
+        Some(ClockDrift {
+            timestamp: reference,
+            drift: drift.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            elapsed: u32::try_from(uptime.saturating_sub(last_sync)).unwrap_or(u32::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(unix: i64) -> Timestamp {
+        Timestamp::from_unix(unix).unwrap()
+    }
+
+    #[test]
+    fn test_first_sync_has_no_drift() {
+        let mut tracker = DriftTracker::new();
+
+        assert_eq!(tracker.record(None, ts(1_737_569_275), 3), None);
+    }
+
+    #[test]
+    fn test_drift_since_previous_sync() {
+        let mut tracker = DriftTracker::new();
+        tracker.record(None, ts(1_737_569_275), 3);
+
+        let drift = tracker.record(Some(ts(1_737_590_877)), ts(1_737_590_875), 21_603);
+
+        assert_eq!(
+            drift,
+            Some(ClockDrift {
+                timestamp: ts(1_737_590_875),
+                drift: 2,
+                elapsed: 21_600,
+            })
+        );
+    }
+
+    #[test]
+    fn test_late_rtc_has_negative_drift() {
+        let mut tracker = DriftTracker::new();
+        tracker.record(None, ts(1_737_569_275), 0);
+
+        let drift = tracker.record(Some(ts(1_737_569_270)), ts(1_737_569_275), 100);
+
+        assert_eq!(drift.map(|drift| drift.drift), Some(-5));
+    }
+
+    #[test]
+    fn test_stopped_rtc_has_no_drift() {
+        let mut tracker = DriftTracker::new();
+        tracker.record(None, ts(1_737_569_275), 0);
+
+        assert_eq!(tracker.record(None, ts(1_737_590_875), 21_600), None);
+        assert!(tracker
+            .record(Some(ts(1_737_612_475)), ts(1_737_612_475), 43_200)
+            .is_some());
+    }
+}
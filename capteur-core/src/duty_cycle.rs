@@ -0,0 +1,242 @@
+//! Scheduling of the low-power mode: measures are taken at a fixed interval
+//! and buffered, the Wi-Fi only being powered up to upload them in batches.
+
+use heapless::Deque;
+use protocol::{Timestamp, TimestampError};
+
+/// What the capteur should do after a measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Sleep for this many seconds before the next measure.
+    Sleep(u64),
+    /// Power up the Wi-Fi and upload the buffered measures.
+    Upload,
+}
+
+#[derive(Debug, Clone)]
+pub struct DutyCycle {
+    /// Seconds between two measures.
+    interval: u64,
+    /// Number of buffered measures triggering an upload.
+    batch_len: usize,
+    /// Number of measures the buffer holds.
+    capacity: usize,
+    /// Buffered measures at which to upload next.
+    upload_at: usize,
+}
+
+impl DutyCycle {
+    pub fn new(interval: u64, batch_len: usize, capacity: usize) -> Self {
+        let batch_len = batch_len.clamp(1, capacity.max(1));
+        Self {
+            interval,
+            batch_len,
+            capacity,
+            upload_at: batch_len,
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Change the interval, taking effect from the next measure.
+    pub fn set_interval(&mut self, interval: u64) {
+        self.interval = interval;
+    }
+
+    /// Change the number of measures uploaded at once, taking effect from the
+    /// next upload.
+    pub fn set_batch_len(&mut self, batch_len: usize) {
+        self.batch_len = batch_len.clamp(1, self.capacity.max(1));
+    }
+
+    /// A measure was taken in `elapsed` seconds, `buffered` measures are now
+    /// waiting to be uploaded.
+    pub fn measured(&self, buffered: usize, elapsed: u64) -> Action {
+        if buffered >= self.upload_at {
+            Action::Upload
+        } else {
+            Action::Sleep(self.interval.saturating_sub(elapsed))
+        }
+    }
+
+    /// An upload ended `elapsed` seconds after the last measure, leaving
+    /// `buffered` measures that could not be sent. These are retried with the
+    /// next batch rather than on every measure, to spare the battery while the
+    /// server is unreachable.
+    ///
+    /// Returns how long to sleep before the next measure.
+    pub fn uploaded(&mut self, buffered: usize, elapsed: u64) -> u64 {
+        self.upload_at = buffered.saturating_add(self.batch_len).min(self.capacity);
+        self.interval.saturating_sub(elapsed)
+    }
+}
+
+/// Time a buffered measure was taken, at `taken_at` seconds of uptime, the
+/// time being `now` at `uptime`.
+pub fn taken_at(now: Timestamp, uptime: u64, taken_at: u64) -> Result<Timestamp, TimestampError> {
+    let age = uptime.saturating_sub(taken_at) as i64;
+    Timestamp::from_unix(now.to_unix() - age)
+}
+
+/// Measures waiting to be uploaded, with the uptime in seconds at which they
+/// were taken. When full, the oldest measure is dropped.
+#[derive(Debug)]
+pub struct ReadingBuffer<T, const N: usize> {
+    readings: Deque<(u64, T), N>,
+}
+
+impl<T, const N: usize> Default for ReadingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> ReadingBuffer<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            readings: Deque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    /// Buffer a measure taken at `uptime`, returning whether the oldest one
+    /// was dropped to make room for it.
+    pub fn push(&mut self, uptime: u64, reading: T) -> bool {
+        let dropped = self.readings.is_full();
+        if dropped {
+            self.readings.pop_front();
+        }
+        // Cannot fail, there is room left
+        let _ = self.readings.push_back((uptime, reading));
+        dropped
+    }
+
+    /// Oldest buffered measure, to upload first.
+    pub fn oldest(&self) -> Option<&(u64, T)> {
+        self.readings.front()
+    }
+
+    /// Measure at `index`, from the oldest.
+    pub fn get(&self, index: usize) -> Option<&(u64, T)> {
+        self.readings.iter().nth(index)
+    }
+
+    /// Remove the oldest measure, once uploaded.
+    pub fn pop(&mut self) -> Option<(u64, T)> {
+        self.readings.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sleep_until_batch_is_full() {
+        let duty_cycle = DutyCycle::new(300, 3, 16);
+
+        assert_eq!(duty_cycle.measured(1, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(2, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(3, 2), Action::Upload);
+    }
+
+    #[test]
+    fn test_slow_measure_does_not_underflow() {
+        let duty_cycle = DutyCycle::new(5, 3, 16);
+
+        assert_eq!(duty_cycle.measured(1, 8), Action::Sleep(0));
+    }
+
+    #[test]
+    fn test_set_interval() {
+        let mut duty_cycle = DutyCycle::new(300, 3, 16);
+
+        duty_cycle.set_interval(60);
+
+        assert_eq!(duty_cycle.measured(1, 2), Action::Sleep(58));
+    }
+
+    #[test]
+    fn test_set_batch_len() {
+        let mut duty_cycle = DutyCycle::new(300, 3, 16);
+
+        duty_cycle.set_batch_len(5);
+        duty_cycle.uploaded(0, 20);
+
+        assert_eq!(duty_cycle.measured(4, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(5, 2), Action::Upload);
+    }
+
+    #[test]
+    fn test_successful_upload() {
+        let mut duty_cycle = DutyCycle::new(300, 3, 16);
+
+        assert_eq!(duty_cycle.uploaded(0, 20), 280);
+        assert_eq!(duty_cycle.measured(2, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(3, 2), Action::Upload);
+    }
+
+    #[test]
+    fn test_failed_upload_waits_for_next_batch() {
+        let mut duty_cycle = DutyCycle::new(300, 3, 16);
+
+        duty_cycle.uploaded(3, 60);
+
+        assert_eq!(duty_cycle.measured(4, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(5, 2), Action::Sleep(298));
+        assert_eq!(duty_cycle.measured(6, 2), Action::Upload);
+    }
+
+    #[test]
+    fn test_full_buffer_triggers_upload() {
+        let mut duty_cycle = DutyCycle::new(300, 3, 4);
+
+        duty_cycle.uploaded(3, 60);
+
+        assert_eq!(duty_cycle.measured(4, 2), Action::Upload);
+    }
+
+    #[test]
+    fn test_batch_len_is_bounded() {
+        assert_eq!(DutyCycle::new(300, 0, 16).measured(1, 0), Action::Upload);
+        assert_eq!(DutyCycle::new(300, 32, 16).measured(16, 0), Action::Upload);
+    }
+
+    #[test]
+    fn test_buffer_drops_oldest() {
+        let mut buffer: ReadingBuffer<u8, 2> = ReadingBuffer::new();
+
+        assert!(!buffer.push(10, 1));
+        assert!(!buffer.push(20, 2));
+        assert!(buffer.push(30, 3));
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.oldest(), Some(&(20, 2)));
+        assert_eq!(buffer.get(1), Some(&(30, 3)));
+        assert_eq!(buffer.get(2), None);
+        assert_eq!(buffer.pop(), Some((20, 2)));
+        assert_eq!(buffer.pop(), Some((30, 3)));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_time_of_buffered_measure() {
+        let now = Timestamp::from_unix(1_737_569_275).unwrap();
+
+        assert_eq!(
+            taken_at(now, 3_600, 600),
+            Ok(Timestamp::from_unix(1_737_566_275).unwrap())
+        );
+        assert_eq!(taken_at(now, 3_600, 3_600), Ok(now));
+    }
+}
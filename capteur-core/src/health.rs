@@ -0,0 +1,144 @@
+//! Health telemetry gathered by the firmware and sent in the heartbeats.
+
+use protocol::{FirmwareVersion, Heartbeat, SensorErrors, Timestamp};
+
+/// Kinds of errors of the AM2301 sensor, mirroring `am2301::MeasureError`,
+/// and the samples discarded by the `crate::sampling::Sampler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SensorErrorKind {
+    Timeout,
+    Checksum,
+    Invalid,
+    Implausible,
+}
+
+/// What happened since the boot that the server cannot tell from the
+/// measures alone.
+#[derive(Debug, Default)]
+pub struct HealthMonitor {
+    sensor_errors: SensorErrors,
+    rssi: Option<i16>,
+    last_rtc_sync: Option<Timestamp>,
+}
+
+impl HealthMonitor {
+    pub const fn new() -> Self {
+        Self {
+            sensor_errors: SensorErrors {
+                timeout: 0,
+                checksum: 0,
+                invalid: 0,
+                implausible: 0,
+            },
+            rssi: None,
+            last_rtc_sync: None,
+        }
+    }
+
+    pub fn sensor_failed(&mut self, kind: SensorErrorKind) {
+        let count = match kind {
+            SensorErrorKind::Timeout => &mut self.sensor_errors.timeout,
+            SensorErrorKind::Checksum => &mut self.sensor_errors.checksum,
+            SensorErrorKind::Invalid => &mut self.sensor_errors.invalid,
+            SensorErrorKind::Implausible => &mut self.sensor_errors.implausible,
+        };
+        *count = count.saturating_add(1);
+    }
+
+    /// Record the signal strength of the joined network, `None` once it is
+    /// left.
+    pub fn set_rssi(&mut self, rssi: Option<i16>) {
+        self.rssi = rssi;
+    }
+
+    pub fn rtc_synced(&mut self, reference: Timestamp) {
+        self.last_rtc_sync = Some(reference);
+    }
+
+    /// Heartbeat of a capteur up for `uptime` seconds.
+    pub fn heartbeat(
+        &self,
+        uptime: u64,
+        firmware_version: FirmwareVersion,
+        free_buffer_slots: Option<u16>,
+    ) -> Heartbeat {
+        Heartbeat {
+            uptime,
+            firmware_version,
+            rssi: self.rssi,
+            free_buffer_slots,
+            sensor_errors: self.sensor_errors,
+            last_rtc_sync: self.last_rtc_sync,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION: FirmwareVersion = FirmwareVersion {
+        major: 0,
+        minor: 2,
+        patch: 0,
+    };
+
+    #[test]
+    fn test_fresh_heartbeat() {
+        let heartbeat = HealthMonitor::new().heartbeat(12, VERSION, Some(288));
+
+        assert_eq!(heartbeat.uptime, 12);
+        assert_eq!(heartbeat.firmware_version, VERSION);
+        assert_eq!(heartbeat.rssi, None);
+        assert_eq!(heartbeat.free_buffer_slots, Some(288));
+        assert_eq!(heartbeat.sensor_errors, SensorErrors::default());
+        assert_eq!(heartbeat.last_rtc_sync, None);
+    }
+
+    #[test]
+    fn test_sensor_errors_by_kind() {
+        let mut monitor = HealthMonitor::new();
+        monitor.sensor_failed(SensorErrorKind::Timeout);
+        monitor.sensor_failed(SensorErrorKind::Timeout);
+        monitor.sensor_failed(SensorErrorKind::Checksum);
+        monitor.sensor_failed(SensorErrorKind::Implausible);
+
+        assert_eq!(
+            monitor.heartbeat(60, VERSION, None).sensor_errors,
+            SensorErrors {
+                timeout: 2,
+                checksum: 1,
+                invalid: 0,
+                implausible: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_sensor_errors_saturate() {
+        let mut monitor = HealthMonitor::new();
+        monitor.sensor_errors.invalid = u32::MAX;
+        monitor.sensor_failed(SensorErrorKind::Invalid);
+
+        assert_eq!(
+            monitor.heartbeat(60, VERSION, None).sensor_errors.invalid,
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_link_and_rtc_state() {
+        let mut monitor = HealthMonitor::new();
+        let synced_at = Timestamp::new(2025, 1, 22, 18, 7, 55).unwrap();
+        monitor.set_rssi(Some(-71));
+        monitor.rtc_synced(synced_at);
+
+        let heartbeat = monitor.heartbeat(60, VERSION, None);
+        assert_eq!(heartbeat.rssi, Some(-71));
+        assert_eq!(heartbeat.last_rtc_sync, Some(synced_at));
+
+        monitor.set_rssi(None);
+        assert_eq!(monitor.heartbeat(90, VERSION, None).rssi, None);
+    }
+}
@@ -0,0 +1,55 @@
+//! HTTP client of the capteur, as far as the API is concerned, implemented by
+//! the firmware over its TCP and TLS stack.
+
+use crate::payload::{Body, Encoding};
+
+/// Length of the URLs of the API.
+pub const URL_LEN: usize = 160;
+
+pub type Url = heapless::String<URL_LEN>;
+
+#[allow(async_fn_in_trait)]
+pub trait HttpClient {
+    type Error;
+
+    /// Fetch `url`, returning the body of a successful response, read into
+    /// `buffer`.
+    async fn get<'b>(&mut self, url: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], Self::Error>;
+
+    /// Post `body`, encoded as `encoding`, to `url`, failing unless the
+    /// server accepted it.
+    async fn post(&mut self, url: &str, body: &[u8], encoding: Encoding)
+        -> Result<(), Self::Error>;
+
+    /// Post `body`, `len` bytes long once encoded, to `url`, encoding it piece
+    /// by piece as it is sent.
+    async fn post_body<B: Body>(
+        &mut self,
+        url: &str,
+        body: &B,
+        len: usize,
+    ) -> Result<(), Self::Error>;
+}
+
+/// `url`, `None` when it does not fit in [`URL_LEN`].
+pub fn url(args: core::fmt::Arguments) -> Option<Url> {
+    let mut url = Url::new();
+    core::fmt::write(&mut url, args).ok()?;
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let api_url = "https://example.com/api";
+
+        assert_eq!(
+            url(format_args!("{api_url}/capteurs/{}/drift", "salon")).unwrap(),
+            "https://example.com/api/capteurs/salon/drift"
+        );
+        assert_eq!(url(format_args!("{:161}", api_url)), None);
+    }
+}
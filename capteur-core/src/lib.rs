@@ -0,0 +1,39 @@
+//! Hardware independent logic of the capteur firmware.
+//!
+//! Everything in this crate is `no_std` and free of any `embassy` dependency
+//! so it can be tested on the host with a plain `cargo test`.
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+
+pub mod backoff;
+mod block;
+pub mod config;
+pub mod crash;
+mod crc;
+pub mod dhcp;
+#[cfg(feature = "display")]
+pub mod display;
+pub mod drift;
+pub mod duty_cycle;
+pub mod health;
+pub mod http;
+pub mod liveness;
+#[cfg(test)]
+mod mock;
+pub mod now;
+pub mod ota;
+pub mod payload;
+pub mod provisioning;
+pub mod reboot;
+pub mod reporting;
+pub mod rtc;
+pub mod sampling;
+pub mod settings;
+pub mod sntp;
+pub mod status;
+pub mod time_sync;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod wifi;
@@ -0,0 +1,100 @@
+//! Liveness of the critical tasks of the firmware, to only feed the watchdog
+//! while all of them make progress.
+//!
+//! A task watched by the tracker reports progress with the delay before its
+//! next report, so tasks sleeping for long periods are not considered stuck.
+//! A task waiting on another one, itself watched, goes idle instead.
+
+use protocol::CriticalTask;
+
+const TASKS: [CriticalTask; 3] = [
+    CriticalTask::Measure,
+    CriticalTask::Network,
+    CriticalTask::Post,
+];
+
+#[derive(Debug, Default)]
+pub struct LivenessTracker {
+    /// Uptime, in seconds, by which each task must report again, `None`
+    /// while it is idle.
+    deadlines: [Option<u64>; TASKS.len()],
+}
+
+fn index(task: CriticalTask) -> usize {
+    match task {
+        CriticalTask::Measure => 0,
+        CriticalTask::Network => 1,
+        CriticalTask::Post => 2,
+    }
+}
+
+impl LivenessTracker {
+    /// All the tasks start idle.
+    pub const fn new() -> Self {
+        Self {
+            deadlines: [None; TASKS.len()],
+        }
+    }
+
+    /// Record that `task` made progress at `now` and will report again
+    /// within `timeout` seconds.
+    pub fn progress(&mut self, task: CriticalTask, now: u64, timeout: u64) {
+        self.deadlines[index(task)] = Some(now.saturating_add(timeout));
+    }
+
+    /// Stop watching `task` until it reports progress again.
+    pub fn idle(&mut self, task: CriticalTask) {
+        self.deadlines[index(task)] = None;
+    }
+
+    /// First task which missed its deadline at `now`, if any.
+    pub fn late(&self, now: u64) -> Option<CriticalTask> {
+        TASKS
+            .into_iter()
+            .zip(self.deadlines)
+            .find(|(_, deadline)| deadline.is_some_and(|deadline| now > deadline))
+            .map(|(task, _)| task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_tasks_are_never_late() {
+        let tracker = LivenessTracker::new();
+
+        assert_eq!(tracker.late(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_late_task() {
+        let mut tracker = LivenessTracker::new();
+        tracker.progress(CriticalTask::Measure, 10, 5);
+        tracker.progress(CriticalTask::Network, 10, 60);
+
+        assert_eq!(tracker.late(15), None);
+        assert_eq!(tracker.late(16), Some(CriticalTask::Measure));
+
+        tracker.progress(CriticalTask::Measure, 16, 300);
+        assert_eq!(tracker.late(71), Some(CriticalTask::Network));
+    }
+
+    #[test]
+    fn test_idle_task_stops_being_watched() {
+        let mut tracker = LivenessTracker::new();
+        tracker.progress(CriticalTask::Post, 100, 30);
+        tracker.idle(CriticalTask::Post);
+
+        assert_eq!(tracker.late(1000), None);
+    }
+
+    #[test]
+    fn test_long_timeout_does_not_overflow() {
+        let mut tracker = LivenessTracker::new();
+        tracker.progress(CriticalTask::Network, 100, u64::MAX);
+
+        assert_eq!(tracker.late(u64::MAX), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Test doubles of the clock and the HTTP client, and a minimal executor to
+//! run the async logic in tests.
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::string::String;
+use std::vec::Vec;
+
+use protocol::Timestamp;
+
+use crate::http::HttpClient;
+use crate::payload::{Body, Encoding};
+use crate::rtc::Rtc;
+
+/// Run `future` to completion, the mocks never being pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Get(String),
+    Post {
+        url: String,
+        body: Vec<u8>,
+        encoding: Encoding,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockError;
+
+/// Answers the requests with the queued responses, failing once they are
+/// exhausted.
+#[derive(Debug, Default)]
+pub struct MockHttp {
+    responses: VecDeque<Result<Vec<u8>, MockError>>,
+    pub requests: Vec<Request>,
+}
+
+impl MockHttp {
+    pub fn respond(&mut self, response: Result<&[u8], MockError>) -> &mut Self {
+        self.responses.push_back(response.map(<[u8]>::to_vec));
+        self
+    }
+
+    fn next_response(&mut self) -> Result<Vec<u8>, MockError> {
+        self.responses.pop_front().unwrap_or(Err(MockError))
+    }
+}
+
+impl HttpClient for MockHttp {
+    type Error = MockError;
+
+    async fn get<'b>(&mut self, url: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], MockError> {
+        self.requests.push(Request::Get(url.into()));
+        let body = self.next_response()?;
+        buffer[..body.len()].copy_from_slice(&body);
+        Ok(&buffer[..body.len()])
+    }
+
+    async fn post(&mut self, url: &str, body: &[u8], encoding: Encoding) -> Result<(), MockError> {
+        self.requests.push(Request::Post {
+            url: url.into(),
+            body: body.to_vec(),
+            encoding,
+        });
+        self.next_response().map(|_| ())
+    }
+
+    async fn post_body<B: Body>(
+        &mut self,
+        url: &str,
+        body: &B,
+        len: usize,
+    ) -> Result<(), MockError> {
+        let mut bytes = Vec::with_capacity(len);
+        let mut buffer = [0; protocol::batch::PIECE_LEN];
+        for index in 0..body.pieces() {
+            bytes.extend_from_slice(body.piece(index, &mut buffer).unwrap());
+        }
+        assert_eq!(bytes.len(), len);
+        self.post(url, &bytes, body.encoding()).await
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MockRtc {
+    pub now: Option<Timestamp>,
+    /// Whether setting the clock fails.
+    pub broken: bool,
+}
+
+impl Rtc for MockRtc {
+    type Error = MockError;
+
+    fn now(&self) -> Option<Timestamp> {
+        self.now
+    }
+
+    fn set(&mut self, now: Timestamp) -> Result<(), MockError> {
+        if self.broken {
+            return Err(MockError);
+        }
+        self.now = Some(now);
+        Ok(())
+    }
+}
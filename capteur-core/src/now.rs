@@ -0,0 +1,116 @@
+//! Response of the `/now` endpoint of the API, used to set the RTC when SNTP
+//! is disabled or fails.
+
+use protocol::{Timestamp, TimestampError};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+struct ApiResponse<'a> {
+    /// RFC 3339 time, as serialised by chrono: with a fraction of a second
+    /// unless it is zero, in UTC or at the offset of the server.
+    now: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NowError {
+    /// The body is not the expected JSON object.
+    InvalidResponse,
+    /// The time is malformed or out of range.
+    InvalidDateTime(TimestampError),
+}
+
+impl From<TimestampError> for NowError {
+    fn from(err: TimestampError) -> Self {
+        Self::InvalidDateTime(err)
+    }
+}
+
+/// Current time sent by the API in the `body` of a `/now` response.
+pub fn parse_now(body: &[u8]) -> Result<Timestamp, NowError> {
+    let (response, _) =
+        serde_json_core::from_slice::<ApiResponse>(body).map_err(|_| NowError::InvalidResponse)?;
+    Ok(response.now.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now(body: &str) -> Result<Timestamp, NowError> {
+        parse_now(body.as_bytes())
+    }
+
+    #[test]
+    fn test_parse_now() {
+        // `/now` as answered by the server, chrono dropping a zero fraction
+        let expected = Ok(Timestamp::new(2025, 1, 22, 18, 7, 55).unwrap());
+
+        assert_eq!(
+            now(r#"{"now":"2025-01-22T18:07:55.123456789Z","weekday":2}"#),
+            expected
+        );
+        assert_eq!(
+            now(r#"{"now":"2025-01-22T18:07:55.120Z","weekday":2}"#),
+            expected
+        );
+        assert_eq!(
+            now(r#"{"now":"2025-01-22T18:07:55Z","weekday":2}"#),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_offset_is_converted_to_utc() {
+        assert_eq!(
+            now(r#"{"now":"2025-01-01T00:30:00.5+01:00","weekday":3}"#),
+            Ok(Timestamp::new(2024, 12, 31, 23, 30, 0).unwrap())
+        );
+        assert_eq!(
+            now(r#"{"now":"2025-01-22T13:07:55.123-05:00","weekday":3}"#),
+            Ok(Timestamp::new(2025, 1, 22, 18, 7, 55).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_time_zone_is_ignored() {
+        let body = r#"{"now":"2026-10-19T04:13:51.777171328Z","weekday":1,"local_weekday":1,"time_zone":"Europe/Paris","local":"2026-10-19T06:13:51.777171328+02:00","utc_offset":7200,"dst":true,"next_transition":{"at":"2026-10-25T01:00:00Z","utc_offset":3600,"dst":false}}"#;
+
+        assert_eq!(
+            now(body),
+            Ok(Timestamp::new(2026, 10, 19, 4, 13, 51).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        assert_eq!(parse_now(b"Bad Gateway"), Err(NowError::InvalidResponse));
+        assert_eq!(
+            parse_now(br#"{"weekday":6}"#),
+            Err(NowError::InvalidResponse)
+        );
+    }
+
+    #[test]
+    fn test_invalid_date_time() {
+        for (date_time, err) in [
+            ("", TimestampError::InvalidFormat),
+            ("2025-01-05", TimestampError::InvalidFormat),
+            ("2025-01-05T03:04:é5Z", TimestampError::InvalidFormat),
+            ("2025-01-05T03:04:05.Z", TimestampError::InvalidFormat),
+            ("2025-13-05T03:04:05Z", TimestampError::OutOfRange),
+            ("2025-02-29T03:04:05.123Z", TimestampError::OutOfRange),
+            ("2025-01-05T03:04:05", TimestampError::InvalidOffset),
+            ("2025-01-05T03:04:05.123+1", TimestampError::InvalidOffset),
+        ] {
+            let mut body = heapless::String::<64>::new();
+            core::fmt::write(&mut body, format_args!(r#"{{"now":"{date_time}"}}"#)).unwrap();
+
+            assert_eq!(
+                now(&body),
+                Err(NowError::InvalidDateTime(err)),
+                "{date_time}"
+            );
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Verification of the firmware images downloaded over the air.
+//!
+//! The server advertises the latest image of the group of a capteur with a
+//! [`FirmwareManifest`]. The image is signed with Ed25519, the signature
+//! covering `SHA-512(signed_header(version, size) || image)` as described in
+//! [`protocol::firmware::signed_header`]. Images are signed offline with the
+//! `sign_firmware` example of this crate, the server never holds the key.
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use protocol::firmware::signed_header;
+use protocol::{FirmwareManifest, FirmwareVersion};
+use sha2::{Digest, Sha512};
+
+pub type PublicKey = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OtaError {
+    /// The signature or the key is not valid hexadecimal of the right length.
+    InvalidEncoding,
+    /// The image does not fit in the update partition.
+    TooLarge,
+    /// The image received is not of the size announced by the manifest.
+    SizeMismatch,
+    InvalidSignature,
+}
+
+/// Parse a 32 bytes hexadecimal key. Being `const`, an invalid public key
+/// embedded at build time fails the build.
+pub const fn parse_key(hex: &str) -> Result<PublicKey, OtaError> {
+    match decode_hex(hex.as_bytes()) {
+        Some(key) => Ok(key),
+        None => Err(OtaError::InvalidEncoding),
+    }
+}
+
+const fn decode_hex<const N: usize>(hex: &[u8]) -> Option<[u8; N]> {
+    if hex.len() != 2 * N {
+        return None;
+    }
+    let mut bytes = [0; N];
+    let mut i = 0;
+    while i < N {
+        let (Some(high), Some(low)) = (hex_value(hex[2 * i]), hex_value(hex[2 * i + 1])) else {
+            return None;
+        };
+        bytes[i] = high << 4 | low;
+        i += 1;
+    }
+    Some(bytes)
+}
+
+const fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Whether the manifest advertises a firmware newer than `current`. Older
+/// firmwares are never installed, to roll back publish the old image under a
+/// new version.
+pub fn is_update(manifest: &FirmwareManifest, current: FirmwareVersion) -> bool {
+    manifest.version > current
+}
+
+/// Checks an image against its manifest while it is downloaded.
+pub struct ImageVerifier {
+    hasher: Sha512,
+    signature: [u8; 64],
+    size: u32,
+    received: u32,
+}
+
+impl ImageVerifier {
+    /// Start the verification of the image announced by `manifest`, to be
+    /// written to a partition of `capacity` bytes.
+    pub fn new(manifest: &FirmwareManifest, capacity: usize) -> Result<Self, OtaError> {
+        let signature =
+            decode_hex(manifest.signature.as_bytes()).ok_or(OtaError::InvalidEncoding)?;
+        if manifest.size == 0 || manifest.size as usize > capacity {
+            return Err(OtaError::TooLarge);
+        }
+        let mut hasher = Sha512::new();
+        hasher.update(signed_header(manifest.version, manifest.size));
+
+        Ok(Self {
+            hasher,
+            signature,
+            size: manifest.size,
+            received: 0,
+        })
+    }
+
+    /// Bytes of the image received so far.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Feed the next chunk of the image.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<(), OtaError> {
+        let received = u32::try_from(chunk.len())
+            .ok()
+            .and_then(|len| self.received.checked_add(len))
+            .filter(|received| *received <= self.size)
+            .ok_or(OtaError::SizeMismatch)?;
+        self.hasher.update(chunk);
+        self.received = received;
+        Ok(())
+    }
+
+    /// Check the whole image was received and is signed by `public_key`.
+    pub fn verify(self, public_key: &PublicKey) -> Result<(), OtaError> {
+        if self.received != self.size {
+            return Err(OtaError::SizeMismatch);
+        }
+        let key = VerifyingKey::from_bytes(public_key).map_err(|_| OtaError::InvalidEncoding)?;
+        let digest = self.hasher.finalize();
+        key.verify_strict(&digest, &Signature::from_bytes(&self.signature))
+            .map_err(|_| OtaError::InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use heapless::String;
+    use std::fmt::Write;
+    use std::vec::Vec;
+
+    const SEED: [u8; 32] = [7; 32];
+
+    fn version(major: u16, minor: u16, patch: u16) -> FirmwareVersion {
+        FirmwareVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    fn image() -> Vec<u8> {
+        (0..10_000).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn signed_manifest(version: FirmwareVersion, image: &[u8]) -> FirmwareManifest {
+        let mut hasher = Sha512::new();
+        hasher.update(signed_header(version, image.len() as u32));
+        hasher.update(image);
+        let signature = SigningKey::from_bytes(&SEED).sign(&hasher.finalize());
+
+        let mut hex = String::new();
+        for byte in signature.to_bytes() {
+            write!(hex, "{:02x}", byte).unwrap();
+        }
+        FirmwareManifest {
+            version,
+            size: image.len() as u32,
+            signature: hex,
+            url: String::try_from("/firmware/default/0.2.0").unwrap(),
+        }
+    }
+
+    fn public_key() -> PublicKey {
+        SigningKey::from_bytes(&SEED).verifying_key().to_bytes()
+    }
+
+    fn verify(manifest: &FirmwareManifest, image: &[u8]) -> Result<(), OtaError> {
+        let mut verifier = ImageVerifier::new(manifest, 64 * 1024)?;
+        for chunk in image.chunks(4096) {
+            verifier.update(chunk)?;
+        }
+        verifier.verify(&public_key())
+    }
+
+    #[test]
+    fn test_valid_image() {
+        let manifest = signed_manifest(version(0, 2, 0), &image());
+
+        assert_eq!(verify(&manifest, &image()), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_image() {
+        let manifest = signed_manifest(version(0, 2, 0), &image());
+        let mut tampered = image();
+        tampered[1234] ^= 0x80;
+
+        assert_eq!(
+            verify(&manifest, &tampered),
+            Err(OtaError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_signature_binds_the_version() {
+        let mut manifest = signed_manifest(version(0, 2, 0), &image());
+        manifest.version = version(0, 3, 0);
+
+        assert_eq!(verify(&manifest, &image()), Err(OtaError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let manifest = signed_manifest(version(0, 2, 0), &image());
+
+        assert_eq!(
+            verify(&manifest, &image()[..9_000]),
+            Err(OtaError::SizeMismatch)
+        );
+        let mut longer = image();
+        longer.push(0);
+        assert_eq!(verify(&manifest, &longer), Err(OtaError::SizeMismatch));
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        let manifest = signed_manifest(version(0, 2, 0), &image());
+
+        assert!(matches!(
+            ImageVerifier::new(&manifest, 8192),
+            Err(OtaError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_signature_encoding() {
+        let mut manifest = signed_manifest(version(0, 2, 0), &image());
+        manifest.signature.truncate(100);
+
+        assert!(matches!(
+            ImageVerifier::new(&manifest, 64 * 1024),
+            Err(OtaError::InvalidEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_is_update() {
+        let manifest = signed_manifest(version(0, 2, 0), &image());
+
+        assert!(is_update(&manifest, version(0, 1, 9)));
+        assert!(!is_update(&manifest, version(0, 2, 0)));
+        assert!(!is_update(&manifest, version(0, 10, 0)));
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let hex = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        assert_eq!(parse_key(hex).unwrap()[..2], [0xd7, 0x5a]);
+        assert_eq!(parse_key(&hex[..62]), Err(OtaError::InvalidEncoding));
+        assert_eq!(
+            parse_key(&hex.replace('d', "g")),
+            Err(OtaError::InvalidEncoding)
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Encoding of the measures and reports posted to the API.
+
+use protocol::batch::{self, Reading, PIECE_LEN};
+use protocol::measure::MEASURE_JSON_LEN;
+use protocol::{Measure, Timestamp};
+use serde::Serialize;
+
+use crate::config::DeviceConfig;
+use crate::http::{url, HttpClient};
+
+/// How the measures are encoded, the API accepting both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Encoding {
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl Encoding {
+    /// Value of the `Content-Type` header of the body.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => protocol::cbor::CONTENT_TYPE,
+        }
+    }
+}
+
+/// The buffer is too small for the encoded body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EncodeError;
+
+/// Encode `measure` into `buffer`, returning the encoded bytes.
+/// `protocol::measure::MEASURE_JSON_LEN` is large enough for both encodings.
+pub fn encode_measure<'a>(
+    measure: &Measure,
+    encoding: Encoding,
+    buffer: &'a mut [u8],
+) -> Result<&'a [u8], EncodeError> {
+    let len = match encoding {
+        Encoding::Json => serde_json_core::to_slice(measure, buffer).map_err(|_| EncodeError)?,
+        #[cfg(feature = "cbor")]
+        Encoding::Cbor => measure.to_cbor(buffer).map_err(|_| EncodeError)?,
+    };
+    Ok(&buffer[..len])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PostError<H> {
+    /// The URL of the API is too long.
+    InvalidUrl,
+    Encode(EncodeError),
+    /// The server is unreachable or refused the body.
+    Http(H),
+}
+
+/// Post `measure`, encoded as `encoding`, to the API at `api_url`.
+pub async fn post_measure<C: HttpClient>(
+    client: &mut C,
+    api_url: &str,
+    measure: &Measure,
+    encoding: Encoding,
+) -> Result<(), PostError<C::Error>> {
+    let url = url(format_args!("{api_url}/measure")).ok_or(PostError::InvalidUrl)?;
+    let mut buffer = [0; MEASURE_JSON_LEN];
+    let body = encode_measure(measure, encoding, &mut buffer).map_err(PostError::Encode)?;
+    client
+        .post(&url, body, encoding)
+        .await
+        .map_err(PostError::Http)
+}
+
+/// Post `body`, encoded as JSON into `buffer`, to `path` under the capteur
+/// of `device_config`, such as `drift` for `/capteurs/{id}/drift`.
+pub async fn post_json<C: HttpClient, T: Serialize>(
+    client: &mut C,
+    device_config: &DeviceConfig,
+    path: &str,
+    body: &T,
+    buffer: &mut [u8],
+) -> Result<(), PostError<C::Error>> {
+    let url = url(format_args!(
+        "{}/capteurs/{}/{path}",
+        device_config.api_url, device_config.capteur_id
+    ))
+    .ok_or(PostError::InvalidUrl)?;
+    let len =
+        serde_json_core::to_slice(body, buffer).map_err(|_| PostError::Encode(EncodeError))?;
+    client
+        .post(&url, &buffer[..len], Encoding::Json)
+        .await
+        .map_err(PostError::Http)
+}
+
+/// A body too large to be buffered, encoded piece by piece as it is sent.
+pub trait Body {
+    fn encoding(&self) -> Encoding;
+
+    fn pieces(&self) -> usize;
+
+    /// Encode the piece at `index` into `buffer`, of
+    /// `protocol::batch::PIECE_LEN` bytes, returning the encoded bytes.
+    fn piece<'b>(&self, index: usize, buffer: &'b mut [u8]) -> Result<&'b [u8], EncodeError>;
+
+    /// Length of the whole body, encoding every piece once.
+    fn encoded_len(&self) -> Result<usize, EncodeError> {
+        let mut buffer = [0; PIECE_LEN];
+        (0..self.pieces()).try_fold(0, |len, index| {
+            Ok(len + self.piece(index, &mut buffer)?.len())
+        })
+    }
+}
+
+/// Readings posted at once to `POST /measures`, fetched by `reading` as they
+/// are encoded, oldest first.
+pub struct Batch<'a, F> {
+    pub capteur_id: &'a str,
+    /// Time of the capteur when sending the batch.
+    pub sent_at: Timestamp,
+    /// Number of readings.
+    pub len: usize,
+    pub encoding: Encoding,
+    pub reading: F,
+}
+
+impl<F: Fn(usize) -> Option<Reading>> Body for Batch<'_, F> {
+    fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    fn pieces(&self) -> usize {
+        match self.encoding {
+            // The head, the readings and the tail
+            Encoding::Json => self.len + 2,
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => self.len + 1,
+        }
+    }
+
+    fn piece<'b>(&self, index: usize, buffer: &'b mut [u8]) -> Result<&'b [u8], EncodeError> {
+        let len = match (self.encoding, index) {
+            (Encoding::Json, 0) => {
+                batch::json::head(self.capteur_id, self.sent_at, buffer).map_err(|_| EncodeError)?
+            }
+            #[cfg(feature = "cbor")]
+            (Encoding::Cbor, 0) => {
+                batch::cbor::head(self.capteur_id, self.sent_at, self.len, buffer)
+                    .map_err(|_| EncodeError)?
+            }
+            (Encoding::Json, index) if index > self.len => return Ok(batch::json::TAIL),
+            (encoding, index) => {
+                let reading = (self.reading)(index - 1).ok_or(EncodeError)?;
+                match encoding {
+                    Encoding::Json => batch::json::reading(&reading, index - 1, buffer)
+                        .map_err(|_| EncodeError)?,
+                    #[cfg(feature = "cbor")]
+                    Encoding::Cbor => {
+                        batch::cbor::reading(&reading, buffer).map_err(|_| EncodeError)?
+                    }
+                }
+            }
+        };
+        Ok(&buffer[..len])
+    }
+}
+
+/// Post `batch` to the API at `api_url`.
+pub async fn post_batch<C: HttpClient, B: Body>(
+    client: &mut C,
+    api_url: &str,
+    batch: &B,
+) -> Result<(), PostError<C::Error>> {
+    let url = url(format_args!("{api_url}/measures")).ok_or(PostError::InvalidUrl)?;
+    let len = batch.encoded_len().map_err(PostError::Encode)?;
+    client
+        .post_body(&url, batch, len)
+        .await
+        .map_err(PostError::Http)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use protocol::{Aggregate, ConfigAck, Timestamp};
+
+    use super::*;
+    use crate::mock::{block_on, MockError, MockHttp, Request};
+
+    fn measure() -> Measure {
+        Measure {
+            timestamp: Timestamp::new(2025, 1, 5, 3, 4, 5).unwrap(),
+            temperature: 21.5,
+            humidity: 45.,
+            capteur_id: "salon".try_into().unwrap(),
+            samples: Some(3),
+            aggregate: Some(Aggregate {
+                count: 2,
+                duration: 5,
+                mean_temperature: 21.25,
+                min_temperature: 21.,
+                max_temperature: 21.5,
+                mean_humidity: 45.,
+                min_humidity: 45.,
+                max_humidity: 45.,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_encode_json() {
+        let mut buffer = [0; MEASURE_JSON_LEN];
+
+        let body = encode_measure(&measure(), Encoding::Json, &mut buffer).unwrap();
+
+        let (decoded, _) = serde_json_core::from_slice::<Measure>(body).unwrap();
+        assert_eq!(decoded, measure());
+        assert!(body.starts_with(br#"{"timestamp":"2025-01-05T03:04:05Z""#));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_encode_cbor() {
+        let mut buffer = [0; MEASURE_JSON_LEN];
+
+        let body = encode_measure(&measure(), Encoding::Cbor, &mut buffer).unwrap();
+
+        assert_eq!(Measure::from_cbor(body).unwrap(), measure());
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        assert_eq!(
+            encode_measure(&measure(), Encoding::Json, &mut [0; 64]),
+            Err(EncodeError)
+        );
+    }
+
+    #[test]
+    fn test_post_measure() {
+        let mut http = MockHttp::default();
+        http.respond(Ok(b"")).respond(Err(MockError));
+
+        let posted = block_on(post_measure(
+            &mut http,
+            "http://192.168.1.10:3000",
+            &measure(),
+            Encoding::Json,
+        ));
+        let rejected = block_on(post_measure(
+            &mut http,
+            "http://192.168.1.10:3000",
+            &measure(),
+            Encoding::Json,
+        ));
+
+        assert_eq!(posted, Ok(()));
+        assert_eq!(rejected, Err(PostError::Http(MockError)));
+        let mut buffer = [0; MEASURE_JSON_LEN];
+        let body = encode_measure(&measure(), Encoding::Json, &mut buffer).unwrap();
+        assert_eq!(
+            http.requests[0],
+            Request::Post {
+                url: "http://192.168.1.10:3000/measure".into(),
+                body: vec::Vec::from(body),
+                encoding: Encoding::Json,
+            }
+        );
+    }
+
+    fn device_config() -> DeviceConfig {
+        DeviceConfig {
+            networks: heapless::Vec::new(),
+            capteur_id: "salon".try_into().unwrap(),
+            api_url: "http://192.168.1.10:3000".try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_post_json() {
+        let mut http = MockHttp::default();
+        http.respond(Ok(b"")).respond(Err(MockError));
+        let ack = ConfigAck { version: 3 };
+
+        let posted = block_on(post_json(
+            &mut http,
+            &device_config(),
+            "config/ack",
+            &ack,
+            &mut [0; 32],
+        ));
+        let rejected = block_on(post_json(
+            &mut http,
+            &device_config(),
+            "config/ack",
+            &ack,
+            &mut [0; 32],
+        ));
+
+        assert_eq!(posted, Ok(()));
+        assert_eq!(rejected, Err(PostError::Http(MockError)));
+        assert_eq!(
+            http.requests[0],
+            Request::Post {
+                url: "http://192.168.1.10:3000/capteurs/salon/config/ack".into(),
+                body: br#"{"version":3}"#.to_vec(),
+                encoding: Encoding::Json,
+            }
+        );
+    }
+
+    #[test]
+    fn test_post_json_invalid() {
+        let mut http = MockHttp::default();
+        let ack = ConfigAck { version: 3 };
+
+        let too_large = block_on(post_json(
+            &mut http,
+            &device_config(),
+            "config/ack",
+            &ack,
+            &mut [0; 8],
+        ));
+        let invalid_url = block_on(post_json(
+            &mut http,
+            &device_config(),
+            &"config/".repeat(20),
+            &ack,
+            &mut [0; 32],
+        ));
+
+        assert_eq!(too_large, Err(PostError::Encode(EncodeError)));
+        assert_eq!(invalid_url, Err(PostError::InvalidUrl));
+        assert!(http.requests.is_empty());
+    }
+
+    fn reading(index: usize) -> Option<Reading> {
+        (index < 3).then(|| Reading {
+            timestamp: Timestamp::new(2025, 1, 5, 3, 4 + index as u8, 5).unwrap(),
+            temperature: 21.5,
+            humidity: 45.,
+            samples: Some(3),
+        })
+    }
+
+    fn readings_batch(
+        len: usize,
+        encoding: Encoding,
+    ) -> Batch<'static, fn(usize) -> Option<Reading>> {
+        Batch {
+            capteur_id: "salon",
+            sent_at: Timestamp::new(2025, 1, 5, 3, 10, 0).unwrap(),
+            len,
+            encoding,
+            reading,
+        }
+    }
+
+    fn posted_body(http: &MockHttp) -> &[u8] {
+        match &http.requests[0] {
+            Request::Post { body, .. } => body,
+            request => panic!("unexpected request {request:?}"),
+        }
+    }
+
+    #[test]
+    fn test_post_batch() {
+        let mut http = MockHttp::default();
+        http.respond(Ok(b""));
+
+        let posted = block_on(post_batch(
+            &mut http,
+            "http://192.168.1.10:3000",
+            &readings_batch(3, Encoding::Json),
+        ));
+
+        assert_eq!(posted, Ok(()));
+        let body = core::str::from_utf8(posted_body(&http)).unwrap();
+        assert!(body.starts_with(
+            r#"{"capteur_id":"salon","sent_at":"2025-01-05T03:10:00Z","measures":[{"timestamp":"2025-01-05T03:04:05Z""#
+        ));
+        assert!(body.ends_with(r#""timestamp":"2025-01-05T03:06:05Z","temperature":21.5,"humidity":45.0,"samples":3}]}"#));
+        assert!(matches!(
+            &http.requests[0],
+            Request::Post { url, .. } if url == "http://192.168.1.10:3000/measures"
+        ));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_post_batch_cbor() {
+        let mut http = MockHttp::default();
+        http.respond(Ok(b""));
+
+        let posted = block_on(post_batch(
+            &mut http,
+            "http://192.168.1.10:3000",
+            &readings_batch(2, Encoding::Cbor),
+        ));
+
+        assert_eq!(posted, Ok(()));
+        let mut expected = vec::Vec::new();
+        let mut buffer = [0; PIECE_LEN];
+        let len = batch::cbor::head(
+            "salon",
+            readings_batch(2, Encoding::Cbor).sent_at,
+            2,
+            &mut buffer,
+        );
+        expected.extend_from_slice(&buffer[..len.unwrap()]);
+        for index in 0..2 {
+            let len = batch::cbor::reading(&reading(index).unwrap(), &mut buffer).unwrap();
+            expected.extend_from_slice(&buffer[..len]);
+        }
+        assert_eq!(posted_body(&http), expected);
+    }
+
+    #[test]
+    fn test_missing_reading_is_not_posted() {
+        let mut http = MockHttp::default();
+
+        let posted = block_on(post_batch(
+            &mut http,
+            "http://192.168.1.10:3000",
+            &readings_batch(4, Encoding::Json),
+        ));
+
+        assert_eq!(posted, Err(PostError::Encode(EncodeError)));
+        assert!(http.requests.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Tiny HTTP server used to provision a capteur from its access point.
+//!
+//! Only what a browser submitting [`FORM_PAGE`] needs is supported: a request
+//! line, a `Content-Length` header and an `application/x-www-form-urlencoded`
+//! body.
+
+use heapless::{String, Vec};
+
+use crate::config::{DeviceConfig, WifiNetwork, MAX_NETWORKS, WIFI_PASSWORD_LEN};
+use protocol::connection::Ssid;
+
+/// Form served on `GET /`, posting the settings back to `POST /`.
+pub const FORM_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width"><title>Envirometer setup</title></head>
+<body>
+<h1>Envirometer setup</h1>
+<form method="post" action="/">
+<fieldset><legend>Wi-Fi networks, by priority</legend>
+<p><input name="wifi_network" maxlength="32" placeholder="Network" required> <input name="wifi_password" type="password" maxlength="64" placeholder="Password"></p>
+<p><input name="wifi_network" maxlength="32" placeholder="Network"> <input name="wifi_password" type="password" maxlength="64" placeholder="Password"></p>
+<p><input name="wifi_network" maxlength="32" placeholder="Network"> <input name="wifi_password" type="password" maxlength="64" placeholder="Password"></p>
+</fieldset>
+<p><label>Capteur id <input name="capteur_id" maxlength="32" required></label></p>
+<p><label>Server URL <input name="api_url" type="url" maxlength="64" placeholder="https://" required></label></p>
+<p><button type="submit">Save and reboot</button></p>
+</form>
+</body>
+</html>
+"#;
+
+/// Page served once the settings have been saved.
+pub const SAVED_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<body><h1>Settings saved</h1><p>The capteur is rebooting and joining the Wi-Fi network.</p></body>
+</html>
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Method {
+    Get,
+    Post,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub method: Method,
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HttpError {
+    /// More bytes must be read before the request can be parsed.
+    Incomplete,
+    Malformed,
+}
+
+/// Parse the HTTP request read so far in `buffer`.
+pub fn parse_request(buffer: &[u8]) -> Result<Request<'_>, HttpError> {
+    let header_end = buffer
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or(HttpError::Incomplete)?;
+    let head = core::str::from_utf8(&buffer[..header_end]).map_err(|_| HttpError::Malformed)?;
+
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().ok_or(HttpError::Malformed)?.split(' ');
+    let method = match request_line.next() {
+        Some("GET") => Method::Get,
+        Some("POST") => Method::Post,
+        Some(_) => Method::Other,
+        None => return Err(HttpError::Malformed),
+    };
+    let path = request_line.next().ok_or(HttpError::Malformed)?;
+
+    let mut content_length = 0;
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or(HttpError::Malformed)?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse().map_err(|_| HttpError::Malformed)?;
+        }
+    }
+
+    let body = &buffer[header_end + 4..];
+    if body.len() < content_length {
+        return Err(HttpError::Incomplete);
+    }
+
+    Ok(Request {
+        method,
+        path,
+        body: &body[..content_length],
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FormError {
+    MissingField(&'static str),
+    /// The field is too long or not valid UTF-8 once decoded.
+    InvalidField(&'static str),
+}
+
+/// Build a [`DeviceConfig`] from the urlencoded body of the provisioning form.
+///
+/// The form repeats the `wifi_network` and `wifi_password` fields, the n-th
+/// password belongs to the n-th network and networks left empty are skipped.
+pub fn parse_form(body: &[u8]) -> Result<DeviceConfig, FormError> {
+    let mut ssids: Vec<Ssid, MAX_NETWORKS> = Vec::new();
+    let mut passwords: Vec<String<WIFI_PASSWORD_LEN>, MAX_NETWORKS> = Vec::new();
+    let mut capteur_id = None;
+    let mut api_url = None;
+
+    for pair in body.split(|b| *b == b'&') {
+        let mut parts = pair.splitn(2, |b| *b == b'=');
+        let name = parts.next().unwrap_or_default();
+        let value = parts.next().unwrap_or_default();
+        match name {
+            b"wifi_network" => ssids
+                .push(decode_field("wifi_network", value)?)
+                .map_err(|_| FormError::InvalidField("wifi_network"))?,
+            b"wifi_password" => passwords
+                .push(decode_field("wifi_password", value)?)
+                .map_err(|_| FormError::InvalidField("wifi_password"))?,
+            b"capteur_id" => capteur_id = Some(decode_field("capteur_id", value)?),
+            b"api_url" => api_url = Some(decode_field("api_url", value)?),
+            _ => {}
+        }
+    }
+
+    let mut passwords = passwords.into_iter();
+    let networks: Vec<WifiNetwork, MAX_NETWORKS> = ssids
+        .into_iter()
+        .map(|ssid| WifiNetwork {
+            ssid,
+            password: passwords.next().unwrap_or_default(),
+        })
+        .filter(|network| !network.ssid.is_empty())
+        .collect();
+    if networks.is_empty() {
+        return Err(FormError::MissingField("wifi_network"));
+    }
+    let capteur_id: String<_> = capteur_id
+        .filter(|value: &String<_>| !value.is_empty())
+        .ok_or(FormError::MissingField("capteur_id"))?;
+    let mut api_url: String<_> = api_url
+        .filter(|value: &String<_>| !value.is_empty())
+        .ok_or(FormError::MissingField("api_url"))?;
+    if !api_url.starts_with("http://") && !api_url.starts_with("https://") {
+        return Err(FormError::InvalidField("api_url"));
+    }
+    while api_url.ends_with('/') {
+        api_url.pop();
+    }
+
+    Ok(DeviceConfig {
+        networks,
+        capteur_id,
+        api_url,
+    })
+}
+
+/// Decode a `application/x-www-form-urlencoded` value.
+fn decode_field<const N: usize>(name: &'static str, value: &[u8]) -> Result<String<N>, FormError> {
+    let invalid = FormError::InvalidField(name);
+    let mut decoded: Vec<u8, N> = Vec::new();
+    let mut bytes = value.iter();
+    while let Some(byte) = bytes.next() {
+        let byte = match byte {
+            b'+' => b' ',
+            b'%' => {
+                let high = bytes.next().and_then(|b| hex_value(*b)).ok_or(invalid)?;
+                let low = bytes.next().and_then(|b| hex_value(*b)).ok_or(invalid)?;
+                high << 4 | low
+            }
+            byte => *byte,
+        };
+        decoded.push(byte).map_err(|_| invalid)?;
+    }
+    String::from_utf8(decoded).map_err(|_| invalid)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_get_request() {
+        let request = parse_request(b"GET / HTTP/1.1\r\nHost: 192.168.4.1\r\n\r\n");
+
+        assert_eq!(
+            request,
+            Ok(Request {
+                method: Method::Get,
+                path: "/",
+                body: b"",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_post_request() {
+        let request = parse_request(
+            b"POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\ncontent-length: 7\r\n\r\na=b&c=d",
+        );
+
+        assert_eq!(
+            request,
+            Ok(Request {
+                method: Method::Post,
+                path: "/",
+                body: b"a=b&c=d",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_incomplete_request() {
+        assert_eq!(
+            parse_request(b"GET / HTTP/1.1\r\nHost: 192.16"),
+            Err(HttpError::Incomplete)
+        );
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\nContent-Length: 7\r\n\r\na=b"),
+            Err(HttpError::Incomplete)
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_request() {
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\nContent-Length: seven\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_parse_form() {
+        let config = parse_form(
+            b"wifi_network=Ma+maison&wifi_password=p%40ss%26word&capteur_id=salon&api_url=https%3A%2F%2Fenvirometer.local%2F",
+        )
+        .unwrap();
+
+        assert_eq!(config.networks.len(), 1);
+        assert_eq!(config.networks[0].ssid, "Ma maison");
+        assert_eq!(config.networks[0].password, "p@ss&word");
+        assert_eq!(config.capteur_id, "salon");
+        assert_eq!(config.api_url, "https://envirometer.local");
+    }
+
+    #[test]
+    fn test_parse_form_open_network() {
+        let config = parse_form(
+            b"wifi_network=guest&wifi_password=&capteur_id=salon&api_url=http%3A%2F%2F192.168.1.2",
+        )
+        .unwrap();
+
+        assert_eq!(config.networks[0].password, "");
+    }
+
+    #[test]
+    fn test_parse_form_several_networks() {
+        let config = parse_form(
+            b"wifi_network=maison&wifi_password=secret&wifi_network=&wifi_password=&wifi_network=guest&wifi_password=&capteur_id=salon&api_url=http%3A%2F%2Fa",
+        )
+        .unwrap();
+
+        assert_eq!(config.networks.len(), 2);
+        assert_eq!(config.networks[0].ssid, "maison");
+        assert_eq!(config.networks[0].password, "secret");
+        assert_eq!(config.networks[1].ssid, "guest");
+        assert_eq!(config.networks[1].password, "");
+    }
+
+    #[test]
+    fn test_parse_form_errors() {
+        assert_eq!(
+            parse_form(b"wifi_network=&capteur_id=salon&api_url=http%3A%2F%2Fa"),
+            Err(FormError::MissingField("wifi_network"))
+        );
+        assert_eq!(
+            parse_form(b"wifi_network=a&wifi_network=b&wifi_network=c&wifi_network=d&capteur_id=salon&api_url=http%3A%2F%2Fa"),
+            Err(FormError::InvalidField("wifi_network"))
+        );
+        assert_eq!(
+            parse_form(b"wifi_network=maison&capteur_id=salon&api_url=ftp%3A%2F%2Fa"),
+            Err(FormError::InvalidField("api_url"))
+        );
+        assert_eq!(
+            parse_form(b"wifi_network=maison&capteur_id=sal%2&api_url=http%3A%2F%2Fa"),
+            Err(FormError::InvalidField("capteur_id"))
+        );
+        assert_eq!(
+            parse_form(
+                b"wifi_network=a-network-name-longer-than-32-bytes&capteur_id=salon&api_url=http%3A%2F%2Fa"
+            ),
+            Err(FormError::InvalidField("wifi_network"))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Why the capteur rebooted.
+//!
+//! Before resetting itself, the firmware records the reason in two registers
+//! surviving the reset, the watchdog scratch registers of the RP2040. At the
+//! next boot, [`reboot_report`] combines that record with the reset flags of
+//! the chip.
+
+use protocol::{CriticalTask, RebootReason, RebootReport};
+
+/// Marks a valid record in the upper half of the first register, which is
+/// random after a power-up.
+const RECORD_MAGIC: u32 = 0x5ca7_0000;
+const MAGIC_MASK: u32 = 0xffff_0000;
+
+/// Reset flags of the chip, read at boot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResetFlags {
+    /// The watchdog expired.
+    pub watchdog_timeout: bool,
+    /// The watchdog was triggered on purpose.
+    pub watchdog_forced: bool,
+    /// Power-on or brown-out reset.
+    pub power_on: bool,
+    /// The RUN pin was pulled low.
+    pub run_pin: bool,
+    /// Reset from the debug port.
+    pub debugger: bool,
+}
+
+fn reason_code(reason: RebootReason) -> u32 {
+    match reason {
+        RebootReason::PowerOn => 1,
+        RebootReason::ResetPin => 2,
+        RebootReason::Debugger => 3,
+        RebootReason::Watchdog => 4,
+        RebootReason::Panic => 5,
+        RebootReason::FirmwareUpdate => 6,
+        RebootReason::Rollback => 7,
+        RebootReason::Requested => 8,
+        RebootReason::Unknown => 9,
+    }
+}
+
+fn reason_from_code(code: u32) -> Option<RebootReason> {
+    Some(match code {
+        1 => RebootReason::PowerOn,
+        2 => RebootReason::ResetPin,
+        3 => RebootReason::Debugger,
+        4 => RebootReason::Watchdog,
+        5 => RebootReason::Panic,
+        6 => RebootReason::FirmwareUpdate,
+        7 => RebootReason::Rollback,
+        8 => RebootReason::Requested,
+        9 => RebootReason::Unknown,
+        _ => return None,
+    })
+}
+
+fn task_code(task: Option<CriticalTask>) -> u32 {
+    match task {
+        None => 0,
+        Some(CriticalTask::Measure) => 1,
+        Some(CriticalTask::Network) => 2,
+        Some(CriticalTask::Post) => 3,
+    }
+}
+
+fn task_from_code(code: u32) -> Option<Option<CriticalTask>> {
+    Some(match code {
+        0 => None,
+        1 => Some(CriticalTask::Measure),
+        2 => Some(CriticalTask::Network),
+        3 => Some(CriticalTask::Post),
+        _ => return None,
+    })
+}
+
+/// Registers recording that the firmware, up for `uptime` seconds, is about
+/// to reset because of `reason`.
+pub fn encode_record(reason: RebootReason, task: Option<CriticalTask>, uptime: u64) -> [u32; 2] {
+    [
+        RECORD_MAGIC | task_code(task) << 8 | reason_code(reason),
+        u32::try_from(uptime).unwrap_or(u32::MAX),
+    ]
+}
+
+/// Why the capteur rebooted, from the registers written by [`encode_record`]
+/// before the reset, if any, and the reset flags.
+pub fn reboot_report(record: [u32; 2], flags: ResetFlags) -> RebootReport {
+    if record[0] & MAGIC_MASK == RECORD_MAGIC {
+        if let (Some(reason), Some(task)) = (
+            reason_from_code(record[0] & 0xff),
+            task_from_code(record[0] >> 8 & 0xff),
+        ) {
+            return RebootReport {
+                reason,
+                task,
+                uptime: Some(record[1]),
+            };
+        }
+    }
+
+    // The chip-level resets clear the watchdog flags but leave their own
+    // until the next one, so the watchdog flags go first.
+    let reason = if flags.watchdog_timeout {
+        RebootReason::Watchdog
+    } else if flags.watchdog_forced {
+        RebootReason::Requested
+    } else if flags.debugger {
+        RebootReason::Debugger
+    } else if flags.run_pin {
+        RebootReason::ResetPin
+    } else if flags.power_on {
+        RebootReason::PowerOn
+    } else {
+        RebootReason::Unknown
+    };
+    RebootReport {
+        reason,
+        task: None,
+        uptime: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_reason() {
+        let record = encode_record(RebootReason::Watchdog, Some(CriticalTask::Post), 3600);
+        let flags = ResetFlags {
+            watchdog_timeout: true,
+            power_on: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            reboot_report(record, flags),
+            RebootReport {
+                reason: RebootReason::Watchdog,
+                task: Some(CriticalTask::Post),
+                uptime: Some(3600),
+            }
+        );
+    }
+
+    #[test]
+    fn test_every_reason_round_trips() {
+        for code in 1..=9 {
+            let reason = reason_from_code(code).unwrap();
+            let record = encode_record(reason, None, 0);
+
+            assert_eq!(reboot_report(record, ResetFlags::default()).reason, reason);
+        }
+    }
+
+    #[test]
+    fn test_unrecorded_watchdog_reset() {
+        let flags = ResetFlags {
+            watchdog_timeout: true,
+            power_on: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            reboot_report([0x1234_5678, 42], flags),
+            RebootReport {
+                reason: RebootReason::Watchdog,
+                task: None,
+                uptime: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_power_on() {
+        let flags = ResetFlags {
+            power_on: true,
+            ..Default::default()
+        };
+
+        assert_eq!(reboot_report([0, 0], flags).reason, RebootReason::PowerOn);
+        assert_eq!(
+            reboot_report([0, 0], ResetFlags::default()).reason,
+            RebootReason::Unknown
+        );
+    }
+
+    #[test]
+    fn test_corrupted_record_is_ignored() {
+        let flags = ResetFlags {
+            run_pin: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            reboot_report([RECORD_MAGIC | 0xff, 0], flags).reason,
+            RebootReason::ResetPin
+        );
+    }
+
+    #[test]
+    fn test_uptime_saturates() {
+        let record = encode_record(RebootReason::Panic, None, u64::MAX);
+
+        assert_eq!(
+            reboot_report(record, ResetFlags::default()).uptime,
+            Some(u32::MAX)
+        );
+    }
+}
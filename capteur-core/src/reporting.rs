@@ -0,0 +1,278 @@
+//! Reporting policy: readings are taken at the sensor interval, but only some
+//! of them are sent, with the statistics of the readings since the previous
+//! one sent. A reading is sent once the aggregation period is over, as soon
+//! as it moved by more than the deadband, or after the longest silence
+//! allowed, whichever comes first.
+
+use protocol::Aggregate;
+
+/// Change of the readings worth sending right away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadband {
+    /// In °C.
+    pub temperature: f64,
+    /// In %.
+    pub humidity: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportingPolicy {
+    /// Seconds over which readings are aggregated, only changes being sent
+    /// when `None`.
+    pub period: Option<u64>,
+    /// Changes since the last reading sent to send right away, if any.
+    pub deadband: Option<Deadband>,
+    /// Longest time without sending any reading, in seconds.
+    pub max_silence: u64,
+}
+
+/// Statistics of the readings not sent yet.
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    count: u16,
+    first_at: u64,
+    temperature_sum: f64,
+    min_temperature: f64,
+    max_temperature: f64,
+    humidity_sum: f64,
+    min_humidity: f64,
+    max_humidity: f64,
+}
+
+impl Accumulator {
+    fn new(now: u64, temperature: f64, humidity: f64) -> Self {
+        Self {
+            count: 1,
+            first_at: now,
+            temperature_sum: temperature,
+            min_temperature: temperature,
+            max_temperature: temperature,
+            humidity_sum: humidity,
+            min_humidity: humidity,
+            max_humidity: humidity,
+        }
+    }
+
+    fn add(&mut self, temperature: f64, humidity: f64) {
+        // Beyond `u16::MAX` readings, the mean is taken over the first ones
+        // only.
+        let Some(count) = self.count.checked_add(1) else {
+            return;
+        };
+        self.count = count;
+        self.temperature_sum += temperature;
+        self.min_temperature = self.min_temperature.min(temperature);
+        self.max_temperature = self.max_temperature.max(temperature);
+        self.humidity_sum += humidity;
+        self.min_humidity = self.min_humidity.min(humidity);
+        self.max_humidity = self.max_humidity.max(humidity);
+    }
+
+    fn aggregate(&self, now: u64) -> Aggregate {
+        let count = f64::from(self.count);
+        Aggregate {
+            count: self.count,
+            duration: u32::try_from(now.saturating_sub(self.first_at)).unwrap_or(u32::MAX),
+            mean_temperature: (self.temperature_sum / count) as f32,
+            min_temperature: self.min_temperature as f32,
+            max_temperature: self.max_temperature as f32,
+            mean_humidity: (self.humidity_sum / count) as f32,
+            min_humidity: self.min_humidity as f32,
+            max_humidity: self.max_humidity as f32,
+        }
+    }
+}
+
+/// Decides which readings to send, following a [`ReportingPolicy`].
+#[derive(Debug, Clone)]
+pub struct Reporter {
+    policy: ReportingPolicy,
+    pending: Option<Accumulator>,
+    /// When the last reading was sent, and its temperature and humidity.
+    last_sent: Option<(u64, f64, f64)>,
+}
+
+impl Reporter {
+    pub fn new(policy: ReportingPolicy) -> Self {
+        Self {
+            policy,
+            pending: None,
+            last_sent: None,
+        }
+    }
+
+    fn moved(&self, temperature: f64, humidity: f64) -> bool {
+        let (Some(deadband), Some((_, last_temperature, last_humidity))) =
+            (self.policy.deadband, self.last_sent)
+        else {
+            return false;
+        };
+        (temperature - last_temperature).abs() > deadband.temperature
+            || (humidity - last_humidity).abs() > deadband.humidity
+    }
+
+    fn is_due(&self, now: u64) -> bool {
+        let Some((sent_at, _, _)) = self.last_sent else {
+            // The first reading is sent right away
+            return true;
+        };
+        let interval = self
+            .policy
+            .period
+            .map_or(self.policy.max_silence, |period| {
+                period.min(self.policy.max_silence)
+            });
+        now.saturating_sub(sent_at) >= interval
+    }
+
+    /// A reading was taken at `now`, in seconds. Returns the statistics of the
+    /// readings since the last one sent, this one included, when it is to be
+    /// sent.
+    pub fn reading(&mut self, now: u64, temperature: f64, humidity: f64) -> Option<Aggregate> {
+        match &mut self.pending {
+            Some(pending) => pending.add(temperature, humidity),
+            None => self.pending = Some(Accumulator::new(now, temperature, humidity)),
+        }
+        if !self.is_due(now) && !self.moved(temperature, humidity) {
+            return None;
+        }
+
+        self.last_sent = Some((now, temperature, humidity));
+        self.pending.take().map(|pending| pending.aggregate(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: ReportingPolicy = ReportingPolicy {
+        period: Some(300),
+        deadband: Some(Deadband {
+            temperature: 0.5,
+            humidity: 3.,
+        }),
+        max_silence: 3600,
+    };
+
+    #[test]
+    fn test_first_reading_is_sent() {
+        let mut reporter = Reporter::new(POLICY);
+
+        assert_eq!(
+            reporter.reading(0, 21., 45.),
+            Some(Aggregate {
+                count: 1,
+                duration: 0,
+                mean_temperature: 21.,
+                min_temperature: 21.,
+                max_temperature: 21.,
+                mean_humidity: 45.,
+                min_humidity: 45.,
+                max_humidity: 45.,
+            })
+        );
+    }
+
+    #[test]
+    fn test_aggregate_sent_every_period() {
+        let mut reporter = Reporter::new(POLICY);
+        reporter.reading(0, 21., 45.);
+
+        for now in (5..300).step_by(5) {
+            let temperature = if now % 10 == 0 { 21.2 } else { 21. };
+            assert_eq!(reporter.reading(now, temperature, 46.), None);
+        }
+
+        assert_eq!(
+            reporter.reading(300, 21.4, 44.),
+            Some(Aggregate {
+                count: 60,
+                duration: 295,
+                mean_temperature: 21.103333,
+                min_temperature: 21.,
+                max_temperature: 21.4,
+                mean_humidity: 45.966667,
+                min_humidity: 44.,
+                max_humidity: 46.,
+            })
+        );
+        assert_eq!(reporter.reading(305, 21.4, 44.), None);
+    }
+
+    #[test]
+    fn test_change_beyond_deadband_is_sent_right_away() {
+        let mut reporter = Reporter::new(POLICY);
+        reporter.reading(0, 21., 45.);
+        assert_eq!(reporter.reading(5, 21.5, 47.9), None);
+
+        let aggregate = reporter.reading(10, 21.6, 45.).unwrap();
+        assert_eq!(aggregate.count, 2);
+        assert_eq!(aggregate.duration, 5);
+        assert_eq!(aggregate.max_temperature, 21.6);
+
+        // The reference is now the reading sent
+        assert_eq!(reporter.reading(15, 21.2, 45.), None);
+        assert!(reporter.reading(20, 21.6, 41.9).is_some());
+    }
+
+    #[test]
+    fn test_max_silence_without_period() {
+        let mut reporter = Reporter::new(ReportingPolicy {
+            period: None,
+            ..POLICY
+        });
+        reporter.reading(0, 21., 45.);
+        for now in (60..3600).step_by(60) {
+            assert_eq!(reporter.reading(now, 21., 45.), None);
+        }
+
+        let aggregate = reporter.reading(3600, 21., 45.).unwrap();
+        assert_eq!(aggregate.count, 60);
+        assert_eq!(aggregate.duration, 3540);
+    }
+
+    #[test]
+    fn test_max_silence_shorter_than_period() {
+        let mut reporter = Reporter::new(ReportingPolicy {
+            period: Some(3600),
+            deadband: None,
+            max_silence: 600,
+        });
+        reporter.reading(0, 21., 45.);
+
+        assert_eq!(reporter.reading(599, 30., 90.), None);
+        assert!(reporter.reading(600, 21., 45.).is_some());
+    }
+
+    #[test]
+    fn test_every_reading_sent_with_zero_period() {
+        let mut reporter = Reporter::new(ReportingPolicy {
+            period: Some(0),
+            deadband: None,
+            max_silence: 3600,
+        });
+
+        for now in (0..30).step_by(5) {
+            assert_eq!(reporter.reading(now, 21., 45.).unwrap().count, 1);
+        }
+    }
+
+    #[test]
+    fn test_count_saturates() {
+        let mut reporter = Reporter::new(ReportingPolicy {
+            period: None,
+            deadband: None,
+            max_silence: u64::MAX,
+        });
+        reporter.reading(0, 21., 45.);
+        for now in 1..=u64::from(u16::MAX) + 10 {
+            assert_eq!(reporter.reading(now, 21., 45.), None);
+        }
+
+        let aggregate = reporter.reading(u64::MAX, 21., 45.).unwrap();
+        assert_eq!(aggregate.count, u16::MAX);
+        assert_eq!(aggregate.duration, u32::MAX);
+        assert_eq!(aggregate.mean_temperature, 21.);
+    }
+}
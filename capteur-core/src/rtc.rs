@@ -0,0 +1,74 @@
+//! Real-time clock of the capteur, implemented by the firmware over the
+//! RP2040 one.
+
+use protocol::Timestamp;
+
+pub trait Rtc {
+    type Error;
+
+    /// Current time, `None` while the clock is not running.
+    fn now(&self) -> Option<Timestamp>;
+
+    /// Set the clock to `now` and start it.
+    fn set(&mut self, now: Timestamp) -> Result<(), Self::Error>;
+}
+
+/// Day of the week kept by the RTC, besides the date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    /// Day numbered from 0 for Sunday to 6 for Saturday, as by
+    /// [`Timestamp::weekday`].
+    pub fn from_u8(day: u8) -> Option<Self> {
+        Some(match day {
+            0 => Self::Sunday,
+            1 => Self::Monday,
+            2 => Self::Tuesday,
+            3 => Self::Wednesday,
+            4 => Self::Thursday,
+            5 => Self::Friday,
+            6 => Self::Saturday,
+            _ => return None,
+        })
+    }
+
+    /// Day of the week of `timestamp`.
+    pub fn of(timestamp: &Timestamp) -> Self {
+        // `Timestamp::weekday` is always below 7
+        Self::from_u8(timestamp.weekday()).unwrap_or(Self::Sunday)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weekday_from_u8() {
+        assert_eq!(Weekday::from_u8(0), Some(Weekday::Sunday));
+        assert_eq!(Weekday::from_u8(1), Some(Weekday::Monday));
+        assert_eq!(Weekday::from_u8(6), Some(Weekday::Saturday));
+        assert_eq!(Weekday::from_u8(7), None);
+    }
+
+    #[test]
+    fn test_weekday_of_timestamp() {
+        let weekday = |day| Weekday::of(&Timestamp::new(2025, 1, day, 12, 0, 0).unwrap());
+
+        // The first week of 2025, from Sunday the 5th
+        assert_eq!(weekday(5), Weekday::Sunday);
+        assert_eq!(weekday(6), Weekday::Monday);
+        assert_eq!(weekday(10), Weekday::Friday);
+        assert_eq!(weekday(11), Weekday::Saturday);
+    }
+}
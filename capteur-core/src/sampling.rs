@@ -0,0 +1,221 @@
+//! Filtering of the sensor readings: several samples are taken for each
+//! measure, failed reads being retried, and their median is reported so that
+//! a single spurious value does not reach the server.
+
+use core::ops::RangeInclusive;
+
+use heapless::Vec;
+
+/// Valid samples wanted for each measure.
+pub const SAMPLES_PER_MEASURE: usize = 3;
+/// Reads attempted for each measure, failed ones included.
+pub const MAX_ATTEMPTS: usize = 5;
+
+/// Temperatures the AM2301 can measure, in °C.
+pub const TEMPERATURE_RANGE: RangeInclusive<f64> = -40.0..=80.0;
+/// Relative humidities the AM2301 can measure, in %.
+pub const HUMIDITY_RANGE: RangeInclusive<f64> = 0.0..=100.0;
+
+/// A single read of the sensor, with a valid checksum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub temperature: f64,
+    pub humidity: f64,
+}
+
+impl Sample {
+    /// Whether the sensor can actually measure these values, corrupted reads
+    /// sometimes having a valid checksum.
+    pub fn is_plausible(&self) -> bool {
+        TEMPERATURE_RANGE.contains(&self.temperature) && HUMIDITY_RANGE.contains(&self.humidity)
+    }
+}
+
+/// Median of the samples of a measure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Filtered {
+    pub temperature: f64,
+    pub humidity: f64,
+    /// Number of plausible samples the median is taken from.
+    pub samples: u8,
+}
+
+/// Median of `values`, the mean of the two middle ones for an even count.
+fn median<const N: usize>(mut values: Vec<f64, N>) -> Option<f64> {
+    values.sort_unstable_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    match values.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(values[middle]),
+        _ => Some((values[middle - 1] + values[middle]) / 2.),
+    }
+}
+
+/// Median temperature and humidity of the plausible `samples`, taken
+/// independently, `None` when none is plausible.
+pub fn median_filter<const N: usize>(samples: &[Sample]) -> Option<Filtered> {
+    let plausible = || {
+        samples
+            .iter()
+            .filter(|sample| sample.is_plausible())
+            .take(N)
+    };
+    let temperatures: Vec<f64, N> = plausible().map(|sample| sample.temperature).collect();
+    let humidities: Vec<f64, N> = plausible().map(|sample| sample.humidity).collect();
+
+    Some(Filtered {
+        temperature: median(temperatures)?,
+        humidity: median(humidities)?,
+        samples: plausible().count() as u8,
+    })
+}
+
+/// Samples of the measure being taken.
+#[derive(Debug, Clone, Default)]
+pub struct Sampler {
+    samples: Vec<Sample, SAMPLES_PER_MEASURE>,
+    attempts: usize,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads attempted so far.
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// A read succeeded, returns whether its values are plausible, the
+    /// implausible ones being discarded like failed reads.
+    pub fn read(&mut self, sample: Sample) -> bool {
+        self.attempts += 1;
+        sample.is_plausible() && self.samples.push(sample).is_ok()
+    }
+
+    /// A read failed, on a timeout or an invalid checksum.
+    pub fn failed(&mut self) {
+        self.attempts += 1;
+    }
+
+    /// Whether enough samples were taken, or too many reads attempted.
+    pub fn is_done(&self) -> bool {
+        self.samples.is_full() || self.attempts >= MAX_ATTEMPTS
+    }
+
+    /// The measure, `None` when every read failed.
+    pub fn filtered(&self) -> Option<Filtered> {
+        median_filter::<SAMPLES_PER_MEASURE>(&self.samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(temperature: f64, humidity: f64) -> Sample {
+        Sample {
+            temperature,
+            humidity,
+        }
+    }
+
+    #[test]
+    fn test_median_rejects_spurious_value() {
+        let samples = [sample(21.3, 45.8), sample(79.9, 2.1), sample(21.4, 45.6)];
+
+        assert_eq!(
+            median_filter::<3>(&samples),
+            Some(Filtered {
+                temperature: 21.4,
+                humidity: 45.6,
+                samples: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_median_of_even_count() {
+        let samples = [sample(21., 40.), sample(22., 42.)];
+
+        assert_eq!(
+            median_filter::<3>(&samples),
+            Some(Filtered {
+                temperature: 21.5,
+                humidity: 41.,
+                samples: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_implausible_samples_are_ignored() {
+        let samples = [sample(-3276.7, 45.), sample(20., 6553.5), sample(19.5, 50.)];
+
+        assert_eq!(
+            median_filter::<3>(&samples),
+            Some(Filtered {
+                temperature: 19.5,
+                humidity: 50.,
+                samples: 1,
+            })
+        );
+        assert_eq!(median_filter::<3>(&samples[..2]), None);
+        assert_eq!(median_filter::<3>(&[]), None);
+    }
+
+    #[test]
+    fn test_sensor_range_bounds_are_plausible() {
+        assert!(sample(-40., 0.).is_plausible());
+        assert!(sample(80., 100.).is_plausible());
+        assert!(!sample(f64::NAN, 50.).is_plausible());
+    }
+
+    #[test]
+    fn test_sampler_stops_once_enough_samples() {
+        let mut sampler = Sampler::new();
+        for temperature in [21., 23., 22.] {
+            assert!(!sampler.is_done());
+            assert!(sampler.read(sample(temperature, 50.)));
+        }
+
+        assert!(sampler.is_done());
+        assert_eq!(sampler.attempts(), 3);
+        assert_eq!(sampler.filtered().unwrap().temperature, 22.);
+    }
+
+    #[test]
+    fn test_sampler_retries_failed_reads() {
+        let mut sampler = Sampler::new();
+        sampler.failed();
+        assert!(!sampler.read(sample(150., 50.)));
+        assert!(sampler.read(sample(21., 50.)));
+        sampler.failed();
+        assert!(!sampler.is_done());
+        assert!(sampler.read(sample(22., 52.)));
+
+        assert!(sampler.is_done());
+        assert_eq!(
+            sampler.filtered(),
+            Some(Filtered {
+                temperature: 21.5,
+                humidity: 51.,
+                samples: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sampler_gives_up_after_max_attempts() {
+        let mut sampler = Sampler::new();
+        for _ in 0..MAX_ATTEMPTS {
+            assert!(!sampler.is_done());
+            sampler.failed();
+        }
+
+        assert!(sampler.is_done());
+        assert_eq!(sampler.filtered(), None);
+    }
+}
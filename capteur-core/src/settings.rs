@@ -0,0 +1,108 @@
+//! Settings pushed by the server, persisted in their own flash sector so that
+//! they still apply after a reboot without network.
+//!
+//! The block is framed as described in [`crate::block`], with the magic
+//! `ENVS`. The payload is the settings document, as JSON.
+
+use protocol::CapteurSettings;
+
+use crate::block::{self, CRC_LEN, HEADER_LEN};
+use crate::config::ConfigError;
+
+/// Size of the buffer needed to encode any [`CapteurSettings`].
+pub const SETTINGS_BLOCK_LEN: usize = 256;
+
+const MAGIC: &[u8; 4] = b"ENVS";
+const VERSION: u8 = 1;
+
+pub fn encode(settings: &CapteurSettings, buffer: &mut [u8]) -> Result<usize, ConfigError> {
+    if buffer.len() < HEADER_LEN + CRC_LEN {
+        return Err(ConfigError::BufferTooSmall);
+    }
+    let payload_end = buffer.len() - CRC_LEN;
+    let payload_len = serde_json_core::to_slice(settings, &mut buffer[HEADER_LEN..payload_end])
+        .map_err(|_| ConfigError::BufferTooSmall)?;
+
+    block::seal(buffer, MAGIC, VERSION, payload_len)
+}
+
+/// Decode settings saved by [`encode`], rejecting settings out of bounds.
+pub fn decode(block: &[u8]) -> Result<CapteurSettings, ConfigError> {
+    let (_, payload) = block::open(block, MAGIC, &[VERSION])?;
+
+    match serde_json_core::from_slice::<CapteurSettings>(payload) {
+        Ok((settings, _)) if settings.is_valid() => Ok(settings),
+        _ => Err(ConfigError::InvalidField),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::settings::{MAX_MEASURE_INTERVAL, MAX_UPLOAD_BATCH_LEN};
+
+    fn settings() -> CapteurSettings {
+        CapteurSettings {
+            version: 7,
+            measure_interval: Some(60),
+            upload_batch_len: Some(24),
+            verbose: true,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+        encode(&settings(), &mut block).unwrap();
+
+        assert_eq!(&block[..6], b"ENVS\x01\x00");
+        assert_eq!(decode(&block), Ok(settings()));
+    }
+
+    #[test]
+    fn test_largest_settings_fit_in_block() {
+        let settings = CapteurSettings {
+            version: u32::MAX,
+            measure_interval: Some(MAX_MEASURE_INTERVAL),
+            upload_batch_len: Some(MAX_UPLOAD_BATCH_LEN),
+            verbose: false,
+        };
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+
+        assert!(encode(&settings, &mut block).is_ok());
+    }
+
+    #[test]
+    fn test_decode_erased_flash() {
+        assert_eq!(decode(&[0xff; SETTINGS_BLOCK_LEN]), Err(ConfigError::Empty));
+    }
+
+    #[test]
+    fn test_decode_device_config_block() {
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+        block[..4].copy_from_slice(b"ENVC");
+
+        assert_eq!(decode(&block), Err(ConfigError::InvalidMagic));
+    }
+
+    #[test]
+    fn test_decode_corrupted_block() {
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+        encode(&settings(), &mut block).unwrap();
+        block[20] ^= 0x01;
+
+        assert_eq!(decode(&block), Err(ConfigError::InvalidChecksum));
+    }
+
+    #[test]
+    fn test_decode_invalid_settings() {
+        let invalid = CapteurSettings {
+            measure_interval: Some(1),
+            ..settings()
+        };
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+        encode(&invalid, &mut block).unwrap();
+
+        assert_eq!(decode(&block), Err(ConfigError::InvalidField));
+    }
+}
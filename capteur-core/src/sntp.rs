@@ -0,0 +1,205 @@
+//! Minimal SNTP (RFC 4330) client packets.
+//!
+//! The capteur has no notion of time before its first synchronisation, so the
+//! request carries a random nonce as transmit timestamp instead of the local
+//! time. The server echoes it back as origin timestamp, which lets us discard
+//! stray or spoofed responses.
+
+use protocol::{Timestamp, TimestampError};
+
+pub const NTP_PORT: u16 = 123;
+pub const NTP_PACKET_LEN: usize = 48;
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const LEAP_NOT_SYNCHRONIZED: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SntpError {
+    /// The packet is shorter than an NTP header.
+    TooShort,
+    /// The packet is not a server response.
+    InvalidMode,
+    /// The server does not answer the request we sent.
+    OriginMismatch,
+    /// The server is not synchronised (leap indicator alarm or stratum 0).
+    Unsynchronized,
+    /// The transmit timestamp cannot be represented as a [`Timestamp`].
+    InvalidTimestamp,
+}
+
+impl From<TimestampError> for SntpError {
+    fn from(_: TimestampError) -> Self {
+        Self::InvalidTimestamp
+    }
+}
+
+/// Build a client request using `nonce` as transmit timestamp.
+pub fn request(nonce: u64) -> [u8; NTP_PACKET_LEN] {
+    let mut packet = [0; NTP_PACKET_LEN];
+    packet[0] = (VERSION << 3) | MODE_CLIENT;
+    packet[40..48].copy_from_slice(&nonce.to_be_bytes());
+    packet
+}
+
+/// Time reported by an NTP server, in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServerTime {
+    pub unix_millis: i64,
+    pub stratum: u8,
+}
+
+impl ServerTime {
+    /// Server time once corrected by half of the measured round trip delay,
+    /// rounded to the nearest second.
+    pub fn to_timestamp(&self, round_trip_millis: u64) -> Result<Timestamp, SntpError> {
+        let millis = self.unix_millis + (round_trip_millis / 2) as i64;
+        Ok(Timestamp::from_unix((millis + 500).div_euclid(1000))?)
+    }
+}
+
+/// Parse and validate the server response to the request built with `nonce`.
+pub fn parse_response(packet: &[u8], nonce: u64) -> Result<ServerTime, SntpError> {
+    if packet.len() < NTP_PACKET_LEN {
+        return Err(SntpError::TooShort);
+    }
+
+    let leap = packet[0] >> 6;
+    let mode = packet[0] & 0b111;
+    let stratum = packet[1];
+    if mode != MODE_SERVER {
+        return Err(SntpError::InvalidMode);
+    }
+    if read_u64(packet, 24) != nonce {
+        return Err(SntpError::OriginMismatch);
+    }
+    if leap == LEAP_NOT_SYNCHRONIZED || stratum == 0 || stratum > 15 {
+        return Err(SntpError::Unsynchronized);
+    }
+
+    let seconds = read_u32(packet, 40);
+    let fraction = read_u32(packet, 44);
+    if seconds == 0 && fraction == 0 {
+        return Err(SntpError::Unsynchronized);
+    }
+
+    Ok(ServerTime {
+        unix_millis: ntp_to_unix_seconds(seconds) * 1000 + ((fraction as u64 * 1000) >> 32) as i64,
+        stratum,
+    })
+}
+
+/// NTP seconds wrap around in 2036: following RFC 4330 section 3, values with
+/// the most significant bit cleared belong to the next era.
+fn ntp_to_unix_seconds(seconds: u32) -> i64 {
+    let seconds = if seconds & 0x8000_0000 == 0 {
+        seconds as i64 + (1 << 32)
+    } else {
+        seconds as i64
+    };
+    seconds - NTP_UNIX_OFFSET
+}
+
+fn read_u32(packet: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&packet[offset..offset + 4]);
+    u32::from_be_bytes(bytes)
+}
+
+fn read_u64(packet: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&packet[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: u64 = 0x0123_4567_89ab_cdef;
+
+    /// Response of a stratum 2 server at 2025-01-22T18:07:55.250Z.
+    fn response() -> [u8; NTP_PACKET_LEN] {
+        let mut packet = [0; NTP_PACKET_LEN];
+        packet[0] = (VERSION << 3) | MODE_SERVER;
+        packet[1] = 2;
+        packet[24..32].copy_from_slice(&NONCE.to_be_bytes());
+        let seconds = (1_737_569_275 + NTP_UNIX_OFFSET) as u32;
+        packet[40..44].copy_from_slice(&seconds.to_be_bytes());
+        packet[44..48].copy_from_slice(&0x4000_0000u32.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_request() {
+        let packet = request(NONCE);
+
+        assert_eq!(packet[0], 0x23);
+        assert!(packet[1..40].iter().all(|b| *b == 0));
+        assert_eq!(&packet[40..], &NONCE.to_be_bytes());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let time = parse_response(&response(), NONCE).unwrap();
+
+        assert_eq!(
+            time,
+            ServerTime {
+                unix_millis: 1_737_569_275_250,
+                stratum: 2
+            }
+        );
+        assert_eq!(
+            time.to_timestamp(0).unwrap(),
+            Timestamp::new(2025, 1, 22, 18, 7, 55).unwrap()
+        );
+        assert_eq!(
+            time.to_timestamp(600).unwrap(),
+            Timestamp::new(2025, 1, 22, 18, 7, 56).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_response_errors() {
+        assert_eq!(
+            parse_response(&response()[..47], NONCE),
+            Err(SntpError::TooShort)
+        );
+        assert_eq!(
+            parse_response(&request(NONCE), NONCE),
+            Err(SntpError::InvalidMode)
+        );
+        assert_eq!(
+            parse_response(&response(), NONCE + 1),
+            Err(SntpError::OriginMismatch)
+        );
+
+        let mut kiss_of_death = response();
+        kiss_of_death[1] = 0;
+        assert_eq!(
+            parse_response(&kiss_of_death, NONCE),
+            Err(SntpError::Unsynchronized)
+        );
+
+        let mut alarm = response();
+        alarm[0] |= LEAP_NOT_SYNCHRONIZED << 6;
+        assert_eq!(
+            parse_response(&alarm, NONCE),
+            Err(SntpError::Unsynchronized)
+        );
+    }
+
+    #[test]
+    fn test_era_rollover() {
+        assert_eq!(ntp_to_unix_seconds(NTP_UNIX_OFFSET as u32), 0);
+        // 2036-02-07T06:28:16Z is the first second of NTP era 1.
+        assert_eq!(ntp_to_unix_seconds(0), 2_085_978_496);
+    }
+}
@@ -0,0 +1,124 @@
+//! State of the capteur shown on the on-board LED, each state blinking its own
+//! pattern so it can be told at a glance.
+
+/// What the capteur is doing, or what last went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Status {
+    /// Starting up, until the Wi-Fi is first joined.
+    #[default]
+    Booting,
+    /// Joining a Wi-Fi network, or waiting to retry.
+    JoiningWifi,
+    /// Joined, waiting for a DHCP lease.
+    WaitingDhcp,
+    /// Connected, nothing posted yet.
+    Connected,
+    /// The provisioning access point is open.
+    Provisioning,
+    /// The RTC could not be set from the server.
+    RtcSyncFailed,
+    /// The last post reached the server.
+    PostOk,
+    /// The last post failed.
+    PostFailing,
+}
+
+/// Step of a blink pattern: the LED state, held for `duration` milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blink {
+    pub on: bool,
+    pub duration: u64,
+}
+
+const fn on(duration: u64) -> Blink {
+    Blink { on: true, duration }
+}
+
+const fn off(duration: u64) -> Blink {
+    Blink {
+        on: false,
+        duration,
+    }
+}
+
+// Fast blinking
+const BOOTING: &[Blink] = &[on(100), off(100)];
+// Slow blinking
+const JOINING_WIFI: &[Blink] = &[on(500), off(500)];
+// Two flashes
+const WAITING_DHCP: &[Blink] = &[on(100), off(150), on(100), off(650)];
+// Steady
+const CONNECTED: &[Blink] = &[on(1000)];
+// A long flash and a short one
+const PROVISIONING: &[Blink] = &[on(600), off(200), on(100), off(600)];
+// Three flashes
+const RTC_SYNC_FAILED: &[Blink] = &[on(100), off(150), on(100), off(150), on(100), off(900)];
+// A short flash every 5 seconds, to stay mostly off
+const POST_OK: &[Blink] = &[on(50), off(4950)];
+// Mostly on
+const POST_FAILING: &[Blink] = &[on(1500), off(500)];
+
+impl Status {
+    /// Steps repeated while in this state.
+    pub fn pattern(self) -> &'static [Blink] {
+        match self {
+            Status::Booting => BOOTING,
+            Status::JoiningWifi => JOINING_WIFI,
+            Status::WaitingDhcp => WAITING_DHCP,
+            Status::Connected => CONNECTED,
+            Status::Provisioning => PROVISIONING,
+            Status::RtcSyncFailed => RTC_SYNC_FAILED,
+            Status::PostOk => POST_OK,
+            Status::PostFailing => POST_FAILING,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [Status; 8] = [
+        Status::Booting,
+        Status::JoiningWifi,
+        Status::WaitingDhcp,
+        Status::Connected,
+        Status::Provisioning,
+        Status::RtcSyncFailed,
+        Status::PostOk,
+        Status::PostFailing,
+    ];
+
+    #[test]
+    fn test_patterns_are_distinct() {
+        for (i, status) in STATUSES.iter().enumerate() {
+            for other in &STATUSES[i + 1..] {
+                assert_ne!(
+                    status.pattern(),
+                    other.pattern(),
+                    "{status:?} and {other:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_patterns_can_be_repeated() {
+        for status in STATUSES {
+            let pattern = status.pattern();
+            assert!(!pattern.is_empty());
+            assert!(pattern.iter().all(|blink| blink.duration > 0));
+            // Steps alternate, including across repetitions, so every step
+            // can be seen.
+            for (blink, next) in pattern.iter().zip(pattern.iter().cycle().skip(1)) {
+                assert!(pattern.len() == 1 || blink.on != next.on, "{status:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_booting_by_default() {
+        assert_eq!(Status::default(), Status::Booting);
+    }
+}
@@ -0,0 +1,267 @@
+//! Synchronisation of the RTC, from SNTP or the `/now` endpoint of the API,
+//! and its schedule: periodically to correct the drift, retried with an
+//! exponential backoff when it fails.
+
+use protocol::{ClockDrift, Timestamp};
+
+use crate::backoff::Backoff;
+use crate::drift::DriftTracker;
+use crate::http::{url, HttpClient};
+use crate::now::{parse_now, NowError};
+use crate::rtc::Rtc;
+
+/// Seconds between two synchronisations of the RTC, to correct its drift.
+pub const RTC_SYNC_PERIOD: u64 = 6 * 3600;
+/// Bounds, in seconds, of the delay between two failed synchronisations.
+pub const RTC_SYNC_RETRY_MIN: u64 = 5;
+pub const RTC_SYNC_RETRY_MAX: u64 = 600;
+
+/// Size of the buffer receiving the `/now` response.
+const NOW_RESPONSE_LEN: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SyncError<R, H> {
+    /// The URL of the API is too long.
+    InvalidUrl,
+    /// `/now` could not be fetched.
+    Http(H),
+    /// `/now` did not answer a valid time.
+    Now(NowError),
+    /// The RTC could not be set.
+    Rtc(R),
+}
+
+/// A successful synchronisation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Synced {
+    /// Time the RTC was set to.
+    pub reference: Timestamp,
+    /// How much the RTC drifted since the previous synchronisation.
+    pub drift: Option<ClockDrift>,
+}
+
+async fn fetch_now<R, C: HttpClient>(
+    client: &mut C,
+    api_url: &str,
+) -> Result<Timestamp, SyncError<R, C::Error>> {
+    let url = url(format_args!("{api_url}/now")).ok_or(SyncError::InvalidUrl)?;
+    let mut buffer = [0; NOW_RESPONSE_LEN];
+    let body = client
+        .get(&url, &mut buffer)
+        .await
+        .map_err(SyncError::Http)?;
+    parse_now(body).map_err(SyncError::Now)
+}
+
+/// Set `rtc` to `sntp`, the time from SNTP when it succeeded, or else to the
+/// time from `/now` of the API at `api_url`. `uptime` is in seconds.
+pub async fn sync_rtc<R: Rtc, C: HttpClient>(
+    rtc: &mut R,
+    client: &mut C,
+    drift_tracker: &mut DriftTracker,
+    sntp: Option<Timestamp>,
+    api_url: &str,
+    uptime: u64,
+) -> Result<Synced, SyncError<R::Error, C::Error>> {
+    let reference = match sntp {
+        Some(now) => now,
+        None => fetch_now(client, api_url).await?,
+    };
+
+    let rtc_now = rtc.now();
+    rtc.set(reference).map_err(SyncError::Rtc)?;
+    Ok(Synced {
+        reference,
+        drift: drift_tracker.record(rtc_now, reference, uptime),
+    })
+}
+
+/// When to synchronise the RTC next.
+#[derive(Debug, Clone)]
+pub struct SyncSchedule {
+    period: u64,
+    backoff: Backoff,
+}
+
+impl SyncSchedule {
+    /// Synchronise every `period` seconds.
+    pub const fn new(period: u64) -> Self {
+        Self {
+            period,
+            backoff: Backoff::new(RTC_SYNC_RETRY_MIN, RTC_SYNC_RETRY_MAX),
+        }
+    }
+
+    /// The synchronisation succeeded, returns the seconds until the next one.
+    pub fn synced(&mut self) -> u64 {
+        self.backoff.reset();
+        self.period
+    }
+
+    /// The synchronisation failed, returns the seconds until it is retried.
+    pub fn failed(&mut self) -> u64 {
+        self.backoff.next_delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use protocol::TimestampError;
+
+    use super::*;
+    use crate::mock::{block_on, MockError, MockHttp, MockRtc, Request};
+
+    const API_URL: &str = "https://example.com";
+    const NOW: &[u8] = br#"{"now":"2025-01-22T18:07:55.123456789Z","weekday":3}"#;
+
+    fn ts(unix: i64) -> Timestamp {
+        Timestamp::from_unix(unix).unwrap()
+    }
+
+    #[test]
+    fn test_sntp_time_is_used_first() {
+        let mut rtc = MockRtc::default();
+        let mut http = MockHttp::default();
+
+        let synced = block_on(sync_rtc(
+            &mut rtc,
+            &mut http,
+            &mut DriftTracker::new(),
+            Some(ts(1_737_569_275)),
+            API_URL,
+            3,
+        ));
+
+        assert_eq!(
+            synced,
+            Ok(Synced {
+                reference: ts(1_737_569_275),
+                drift: None,
+            })
+        );
+        assert_eq!(rtc.now, Some(ts(1_737_569_275)));
+        assert!(http.requests.is_empty());
+    }
+
+    #[test]
+    fn test_falls_back_to_api() {
+        let mut rtc = MockRtc::default();
+        let mut http = MockHttp::default();
+        http.respond(Ok(NOW));
+
+        let synced = block_on(sync_rtc(
+            &mut rtc,
+            &mut http,
+            &mut DriftTracker::new(),
+            None,
+            API_URL,
+            3,
+        ))
+        .unwrap();
+
+        assert_eq!(synced.reference, ts(1_737_569_275));
+        assert_eq!(rtc.now, Some(ts(1_737_569_275)));
+        assert_eq!(
+            http.requests,
+            vec![Request::Get("https://example.com/now".into())]
+        );
+    }
+
+    #[test]
+    fn test_drift_since_previous_sync() {
+        let mut rtc = MockRtc::default();
+        let mut http = MockHttp::default();
+        let mut drift_tracker = DriftTracker::new();
+        let mut sync = |sntp, rtc: &mut MockRtc, uptime| {
+            block_on(sync_rtc(
+                rtc,
+                &mut http,
+                &mut drift_tracker,
+                Some(sntp),
+                API_URL,
+                uptime,
+            ))
+        };
+        sync(ts(1_737_569_275), &mut rtc, 3).unwrap();
+        // The RTC ran 2s fast
+        rtc.now = Some(ts(1_737_590_877));
+
+        let synced = sync(ts(1_737_590_875), &mut rtc, 21_603).unwrap();
+
+        assert_eq!(
+            synced.drift,
+            Some(ClockDrift {
+                timestamp: ts(1_737_590_875),
+                drift: 2,
+                elapsed: 21_600,
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let sync = |http: &mut MockHttp, rtc: &mut MockRtc, api_url| {
+            block_on(sync_rtc(
+                rtc,
+                http,
+                &mut DriftTracker::new(),
+                None,
+                api_url,
+                3,
+            ))
+        };
+
+        let mut http = MockHttp::default();
+        http.respond(Err(MockError))
+            .respond(Ok(b"<html>Bad Gateway</html>"))
+            .respond(Ok(br#"{"now":"2025-02-30T18:07:55Z"}"#))
+            .respond(Ok(NOW));
+        let mut rtc = MockRtc {
+            broken: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sync(&mut http, &mut rtc, API_URL),
+            Err(SyncError::Http(MockError))
+        );
+        assert_eq!(
+            sync(&mut http, &mut rtc, API_URL),
+            Err(SyncError::Now(NowError::InvalidResponse))
+        );
+        assert_eq!(
+            sync(&mut http, &mut rtc, API_URL),
+            Err(SyncError::Now(NowError::InvalidDateTime(
+                TimestampError::OutOfRange
+            )))
+        );
+        assert_eq!(
+            sync(&mut http, &mut rtc, API_URL),
+            Err(SyncError::Rtc(MockError))
+        );
+        let too_long = [b'a'; 200];
+        assert_eq!(
+            sync(
+                &mut http,
+                &mut rtc,
+                core::str::from_utf8(&too_long).unwrap()
+            ),
+            Err(SyncError::InvalidUrl)
+        );
+        assert_eq!(rtc.now, None);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = SyncSchedule::new(RTC_SYNC_PERIOD);
+
+        let retries: std::vec::Vec<_> = (0..9).map(|_| schedule.failed()).collect();
+        assert_eq!(retries, [5, 10, 20, 40, 80, 160, 320, 600, 600]);
+
+        assert_eq!(schedule.synced(), RTC_SYNC_PERIOD);
+        assert_eq!(schedule.failed(), RTC_SYNC_RETRY_MIN);
+    }
+}
@@ -0,0 +1,329 @@
+//! Server authentication for the TLS connections of the capteur.
+//!
+//! Besides the CA verification provided by `embedded-tls`, the server can be
+//! pinned by the SHA-256 fingerprint of the SubjectPublicKeyInfo of its
+//! certificate, as printed by:
+//!
+//! ```text
+//! openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+//! ```
+//!
+//! Pinning the key rather than the certificate keeps the pin valid when the
+//! certificate is renewed with the same key. Only P-256 keys are supported.
+
+use embedded_tls::{
+    CertificateEntryRef, CertificateRef, CertificateVerifyRef, SignatureScheme, TlsCipherSuite,
+    TlsError, TlsVerifier,
+};
+use heapless::Vec;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+pub type Fingerprint = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FingerprintError {
+    InvalidLength,
+    InvalidDigit,
+}
+
+/// Parse a hexadecimal fingerprint, optionally separated by colons as
+/// printed by `openssl`. Being `const`, an invalid fingerprint embedded at
+/// build time fails the build.
+pub const fn parse_fingerprint(hex: &str) -> Result<Fingerprint, FingerprintError> {
+    let hex = hex.as_bytes();
+    let mut fingerprint = [0; 32];
+    let mut len = 0;
+    let mut i = 0;
+    while i < hex.len() {
+        if hex[i] == b':' {
+            i += 1;
+            continue;
+        }
+        if i + 1 >= hex.len() || len == fingerprint.len() {
+            return Err(FingerprintError::InvalidLength);
+        }
+        let (Some(high), Some(low)) = (hex_value(hex[i]), hex_value(hex[i + 1])) else {
+            return Err(FingerprintError::InvalidDigit);
+        };
+        fingerprint[len] = high << 4 | low;
+        len += 1;
+        i += 2;
+    }
+    if len != fingerprint.len() {
+        return Err(FingerprintError::InvalidLength);
+    }
+    Ok(fingerprint)
+}
+
+const fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Authenticate the server by the fingerprint of its public key.
+///
+/// The host name is not checked: only the owner of the pinned key can
+/// complete the handshake.
+pub struct PinnedKeyVerifier<CipherSuite: TlsCipherSuite> {
+    pin: Fingerprint,
+    /// SEC1 encoded public key of the server, once its certificate matched.
+    public_key: Option<Vec<u8, 65>>,
+    transcript: Option<CipherSuite::Hash>,
+}
+
+impl<CipherSuite: TlsCipherSuite> PinnedKeyVerifier<CipherSuite> {
+    pub fn new(pin: Fingerprint) -> Self {
+        Self {
+            pin,
+            public_key: None,
+            transcript: None,
+        }
+    }
+}
+
+impl<CipherSuite: TlsCipherSuite> TlsVerifier<CipherSuite> for PinnedKeyVerifier<CipherSuite> {
+    fn set_hostname_verification(&mut self, _hostname: &str) -> Result<(), TlsError> {
+        Ok(())
+    }
+
+    fn verify_certificate(
+        &mut self,
+        transcript: &CipherSuite::Hash,
+        cert: CertificateRef,
+    ) -> Result<(), TlsError> {
+        let Some(CertificateEntryRef::X509(certificate)) = cert.entries.first() else {
+            return Err(TlsError::InvalidCertificate);
+        };
+        let spki = subject_public_key_info(certificate).ok_or(TlsError::DecodeError)?;
+        if Sha256::digest(spki.raw).as_slice() != self.pin {
+            return Err(TlsError::InvalidCertificate);
+        }
+
+        let public_key = public_key(&spki).ok_or(TlsError::DecodeError)?;
+        self.public_key = Some(Vec::from_slice(public_key).map_err(|_| TlsError::DecodeError)?);
+        self.transcript = Some(transcript.clone());
+        Ok(())
+    }
+
+    fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), TlsError> {
+        let (Some(public_key), Some(transcript)) = (self.public_key.take(), self.transcript.take())
+        else {
+            return Err(TlsError::InvalidCertificate);
+        };
+        if verify.signature_scheme != SignatureScheme::EcdsaSecp256r1Sha256 {
+            return Err(TlsError::InvalidSignatureScheme);
+        }
+
+        // RFC 8446 section 4.4.3
+        let mut message: Vec<u8, 146> = Vec::new();
+        message
+            .resize(64, 0x20)
+            .map_err(|_| TlsError::EncodeError)?;
+        message
+            .extend_from_slice(b"TLS 1.3, server CertificateVerify\x00")
+            .map_err(|_| TlsError::EncodeError)?;
+        message
+            .extend_from_slice(&transcript.finalize())
+            .map_err(|_| TlsError::EncodeError)?;
+
+        let key = VerifyingKey::from_sec1_bytes(&public_key).map_err(|_| TlsError::DecodeError)?;
+        let signature = Signature::from_der(verify.signature).map_err(|_| TlsError::DecodeError)?;
+        key.verify(&message, &signature)
+            .map_err(|_| TlsError::InvalidSignature)
+    }
+}
+
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_VERSION: u8 = 0xa0;
+
+/// DER element, `raw` includes the tag and length.
+struct Element<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    raw: &'a [u8],
+}
+
+/// Read the DER element at the start of `der`, advancing past it.
+fn next_element<'a>(der: &mut &'a [u8]) -> Option<Element<'a>> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first {
+        0..=0x7f => (first as usize, rest),
+        0x81..=0x83 => {
+            let count = (first & 0x7f) as usize;
+            if rest.len() < count {
+                return None;
+            }
+            let len = rest[..count]
+                .iter()
+                .fold(0, |len, byte| len << 8 | *byte as usize);
+            (len, &rest[count..])
+        }
+        _ => return None,
+    };
+    if rest.len() < len {
+        return None;
+    }
+
+    let header_len = der.len() - rest.len();
+    let element = Element {
+        tag,
+        contents: &rest[..len],
+        raw: &der[..header_len + len],
+    };
+    *der = &rest[len..];
+    Some(element)
+}
+
+fn expect_element<'a>(der: &mut &'a [u8], tag: u8) -> Option<Element<'a>> {
+    next_element(der).filter(|element| element.tag == tag)
+}
+
+/// SubjectPublicKeyInfo of an X.509 certificate (RFC 5280 section 4.1).
+fn subject_public_key_info(certificate: &[u8]) -> Option<Element<'_>> {
+    let mut der = certificate;
+    let mut certificate = expect_element(&mut der, TAG_SEQUENCE)?.contents;
+    let mut tbs_certificate = expect_element(&mut certificate, TAG_SEQUENCE)?.contents;
+
+    if tbs_certificate.first() == Some(&TAG_VERSION) {
+        next_element(&mut tbs_certificate)?;
+    }
+    // serialNumber, signature, issuer, validity and subject
+    for _ in 0..5 {
+        next_element(&mut tbs_certificate)?;
+    }
+    expect_element(&mut tbs_certificate, TAG_SEQUENCE)
+}
+
+/// Key of a SubjectPublicKeyInfo, without the unused bits count.
+fn public_key<'a>(spki: &Element<'a>) -> Option<&'a [u8]> {
+    let mut spki = spki.contents;
+    // algorithm
+    expect_element(&mut spki, TAG_SEQUENCE)?;
+    match expect_element(&mut spki, TAG_BIT_STRING)?.contents {
+        [0, key @ ..] => Some(key),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_tls::Aes128GcmSha256;
+
+    /// Self-signed P-256 certificate for `envirometer.local`.
+    const CERTIFICATE: &[u8] = include_bytes!("testdata/server.der");
+    /// Signature of the CertificateVerify message of a handshake whose
+    /// transcript is `b"transcript"`, by the key of `CERTIFICATE`.
+    const SIGNATURE: &[u8] = include_bytes!("testdata/certificate_verify.sig");
+    const PIN: &str = "44aaad250bb6796b332e86eb231568261b03ac5ef28f3755da26c4c58e119306";
+
+    fn transcript() -> Sha256 {
+        let mut transcript = Sha256::new();
+        transcript.update(b"transcript");
+        transcript
+    }
+
+    fn server_certificate(certificate: &[u8]) -> CertificateRef<'_> {
+        let mut cert = CertificateRef::with_context(&[]);
+        cert.add(CertificateEntryRef::X509(certificate)).unwrap();
+        cert
+    }
+
+    fn verify(pin: &str, signature: &[u8]) -> Result<(), TlsError> {
+        let mut verifier =
+            PinnedKeyVerifier::<Aes128GcmSha256>::new(parse_fingerprint(pin).unwrap());
+        verifier.verify_certificate(&transcript(), server_certificate(CERTIFICATE))?;
+        verifier.verify_signature(CertificateVerifyRef {
+            signature_scheme: SignatureScheme::EcdsaSecp256r1Sha256,
+            signature,
+        })
+    }
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let fingerprint = parse_fingerprint(PIN).unwrap();
+        assert_eq!(fingerprint[..4], [0x44, 0xaa, 0xad, 0x25]);
+
+        let with_colons = "44:AA:AD:25:0B:B6:79:6B:33:2E:86:EB:23:15:68:26:1B:03:AC:5E:F2:8F:37:55:DA:26:C4:C5:8E:11:93:06";
+        assert_eq!(parse_fingerprint(with_colons), Ok(fingerprint));
+
+        assert_eq!(
+            parse_fingerprint(&PIN[..62]),
+            Err(FingerprintError::InvalidLength)
+        );
+        assert_eq!(parse_fingerprint(""), Err(FingerprintError::InvalidLength));
+        assert_eq!(
+            parse_fingerprint(&PIN.replace('4', "g")),
+            Err(FingerprintError::InvalidDigit)
+        );
+    }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        let spki = subject_public_key_info(CERTIFICATE).unwrap();
+
+        assert_eq!(spki.raw.len(), 91);
+        let key = public_key(&spki).unwrap();
+        assert_eq!(key.len(), 65);
+        assert_eq!(key[0], 0x04);
+    }
+
+    #[test]
+    fn test_truncated_certificate() {
+        assert!(subject_public_key_info(&CERTIFICATE[..100]).is_none());
+        assert!(subject_public_key_info(&[]).is_none());
+    }
+
+    #[test]
+    fn test_pinned_key() {
+        assert!(verify(PIN, SIGNATURE).is_ok());
+    }
+
+    #[test]
+    fn test_other_key() {
+        let other = "00".repeat(32);
+
+        assert!(matches!(
+            verify(&other, SIGNATURE),
+            Err(TlsError::InvalidCertificate)
+        ));
+    }
+
+    #[test]
+    fn test_forged_signature() {
+        let mut signature = std::vec::Vec::from(SIGNATURE);
+        let last = signature.len() - 1;
+        signature[last] ^= 0x01;
+
+        assert!(matches!(
+            verify(PIN, &signature),
+            Err(TlsError::InvalidSignature | TlsError::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_scheme() {
+        let mut verifier =
+            PinnedKeyVerifier::<Aes128GcmSha256>::new(parse_fingerprint(PIN).unwrap());
+        verifier
+            .verify_certificate(&transcript(), server_certificate(CERTIFICATE))
+            .unwrap();
+
+        assert!(matches!(
+            verifier.verify_signature(CertificateVerifyRef {
+                signature_scheme: SignatureScheme::Ed25519,
+                signature: SIGNATURE,
+            }),
+            Err(TlsError::InvalidSignatureScheme)
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+//! Wi-Fi connection supervision: which network to join next, and how the
+//! connection state changes are reported to the server.
+
+use protocol::connection::Ssid;
+use protocol::{ConnectionEvent, ConnectionEventKind};
+
+use crate::backoff::Backoff;
+
+/// Order of the join attempts over the configured networks.
+///
+/// Networks are tried by decreasing priority. Once all of them failed, the
+/// next round starts from the first network again after a backoff delay.
+#[derive(Debug, Clone)]
+pub struct JoinScheduler {
+    network_count: usize,
+    next: usize,
+    failed_rounds: u32,
+    backoff: Backoff,
+}
+
+impl JoinScheduler {
+    pub const fn new(network_count: usize, backoff: Backoff) -> Self {
+        Self {
+            network_count,
+            next: 0,
+            failed_rounds: 0,
+            backoff,
+        }
+    }
+
+    /// Index of the network to try next.
+    pub fn network(&self) -> usize {
+        self.next
+    }
+
+    /// Record a failed attempt, returning the delay in seconds before the next
+    /// one: 0 while there are networks left to try in the current round.
+    pub fn failed(&mut self) -> u64 {
+        self.next += 1;
+        if self.next < self.network_count {
+            return 0;
+        }
+        self.next = 0;
+        self.failed_rounds += 1;
+        self.backoff.next_delay()
+    }
+
+    /// Number of rounds where no network could be joined, since the last
+    /// successful connection.
+    pub fn failed_rounds(&self) -> u32 {
+        self.failed_rounds
+    }
+
+    /// Start again from the preferred network on the next disconnection.
+    pub fn connected(&mut self) {
+        self.next = 0;
+        self.failed_rounds = 0;
+        self.backoff.reset();
+    }
+}
+
+/// Turn the periodic link checks into connection events.
+#[derive(Debug, Clone)]
+pub struct LinkMonitor {
+    up: bool,
+    /// Uptime at which the connection was lost, the capteur boots offline.
+    down_since: u64,
+}
+
+impl Default for LinkMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkMonitor {
+    pub const fn new() -> Self {
+        Self {
+            up: false,
+            down_since: 0,
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+
+    /// Record whether the connection to `ssid` is up, `uptime` seconds after
+    /// boot, returning an event when the state changed.
+    pub fn update(&mut self, up: bool, ssid: &Ssid, uptime: u64) -> Option<ConnectionEvent> {
+        if up == self.up {
+            return None;
+        }
+        self.up = up;
+
+        let (kind, downtime) = if up {
+            let downtime = uptime.saturating_sub(self.down_since);
+            (
+                ConnectionEventKind::Connected,
+                u32::try_from(downtime).unwrap_or(u32::MAX),
+            )
+        } else {
+            self.down_since = uptime;
+            (ConnectionEventKind::Disconnected, 0)
+        };
+
+        Some(ConnectionEvent {
+            kind,
+            ssid: ssid.clone(),
+            uptime,
+            downtime,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_networks_tried_in_order() {
+        let mut scheduler = JoinScheduler::new(3, Backoff::new(5, 60));
+
+        assert_eq!(scheduler.network(), 0);
+        assert_eq!(scheduler.failed(), 0);
+        assert_eq!(scheduler.network(), 1);
+        assert_eq!(scheduler.failed(), 0);
+        assert_eq!(scheduler.network(), 2);
+        assert_eq!(scheduler.failed(), 5);
+        assert_eq!(scheduler.network(), 0);
+        assert_eq!(scheduler.failed_rounds(), 1);
+    }
+
+    #[test]
+    fn test_backoff_between_rounds() {
+        let mut scheduler = JoinScheduler::new(1, Backoff::new(5, 60));
+
+        let delays = [(); 5].map(|_| scheduler.failed());
+
+        assert_eq!(delays, [5, 10, 20, 40, 60]);
+        assert_eq!(scheduler.failed_rounds(), 5);
+    }
+
+    #[test]
+    fn test_connected_resets_scheduler() {
+        let mut scheduler = JoinScheduler::new(2, Backoff::new(5, 60));
+        scheduler.failed();
+        scheduler.failed();
+        scheduler.failed();
+
+        scheduler.connected();
+
+        assert_eq!(scheduler.network(), 0);
+        assert_eq!(scheduler.failed_rounds(), 0);
+        scheduler.failed();
+        assert_eq!(scheduler.failed(), 5);
+    }
+
+    #[test]
+    fn test_link_events() {
+        let ssid = Ssid::try_from("maison").unwrap();
+        let mut monitor = LinkMonitor::new();
+
+        assert_eq!(monitor.update(false, &ssid, 2), None);
+        let connected = monitor.update(true, &ssid, 8).unwrap();
+        assert_eq!(connected.kind, ConnectionEventKind::Connected);
+        assert_eq!(connected.downtime, 8);
+        assert_eq!(monitor.update(true, &ssid, 60), None);
+
+        let disconnected = monitor.update(false, &ssid, 3600).unwrap();
+        assert_eq!(disconnected.kind, ConnectionEventKind::Disconnected);
+        assert_eq!(disconnected.ssid, "maison");
+        assert_eq!(disconnected.uptime, 3600);
+
+        let reconnected = monitor.update(true, &ssid, 3630).unwrap();
+        assert_eq!(reconnected.downtime, 30);
+    }
+}
@@ -11,7 +11,7 @@
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
@@ -29,6 +29,21 @@ fn main() {
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
 
+    // The `tls-ca` feature embeds the CA the API server certificate is
+    // verified against, exported in DER, e.g. with
+    // `openssl x509 -in ca.pem -outform der -out certs/ca.der`.
+    if env::var_os("CARGO_FEATURE_TLS_CA").is_some() {
+        let ca = Path::new("certs/ca.der");
+        if !ca.is_file() {
+            panic!(
+                "the `tls-ca` feature needs the CA certificate of the API server in {}, \
+                 exported in DER (openssl x509 -in ca.pem -outform der -out certs/ca.der)",
+                ca.display()
+            );
+        }
+        println!("cargo:rerun-if-changed=certs/ca.der");
+    }
+
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
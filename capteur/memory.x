@@ -1,5 +1,28 @@
+/* Flash layout shared with the bootloader, keep both `memory.x` in sync.
+   The application runs from the ACTIVE partition of the bootloader. */
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    BOOTLOADER_STATE : ORIGIN = 0x10006000, LENGTH = 4K
+    FLASH : ORIGIN = 0x10007000, LENGTH = 1000K
+    DFU : ORIGIN = 0x10101000, LENGTH = 1004K
+    /* Of the 16K left at the end, 0x1FC000..0x1FE000 is free, the sector at
+       0x1FE000 holds the settings set on the server and the last one, at
+       0x1FF000, the device configuration */
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
-}
\ No newline at end of file
+    /* SCRATCH_X, unused as the second core is not started. Left alone by the
+       bootloader and the startup code, it keeps the last panic across the
+       reset, see `src/crash.rs` */
+    PANIC_RECORD : ORIGIN = 0x20040000, LENGTH = 4K
+}
+
+SECTIONS {
+    .panic_record (NOLOAD) : ALIGN(4) {
+        KEEP(*(.panic_record));
+    } > PANIC_RECORD
+} INSERT AFTER .bss;
+
+__bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+__bootloader_state_end = ORIGIN(BOOTLOADER_STATE) + LENGTH(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+
+__bootloader_dfu_start = ORIGIN(DFU) - ORIGIN(BOOT2);
+__bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOT2);
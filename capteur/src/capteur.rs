@@ -1,11 +1,45 @@
-use am2301::{measure_once_timeout, Measure as _Measure};
+use am2301::{measure_once_timeout, MeasureError};
+#[cfg(not(feature = "low-power"))]
+use capteur_core::reporting::{Deadband, Reporter, ReportingPolicy};
+use capteur_core::sampling::{Sample, Sampler};
 use defmt::*;
 use embassy_futures::join::join;
 use embassy_rp::gpio::Flex;
 use embassy_rp::peripherals::PIN_21;
-use embassy_time::{Instant, Timer};
+use embassy_time::{Duration, Instant, Timer};
+#[cfg(not(feature = "low-power"))]
+use protocol::CriticalTask;
 
-use crate::{Measure, MEASURE_SIGNAL, NETWORK_STACK_SIGNAL};
+use crate::health;
+use crate::settings::{settings, verbose};
+#[cfg(not(feature = "low-power"))]
+use crate::watchdog;
+use crate::{Measure, NETWORK_STACK_SIGNAL};
+
+#[cfg(not(feature = "low-power"))]
+const DEFAULT_MEASURE_INTERVAL: u64 = 5;
+/// Longest a measure may take, on top of the time the task sleeps.
+pub const MEASURE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Shortest delay between two reads of the AM2301.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// Measures are aggregated over 5 minutes, changes beyond the deadband being
+/// sent right away. In low-power mode, they are uploaded in batches instead.
+#[cfg(not(feature = "low-power"))]
+const REPORTING_POLICY: ReportingPolicy = ReportingPolicy {
+    period: Some(300),
+    deadband: Some(Deadband {
+        temperature: 0.5,
+        humidity: 3.,
+    }),
+    max_silence: 3600,
+};
+
+/// Seconds between two measures, as set on the server, `default` otherwise.
+pub fn measure_interval(default: u64) -> u64 {
+    settings()
+        .bounded_measure_interval()
+        .map_or(default, u64::from)
+}
 
 async fn wait_for_network_stack() {
     let mut stack_is_up = NETWORK_STACK_SIGNAL.wait().await;
@@ -14,32 +48,113 @@ async fn wait_for_network_stack() {
     }
 }
 
+/// The AM2301, read at most every `SAMPLE_INTERVAL`.
+pub struct Sensor<'a> {
+    pin: Flex<'a>,
+    last_read: Option<Instant>,
+}
+
+impl<'a> Sensor<'a> {
+    pub fn new(pin: Flex<'a>) -> Self {
+        Self {
+            pin,
+            last_read: None,
+        }
+    }
+
+    async fn read(&mut self) -> Result<Sample, MeasureError> {
+        if let Some(last_read) = self.last_read {
+            Timer::at(last_read + SAMPLE_INTERVAL).await;
+        }
+        let result = measure_once_timeout(&mut self.pin).await;
+        self.last_read = Some(Instant::now());
+        result.map(|measure| Sample {
+            temperature: measure.temperature,
+            humidity: measure.humidity,
+        })
+    }
+}
+
+/// Take a measure, the median of several samples, as read: the server
+/// corrects it with the calibrations of the capteur.
+pub async fn measure(sensor: &mut Sensor<'_>) -> Option<Measure> {
+    let mut sampler = Sampler::new();
+    while !sampler.is_done() {
+        match sensor.read().await {
+            Ok(sample) => {
+                if verbose() {
+                    info!(
+                        "Sample: T = {}, humidity = {}",
+                        sample.temperature, sample.humidity
+                    );
+                }
+                if !sampler.read(sample) {
+                    warn!(
+                        "Discarding implausible sample: T = {}, humidity = {}",
+                        sample.temperature, sample.humidity
+                    );
+                    health::sample_discarded();
+                }
+            }
+            Err(err) => {
+                warn!("Error while measure temperature and humidity: {:?}", err);
+                health::sensor_failed(&err);
+                sampler.failed();
+            }
+        }
+    }
+
+    let Some(filtered) = sampler.filtered() else {
+        warn!("No valid sample after {} attempts", sampler.attempts());
+        return None;
+    };
+    let measure = Measure {
+        temperature: filtered.temperature,
+        humidity: filtered.humidity,
+        samples: filtered.samples,
+    };
+    if verbose() {
+        info!(
+            "Measure: T = {}, humidity = {}, median of {} samples",
+            measure.temperature, measure.humidity, measure.samples
+        );
+    }
+    #[cfg(feature = "display")]
+    crate::display::show_measure(&measure);
+    Some(measure)
+}
+
 #[embassy_executor::task]
 pub async fn measure_task(pin: PIN_21) -> ! {
-    let mut pin = Flex::new(pin);
+    let mut sensor = Sensor::new(Flex::new(pin));
 
     // Wait for device to initialized
     join(Timer::after_secs(2), wait_for_network_stack()).await;
 
+    #[cfg(feature = "low-power")]
+    crate::low_power::measure_loop(&mut sensor).await;
+
+    #[cfg(not(feature = "low-power"))]
+    let mut reporter = Reporter::new(REPORTING_POLICY);
+    #[cfg(not(feature = "low-power"))]
     loop {
         let start = Instant::now();
-        match measure_once_timeout(&mut pin).await {
-            Ok(_Measure {
-                humidity,
-                temperature,
-            }) => {
-                MEASURE_SIGNAL.signal(Measure {
-                    temperature,
-                    humidity,
-                });
-                // info!("Temperature = {} and humidity = {}", temperature, humidity);
-            }
-            Err(err) => {
-                warn!("Error while measure temperature and humidity: {:?}", err)
+        watchdog::progress(CriticalTask::Measure, MEASURE_TIMEOUT);
+        if let Some(measure) = measure(&mut sensor).await {
+            let now = start.as_secs();
+            if let Some(aggregate) = reporter.reading(now, measure.temperature, measure.humidity) {
+                if crate::MEASURES.try_send((measure, aggregate)).is_err() {
+                    warn!("Measure queue full, dropping measure");
+                }
             }
         }
-        let delay = 5 - start.elapsed().as_secs();
+        let delay =
+            measure_interval(DEFAULT_MEASURE_INTERVAL).saturating_sub(start.elapsed().as_secs());
         info!("Sleeping for {}s", delay);
+        watchdog::progress(
+            CriticalTask::Measure,
+            Duration::from_secs(delay) + MEASURE_TIMEOUT,
+        );
         Timer::after_secs(delay).await;
     }
 }
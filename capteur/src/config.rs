@@ -0,0 +1,125 @@
+use core::cell::RefCell;
+
+use capteur_core::config::{ConfigError, DeviceConfig, CONFIG_BLOCK_LEN};
+use capteur_core::settings::{self, SETTINGS_BLOCK_LEN};
+use defmt::*;
+use embassy_rp::flash::{self, Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use protocol::CapteurSettings;
+use static_cell::StaticCell;
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+/// Offset of the sector holding the configuration, reserved in `memory.x`.
+const CONFIG_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+/// Offset of the sector holding the settings set on the server, reserved in
+/// `memory.x`.
+const SETTINGS_OFFSET: u32 = (FLASH_SIZE - 2 * ERASE_SIZE) as u32;
+
+pub type SharedFlash =
+    Mutex<CriticalSectionRawMutex, RefCell<Flash<'static, FLASH, Blocking, FLASH_SIZE>>>;
+
+#[derive(Format, Debug)]
+pub enum ConfigStoreError {
+    Flash(flash::Error),
+    Config(ConfigError),
+}
+
+impl From<flash::Error> for ConfigStoreError {
+    fn from(value: flash::Error) -> Self {
+        Self::Flash(value)
+    }
+}
+
+impl From<ConfigError> for ConfigStoreError {
+    fn from(value: ConfigError) -> Self {
+        Self::Config(value)
+    }
+}
+
+/// Handle on the reserved flash sectors, shared by the tasks writing to them.
+#[derive(Clone, Copy)]
+pub struct ConfigStore {
+    flash: &'static SharedFlash,
+}
+
+impl ConfigStore {
+    pub fn new(flash: FLASH) -> Self {
+        static FLASH: StaticCell<SharedFlash> = StaticCell::new();
+        Self {
+            flash: FLASH.init(Mutex::new(RefCell::new(Flash::new_blocking(flash)))),
+        }
+    }
+
+    /// Flash shared with the firmware updater.
+    pub fn flash(&self) -> &'static SharedFlash {
+        self.flash
+    }
+
+    fn read(&self, offset: u32, block: &mut [u8]) -> Result<(), flash::Error> {
+        self.flash
+            .lock(|flash| flash.borrow_mut().blocking_read(offset, block))
+    }
+
+    fn write(&self, offset: u32, block: &[u8]) -> Result<(), flash::Error> {
+        self.flash.lock(|flash| {
+            let mut flash = flash.borrow_mut();
+            flash.blocking_erase(offset, offset + ERASE_SIZE as u32)?;
+            flash.blocking_write(offset, block)
+        })
+    }
+
+    /// Read the configuration saved in flash, if the capteur was provisioned.
+    pub fn load(&mut self) -> Option<DeviceConfig> {
+        let mut block = [0; CONFIG_BLOCK_LEN];
+        if let Err(err) = self.read(CONFIG_OFFSET, &mut block) {
+            error!("Unable to read the configuration: {}", err);
+            return None;
+        }
+
+        match DeviceConfig::decode(&block) {
+            Ok(config) => Some(config),
+            Err(ConfigError::Empty) => None,
+            Err(err) => {
+                warn!("Invalid configuration in flash: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&mut self, config: &DeviceConfig) -> Result<(), ConfigStoreError> {
+        let mut block = [0xff; CONFIG_BLOCK_LEN];
+        config.encode(&mut block)?;
+        self.write(CONFIG_OFFSET, &block)?;
+
+        Ok(())
+    }
+
+    /// Read the last settings applied, if the server ever sent some.
+    pub fn load_settings(&mut self) -> Option<CapteurSettings> {
+        let mut block = [0; SETTINGS_BLOCK_LEN];
+        if let Err(err) = self.read(SETTINGS_OFFSET, &mut block) {
+            error!("Unable to read the settings: {}", err);
+            return None;
+        }
+
+        match settings::decode(&block) {
+            Ok(settings) => Some(settings),
+            Err(ConfigError::Empty) => None,
+            Err(err) => {
+                warn!("Invalid settings in flash: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn save_settings(&mut self, settings: &CapteurSettings) -> Result<(), ConfigStoreError> {
+        let mut block = [0xff; SETTINGS_BLOCK_LEN];
+        settings::encode(settings, &mut block)?;
+        self.write(SETTINGS_OFFSET, &block)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Panic reports, see `capteur_core::crash`.
+//!
+//! The report is written by the panic handler to the `.panic_record` section,
+//! which is neither initialized at startup nor used by the bootloader, and
+//! stays there until the server acknowledged it.
+
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::ptr::addr_of_mut;
+use core::slice;
+
+use capteur_core::crash::{self, CRASH_BLOCK_LEN};
+use embassy_time::Instant;
+use protocol::CrashReport;
+
+use crate::ota::FIRMWARE_VERSION;
+
+#[link_section = ".panic_record"]
+static mut PANIC_RECORD: MaybeUninit<[u8; CRASH_BLOCK_LEN]> = MaybeUninit::uninit();
+
+/// The reserved RAM, whatever it holds: random bytes after a power-up.
+fn panic_record() -> &'static mut [u8] {
+    // SAFETY: the section is only accessed from the panic handler, with the
+    // interrupts disabled, and from the network task, which does not run any
+    // more once the handler started. Any byte pattern is a valid `u8`.
+    unsafe { slice::from_raw_parts_mut(addr_of_mut!(PANIC_RECORD).cast(), CRASH_BLOCK_LEN) }
+}
+
+/// Save the panic for the next boot, to be called from the panic handler.
+pub fn record(info: &PanicInfo) {
+    let report = crash::crash_report(
+        FIRMWARE_VERSION,
+        Instant::now().as_secs(),
+        info.location()
+            .map(|location| (location.file(), location.line())),
+        info.message(),
+    );
+    let _ = crash::encode(&report, panic_record());
+}
+
+/// Last panic, if not reported yet.
+pub fn pending_report() -> Option<CrashReport> {
+    crash::decode(panic_record()).ok()
+}
+
+pub fn reported() {
+    crash::clear(panic_record());
+}
@@ -0,0 +1,101 @@
+//! Optional SSD1306 OLED showing the latest measure, the time and the status,
+//! laid out by `capteur_core::display`.
+
+use core::cell::Cell;
+
+use capteur_core::display::{draw, Screen};
+use capteur_core::sampling::Filtered;
+use defmt::*;
+use embassy_futures::select::select;
+use embassy_rp::bind_interrupts;
+use embassy_rp::i2c::{self, I2c};
+use embassy_rp::pac;
+use embassy_rp::peripherals::{I2C0, PIN_4, PIN_5};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use protocol::Timestamp;
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306Async};
+
+use crate::led;
+use crate::Measure;
+
+/// The display is redrawn at least this often, in seconds, for the time and
+/// status to stay current.
+const REFRESH_PERIOD: u64 = 1;
+
+bind_interrupts!(struct Irqs {
+    I2C0_IRQ => i2c::InterruptHandler<I2C0>;
+});
+
+static MEASURE: Mutex<CriticalSectionRawMutex, Cell<Option<Filtered>>> =
+    Mutex::new(Cell::new(None));
+static MEASURE_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Show `measure` as the latest one.
+pub fn show_measure(measure: &Measure) {
+    MEASURE.lock(|current| {
+        current.set(Some(Filtered {
+            temperature: measure.temperature,
+            humidity: measure.humidity,
+            samples: measure.samples,
+        }))
+    });
+    MEASURE_CHANGED.signal(());
+}
+
+/// Current time of the RTC, `None` while it is not set. The RTC belongs to the
+/// network task, which sets it, its registers are only read here.
+fn rtc_now() -> Option<Timestamp> {
+    let rtc = pac::RTC;
+    if !rtc.ctrl().read().rtc_active() {
+        return None;
+    }
+    let rtc_0 = rtc.rtc_0().read();
+    let rtc_1 = rtc.rtc_1().read();
+    Timestamp::new(
+        rtc_1.year(),
+        rtc_1.month(),
+        rtc_1.day(),
+        rtc_0.hour(),
+        rtc_0.min(),
+        rtc_0.sec(),
+    )
+    .ok()
+}
+
+#[embassy_executor::task]
+pub async fn display_task(i2c: I2C0, sda: PIN_4, scl: PIN_5) {
+    let mut config = i2c::Config::default();
+    config.frequency = 400_000;
+    let i2c = I2c::new_async(i2c, scl, sda, Irqs, config);
+    let mut display = Ssd1306Async::new(
+        I2CDisplayInterface::new(i2c),
+        DisplaySize128x64,
+        DisplayRotation::Rotate0,
+    )
+    .into_buffered_graphics_mode();
+    if let Err(err) = display.init().await {
+        // Most likely no display is connected
+        warn!("Unable to initialize the display: {}", Debug2Format(&err));
+        return;
+    }
+
+    loop {
+        let screen = Screen {
+            measure: MEASURE.lock(Cell::get),
+            time: rtc_now(),
+            status: led::status(),
+        };
+        let updated = match draw(&screen, &mut display) {
+            Ok(()) => display.flush().await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = updated {
+            warn!("Unable to update the display: {}", Debug2Format(&err));
+        }
+        select(Timer::after_secs(REFRESH_PERIOD), MEASURE_CHANGED.wait()).await;
+    }
+}
@@ -0,0 +1,75 @@
+//! Health telemetry, see `capteur_core::health`.
+
+use core::cell::RefCell;
+
+use am2301::MeasureError;
+use capteur_core::health::{HealthMonitor, SensorErrorKind};
+use cyw43::ScanOptions;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use protocol::connection::Ssid;
+use protocol::{Heartbeat, Timestamp};
+
+use crate::ota::FIRMWARE_VERSION;
+
+static HEALTH: Mutex<CriticalSectionRawMutex, RefCell<HealthMonitor>> =
+    Mutex::new(RefCell::new(HealthMonitor::new()));
+
+pub fn sensor_failed(err: &MeasureError) {
+    let kind = match err {
+        MeasureError::MeasureTimeoutError => SensorErrorKind::Timeout,
+        MeasureError::ChecksumError => SensorErrorKind::Checksum,
+        MeasureError::MeasureError => SensorErrorKind::Invalid,
+    };
+    HEALTH.lock(|health| health.borrow_mut().sensor_failed(kind));
+}
+
+/// Count a sample read without error but discarded as implausible.
+pub fn sample_discarded() {
+    HEALTH.lock(|health| {
+        health
+            .borrow_mut()
+            .sensor_failed(SensorErrorKind::Implausible)
+    });
+}
+
+pub fn set_rssi(rssi: Option<i16>) {
+    HEALTH.lock(|health| health.borrow_mut().set_rssi(rssi));
+}
+
+pub fn rtc_synced(reference: Timestamp) {
+    HEALTH.lock(|health| health.borrow_mut().rtc_synced(reference));
+}
+
+pub fn heartbeat() -> Heartbeat {
+    #[cfg(feature = "low-power")]
+    let free_buffer_slots = Some(crate::low_power::free_slots() as u16);
+    #[cfg(not(feature = "low-power"))]
+    let free_buffer_slots = None;
+
+    HEALTH.lock(|health| {
+        health.borrow().heartbeat(
+            Instant::now().as_secs(),
+            FIRMWARE_VERSION,
+            free_buffer_slots,
+        )
+    })
+}
+
+/// Signal strength of the network `ssid`, from a scan restricted to it.
+pub async fn measure_rssi(control: &mut cyw43::Control<'_>, ssid: &Ssid) -> Option<i16> {
+    let mut scanner = control
+        .scan(ScanOptions {
+            ssid: Some(ssid.clone()),
+            ..Default::default()
+        })
+        .await;
+    let mut rssi = None;
+    // Keep the strongest access point, the scan being over once `next` returns
+    // `None`.
+    while let Some(bss) = scanner.next().await {
+        rssi = rssi.max(Some(bss.rssi));
+    }
+    rssi
+}
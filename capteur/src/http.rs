@@ -0,0 +1,106 @@
+//! `capteur_core::http::HttpClient` over the reqwless client.
+
+use capteur_core::http::HttpClient;
+use capteur_core::payload::{Body, Encoding};
+use defmt::Format;
+use embedded_io_async::Write;
+use embedded_nal_async::{Dns, TcpConnect};
+use protocol::batch::PIECE_LEN;
+use reqwless::client;
+use reqwless::headers::ContentType;
+use reqwless::request::{Method, RequestBody};
+use reqwless::response::StatusCode;
+
+use crate::web::post;
+
+#[derive(Format, Debug)]
+pub enum HttpError {
+    Request(reqwless::Error),
+    /// The server answered with an error.
+    Status(StatusCode),
+    /// The post failed, see `web::post`.
+    Rejected,
+}
+
+impl From<reqwless::Error> for HttpError {
+    fn from(err: reqwless::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+/// The reqwless client, for the logic of `capteur_core`.
+pub struct Api<'c, 'a, T, U>(pub &'c mut client::HttpClient<'a, T, U>)
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a;
+
+impl<'a, T, U> HttpClient for Api<'_, 'a, T, U>
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    type Error = HttpError;
+
+    async fn get<'b>(&mut self, url: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], HttpError> {
+        let mut request = self.0.request(Method::GET, url).await?;
+        let response = request.send(buffer).await?;
+        if !response.status.is_successful() {
+            return Err(HttpError::Status(response.status));
+        }
+        Ok(response.body().read_to_end().await?)
+    }
+
+    async fn post(&mut self, url: &str, body: &[u8], encoding: Encoding) -> Result<(), HttpError> {
+        if post(self.0, url, body, content_type(encoding)).await {
+            Ok(())
+        } else {
+            Err(HttpError::Rejected)
+        }
+    }
+
+    async fn post_body<B: Body>(
+        &mut self,
+        url: &str,
+        body: &B,
+        len: usize,
+    ) -> Result<(), HttpError> {
+        let content_type = content_type(body.encoding());
+        if post(self.0, url, Pieces { body, len }, content_type).await {
+            Ok(())
+        } else {
+            Err(HttpError::Rejected)
+        }
+    }
+}
+
+fn content_type(encoding: Encoding) -> ContentType {
+    match encoding {
+        Encoding::Json => ContentType::ApplicationJson,
+        #[cfg(feature = "cbor")]
+        Encoding::Cbor => ContentType::ApplicationCbor,
+    }
+}
+
+/// A `capteur_core` body, encoded piece by piece as reqwless writes it.
+struct Pieces<'b, B> {
+    body: &'b B,
+    /// Length of the encoded body, sent as `Content-Length`.
+    len: usize,
+}
+
+impl<B: Body> RequestBody for Pieces<'_, B> {
+    fn len(&self) -> Option<usize> {
+        Some(self.len)
+    }
+
+    async fn write<W: Write>(&self, writer: &mut W) -> Result<(), W::Error> {
+        let mut buffer = [0; PIECE_LEN];
+        for index in 0..self.body.pieces() {
+            // Every piece was encoded once already to tell the length
+            if let Ok(piece) = self.body.piece(index, &mut buffer) {
+                writer.write_all(piece).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Status LED, wired to the cyw43 chip, blinking the pattern of the current
+//! status, see `capteur_core::status`.
+//!
+//! The LED is only driven during the radio sessions, see `crate::radio`: it
+//! goes off with the chip when powered down between two uploads.
+
+use core::cell::Cell;
+
+use capteur_core::status::Status;
+use defmt::*;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+
+use crate::wifi::SharedControl;
+
+/// GPIO of the cyw43 chip driving the on-board LED.
+const LED_GPIO: u8 = 0;
+
+static STATUS: Mutex<CriticalSectionRawMutex, Cell<Status>> =
+    Mutex::new(Cell::new(Status::Booting));
+static STATUS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Show `status` on the LED, from the start of its pattern when it changed.
+pub fn set_status(status: Status) {
+    if STATUS.lock(|current| current.replace(status)) != status {
+        STATUS_CHANGED.signal(());
+    }
+}
+
+/// The status currently shown.
+pub fn status() -> Status {
+    STATUS.lock(Cell::get)
+}
+
+/// Blink the status on the LED for as long as the chip is powered, the GPIO
+/// being set only when the LED changes, not for a steady status.
+pub async fn show_status(control: &SharedControl<'_>) -> ! {
+    // Unknown until first set, the chip being just powered up
+    let mut lit = None;
+    loop {
+        let status = status();
+        debug!("Status: {}", status);
+        'pattern: loop {
+            for blink in status.pattern() {
+                // Only waiting is interrupted, the chip not coping with
+                // cancelled commands.
+                if lit != Some(blink.on) {
+                    control.lock().await.gpio_set(LED_GPIO, blink.on).await;
+                    lit = Some(blink.on);
+                }
+                if let Either::Second(_) =
+                    select(Timer::after_millis(blink.duration), STATUS_CHANGED.wait()).await
+                {
+                    break 'pattern;
+                }
+            }
+        }
+    }
+}
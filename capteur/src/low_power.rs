@@ -0,0 +1,89 @@
+//! Low-power mode, for battery operation.
+//!
+//! Measures are taken every `DEFAULT_MEASURE_INTERVAL` seconds, unless set
+//! otherwise on the server, and buffered. The Wi-Fi is only powered up once
+//! `UPLOAD_BATCH_LEN` of them, or the batch length set on the server, are
+//! waiting, to upload them all, a batch per request, before powering the
+//! Wi-Fi chip down again.
+
+use core::cell::RefCell;
+
+use capteur_core::duty_cycle::{Action, DutyCycle, ReadingBuffer};
+use defmt::*;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use protocol::CriticalTask;
+
+use crate::capteur::{measure, measure_interval, Sensor, MEASURE_TIMEOUT};
+use crate::settings::settings;
+use crate::watchdog;
+use crate::Measure;
+
+/// Seconds between two measures, unless set on the server.
+const DEFAULT_MEASURE_INTERVAL: u64 = 300;
+/// Number of buffered measures triggering an upload.
+const UPLOAD_BATCH_LEN: usize = 12;
+/// Number of measures kept while the server is unreachable, a day's worth.
+const BUFFER_LEN: usize = 288;
+
+/// Measures waiting to be uploaded.
+pub static READINGS: Mutex<CriticalSectionRawMutex, RefCell<ReadingBuffer<Measure, BUFFER_LEN>>> =
+    Mutex::new(RefCell::new(ReadingBuffer::new()));
+/// Raised by the measure loop when a batch is ready to be uploaded.
+pub static UPLOAD_REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Raised by the network stack once the upload is over and the Wi-Fi chip
+/// is powered down.
+pub static UPLOAD_DONE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub fn buffered() -> usize {
+    READINGS.lock(|readings| readings.borrow().len())
+}
+
+/// Number of measures uploaded at once, unless set on the server.
+pub fn batch_len() -> usize {
+    settings()
+        .upload_batch_len
+        .map_or(UPLOAD_BATCH_LEN, usize::from)
+}
+
+/// Measures that can still be buffered before the oldest ones are dropped.
+pub fn free_slots() -> usize {
+    BUFFER_LEN - buffered()
+}
+
+pub async fn measure_loop(sensor: &mut Sensor<'_>) -> ! {
+    let mut duty_cycle = DutyCycle::new(DEFAULT_MEASURE_INTERVAL, UPLOAD_BATCH_LEN, BUFFER_LEN);
+
+    loop {
+        duty_cycle.set_interval(measure_interval(DEFAULT_MEASURE_INTERVAL));
+        duty_cycle.set_batch_len(batch_len());
+        let start = Instant::now();
+        watchdog::progress(CriticalTask::Measure, MEASURE_TIMEOUT);
+        if let Some(measure) = measure(sensor).await {
+            let dropped =
+                READINGS.lock(|readings| readings.borrow_mut().push(start.as_secs(), measure));
+            if dropped {
+                warn!("Measure buffer full, dropping the oldest measure");
+            }
+        }
+
+        let delay = match duty_cycle.measured(buffered(), start.elapsed().as_secs()) {
+            Action::Sleep(delay) => delay,
+            Action::Upload => {
+                // The network task is watched during the upload
+                watchdog::idle(CriticalTask::Measure);
+                UPLOAD_REQUEST.signal(());
+                UPLOAD_DONE.wait().await;
+                duty_cycle.uploaded(buffered(), start.elapsed().as_secs())
+            }
+        };
+        info!("Sleeping for {}s", delay);
+        watchdog::progress(
+            CriticalTask::Measure,
+            Duration::from_secs(delay) + MEASURE_TIMEOUT,
+        );
+        Timer::after_secs(delay).await;
+    }
+}
@@ -2,32 +2,68 @@
 #![no_main]
 
 pub mod capteur;
+pub mod config;
+pub mod crash;
+#[cfg(feature = "display")]
+pub mod display;
+pub mod health;
+pub mod http;
+pub mod led;
+#[cfg(feature = "low-power")]
+pub mod low_power;
+pub mod ota;
+pub mod panic;
+pub mod provisioning;
+pub mod radio;
+pub mod reboot;
 pub mod rtc;
+pub mod settings;
+pub mod sntp;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod watchdog;
 pub mod web;
+pub mod wifi;
 
 use crate::capteur::measure_task;
+use crate::watchdog::{watchdog_task, WATCHDOG_TIMEOUT};
 
 use defmt::*;
 use embassy_executor::Spawner;
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_rp::watchdog::Watchdog;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel, signal::Signal,
+};
+use protocol::Aggregate;
 use web::{network_stack, NetworkPeriphals};
 
-use {defmt_rtt as _, panic_probe as _};
+use defmt_rtt as _;
 
+#[derive(Clone)]
 pub struct Measure {
     pub temperature: f64,
     pub humidity: f64,
+    /// Number of valid samples the values are the median of.
+    pub samples: u8,
 }
 
 pub static NETWORK_STACK_SIGNAL: Signal<CriticalSectionRawMutex, bool> = Signal::new();
-pub static MEASURE_SIGNAL: Signal<CriticalSectionRawMutex, Measure> = Signal::new();
+/// Measures to send, with the statistics of the measures taken since the
+/// previous one sent, queued while the network task is busy.
+pub static MEASURES: Channel<CriticalSectionRawMutex, (Measure, Aggregate), 4> = Channel::new();
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
-    info!("Hello World!");
+    info!("Hello World! Firmware {}", ota::FIRMWARE_VERSION);
 
     let p = embassy_rp::init(Default::default());
+    reboot::init();
+    let mut watchdog = Watchdog::new(p.WATCHDOG);
+    watchdog.start(WATCHDOG_TIMEOUT);
+    unwrap!(spawner.spawn(watchdog_task(watchdog)));
     unwrap!(spawner.spawn(measure_task(p.PIN_21)));
+    #[cfg(feature = "display")]
+    unwrap!(spawner.spawn(display::display_task(p.I2C0, p.PIN_4, p.PIN_5)));
 
     let network_peripherals = NetworkPeriphals {
         pin23: p.PIN_23,
@@ -37,6 +73,7 @@ async fn main(spawner: Spawner) {
         pio: p.PIO0,
         dma: p.DMA_CH0,
         rtc: p.RTC,
+        flash: p.FLASH,
     };
     unwrap!(spawner.spawn(network_stack(spawner, network_peripherals)));
 }
@@ -0,0 +1,313 @@
+//! Over-the-air updates, see `capteur_core::ota`.
+//!
+//! The application runs from the ACTIVE partition of the bootloader in
+//! `bootloader/`, to flash once before the application. A newer image
+//! advertised by the server is downloaded to the DFU partition while it is
+//! verified, and the bootloader swaps it in at the next boot. The new image
+//! then has `CHECK_IN_TIMEOUT` to report itself to the server, otherwise the
+//! capteur resets and the bootloader swaps the previous image back.
+
+use core::fmt::write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use capteur_core::config::DeviceConfig;
+use capteur_core::ota::{is_update, parse_key, ImageVerifier, PublicKey};
+use defmt::*;
+use embassy_boot_rp::{AlignedBuffer, BlockingFirmwareUpdater, FirmwareUpdaterConfig, State};
+use embassy_embedded_hal::flash::partition::BlockingPartition;
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Duration, Timer};
+use embedded_io_async::Read;
+use embedded_nal_async::{Dns, TcpConnect};
+use heapless::String;
+use protocol::firmware::MANIFEST_JSON_LEN;
+use protocol::{FirmwareManifest, FirmwareReport, FirmwareVersion, RebootReason};
+use reqwless::client::HttpClient;
+use reqwless::request::Method;
+
+use crate::config::{ConfigStore, FLASH_SIZE};
+use crate::reboot;
+use crate::web::post_json;
+
+pub const FIRMWARE_VERSION: FirmwareVersion =
+    match FirmwareVersion::parse(env!("CARGO_PKG_VERSION")) {
+        Some(version) => version,
+        None => core::panic!("The package version is not major.minor.patch"),
+    };
+
+const PUBLIC_KEY: PublicKey = match parse_key(dotenvy_macro::dotenv!("FIRMWARE_PUBLIC_KEY")) {
+    Ok(key) => key,
+    Err(_) => core::panic!("FIRMWARE_PUBLIC_KEY is not an Ed25519 public key"),
+};
+
+/// How long a new image has to reach the server before it is rolled back.
+const CHECK_IN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Set once the running image reached the server, updates are only
+/// downloaded from then on.
+static CHECKED_IN: AtomicBool = AtomicBool::new(false);
+
+type Partition = BlockingPartition<
+    'static,
+    CriticalSectionRawMutex,
+    Flash<'static, FLASH, Blocking, FLASH_SIZE>,
+>;
+
+extern "C" {
+    static __bootloader_state_start: u32;
+    static __bootloader_state_end: u32;
+    static __bootloader_dfu_start: u32;
+    static __bootloader_dfu_end: u32;
+}
+
+/// Offset and size of a partition defined in `memory.x`.
+fn partition(start: &'static u32, end: &'static u32) -> (u32, u32) {
+    let start = start as *const u32 as u32;
+    let end = end as *const u32 as u32;
+    (start, end - start)
+}
+
+fn dfu_partition() -> (u32, u32) {
+    // SAFETY: only the addresses of the linker symbols are used.
+    unsafe { partition(&__bootloader_dfu_start, &__bootloader_dfu_end) }
+}
+
+fn updater(
+    store: ConfigStore,
+    aligned: &mut [u8],
+) -> BlockingFirmwareUpdater<'_, Partition, Partition> {
+    // SAFETY: only the addresses of the linker symbols are used.
+    let (state_offset, state_size) =
+        unsafe { partition(&__bootloader_state_start, &__bootloader_state_end) };
+    let (dfu_offset, dfu_size) = dfu_partition();
+    let config = FirmwareUpdaterConfig {
+        dfu: BlockingPartition::new(store.flash(), dfu_offset, dfu_size),
+        state: BlockingPartition::new(store.flash(), state_offset, state_size),
+    };
+    BlockingFirmwareUpdater::new(config, aligned)
+}
+
+/// Whether the bootloader just swapped a new image in, which is rolled back
+/// unless it checks in.
+pub fn is_new_image(store: ConfigStore) -> bool {
+    let mut aligned = AlignedBuffer([0; 1]);
+    matches!(updater(store, &mut aligned.0).get_state(), Ok(State::Swap))
+}
+
+/// Reset, for the bootloader to roll the image back, unless it checks in
+/// within `CHECK_IN_TIMEOUT`.
+#[embassy_executor::task]
+pub async fn rollback_task() {
+    warn!(
+        "Running firmware {} for the first time, rolling back unless it checks in",
+        FIRMWARE_VERSION
+    );
+    Timer::after(CHECK_IN_TIMEOUT).await;
+    if !CHECKED_IN.load(Ordering::Relaxed) {
+        error!("Firmware did not check in, rolling back");
+        Timer::after_millis(100).await;
+        reboot::reset(RebootReason::Rollback);
+    }
+}
+
+/// Report the running firmware to the server, marking a new image as booted
+/// on success so that it is kept.
+pub async fn check_in<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    store: ConfigStore,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    if CHECKED_IN.load(Ordering::Relaxed) {
+        return;
+    }
+    let report = FirmwareReport {
+        version: FIRMWARE_VERSION,
+    };
+    if !post_json(
+        http_client,
+        device_config,
+        "firmware",
+        &report,
+        &mut [0; 64],
+    )
+    .await
+    {
+        return;
+    }
+
+    let mut aligned = AlignedBuffer([0; 1]);
+    let mut updater = updater(store, &mut aligned.0);
+    if matches!(updater.get_state(), Ok(State::Swap)) {
+        match updater.mark_booted() {
+            Ok(()) => info!("Firmware {} checked in, keeping it", FIRMWARE_VERSION),
+            Err(err) => {
+                error!("Unable to mark the firmware as booted: {}", err);
+                return;
+            }
+        }
+    }
+    CHECKED_IN.store(true, Ordering::Relaxed);
+}
+
+/// Download and install the firmware advertised by the server when it is
+/// newer than the running one, then reset for the bootloader to swap it in.
+pub async fn update_firmware<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    store: ConfigStore,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    if !CHECKED_IN.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut url: String<160> = String::new();
+    let _ = write(
+        &mut url,
+        format_args!(
+            "{}/capteurs/{}/firmware",
+            device_config.api_url, device_config.capteur_id
+        ),
+    );
+    let Some(manifest) = fetch_manifest(http_client, &url).await else {
+        return;
+    };
+    if !is_update(&manifest, FIRMWARE_VERSION) {
+        return;
+    }
+    info!(
+        "Downloading firmware {}, {} bytes",
+        manifest.version, manifest.size
+    );
+
+    let (_, dfu_size) = dfu_partition();
+    let mut verifier = match ImageVerifier::new(&manifest, dfu_size as usize) {
+        Ok(verifier) => verifier,
+        Err(err) => {
+            warn!("Invalid firmware manifest: {}", err);
+            return;
+        }
+    };
+    url.clear();
+    let _ = write(
+        &mut url,
+        format_args!("{}{}", device_config.api_url, manifest.url),
+    );
+    let mut aligned = AlignedBuffer([0; 1]);
+    let mut updater = updater(store, &mut aligned.0);
+    if !download(http_client, &url, &mut verifier, &mut updater).await {
+        return;
+    }
+
+    if let Err(err) = verifier.verify(&PUBLIC_KEY) {
+        error!("Rejecting firmware {}: {}", manifest.version, err);
+        return;
+    }
+    match updater.mark_updated() {
+        Ok(()) => {
+            info!("Firmware {} installed, rebooting", manifest.version);
+            Timer::after_millis(100).await;
+            reboot::reset(RebootReason::FirmwareUpdate);
+        }
+        Err(err) => error!("Unable to mark the firmware as updated: {}", err),
+    }
+}
+
+async fn fetch_manifest<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    url: &str,
+) -> Option<FirmwareManifest>
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let mut request = match http_client.request(Method::GET, url).await {
+        Ok(request) => request,
+        Err(_) => {
+            warn!("Error when building the request, passing...");
+            return None;
+        }
+    };
+    let mut rx_buffer = [0; 1024];
+    let response = match request.send(&mut rx_buffer).await {
+        Ok(response) if response.status.is_successful() => response,
+        // No firmware published for the group of the capteur
+        Ok(response) if response.status.is_client_error() => return None,
+        _ => {
+            warn!("Unable to fetch the firmware manifest, passing...");
+            return None;
+        }
+    };
+    let Ok(body) = response.body().read_to_end().await else {
+        warn!("Unable to read the firmware manifest, passing...");
+        return None;
+    };
+    if body.len() > MANIFEST_JSON_LEN {
+        warn!("Firmware manifest too long, passing...");
+        return None;
+    }
+    match serde_json_core::from_slice::<FirmwareManifest>(body) {
+        Ok((manifest, _)) => Some(manifest),
+        Err(_) => {
+            warn!("Invalid firmware manifest, passing...");
+            None
+        }
+    }
+}
+
+/// Stream the image at `url` to the DFU partition, returning whether it was
+/// entirely written.
+async fn download<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    url: &str,
+    verifier: &mut ImageVerifier,
+    updater: &mut BlockingFirmwareUpdater<'_, Partition, Partition>,
+) -> bool
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let mut request = match http_client.request(Method::GET, url).await {
+        Ok(request) => request,
+        Err(_) => {
+            warn!("Error when building the request, passing...");
+            return false;
+        }
+    };
+    let mut rx_buffer = [0; 1024];
+    let response = match request.send(&mut rx_buffer).await {
+        Ok(response) if response.status.is_successful() => response,
+        _ => {
+            warn!("Unable to download the firmware, passing...");
+            return false;
+        }
+    };
+
+    let mut reader = response.body().reader();
+    let mut chunk = [0; 1024];
+    loop {
+        let len = match reader.read(&mut chunk).await {
+            Ok(0) => return true,
+            Ok(len) => len,
+            Err(_) => {
+                warn!("Firmware download interrupted");
+                return false;
+            }
+        };
+        let offset = verifier.received() as usize;
+        if let Err(err) = verifier.update(&chunk[..len]) {
+            warn!("Unexpected firmware image: {}", err);
+            return false;
+        }
+        if let Err(err) = updater.write_firmware(offset, &chunk[..len]) {
+            error!("Unable to write the firmware: {}", err);
+            return false;
+        }
+    }
+}
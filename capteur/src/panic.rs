@@ -0,0 +1,24 @@
+//! Panic handler, logging the panic and resetting the capteur, recording the
+//! panic as the reboot reason and keeping its report for the server.
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{error, Display2Format};
+use protocol::RebootReason;
+
+use crate::{crash, reboot};
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    // Only log the first panic, a second one happening while logging it.
+    if !PANICKED.load(Ordering::Relaxed) {
+        PANICKED.store(true, Ordering::Relaxed);
+        error!("{}", Display2Format(info));
+        crash::record(info);
+    }
+    reboot::reset(RebootReason::Panic)
+}
@@ -0,0 +1,215 @@
+use capteur_core::dhcp::{self, DHCP_CLIENT_PORT, DHCP_REPLY_LEN, DHCP_SERVER_PORT};
+use capteur_core::provisioning::{
+    parse_form, parse_request, HttpError, Method, FORM_PAGE, SAVED_PAGE,
+};
+use capteur_core::status::Status;
+use core::fmt::write;
+use defmt::*;
+use dotenvy_macro::*;
+use embassy_futures::select::select;
+use embassy_net::driver::Driver;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{ConfigV4, IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr, Stack, StaticConfigV4};
+use embassy_time::{with_timeout, Duration, Timer};
+use heapless::{String, Vec};
+use protocol::RebootReason;
+
+use crate::config::ConfigStore;
+use crate::led::set_status;
+use crate::reboot;
+use crate::wifi::SharedControl;
+
+/// Access point opened when the capteur is not provisioned, or cannot join
+/// its Wi-Fi network.
+const PROVISIONING_SSID: &str = "envirometer-setup";
+const PROVISIONING_PASSWORD: &str = dotenv!("PROVISIONING_PASSWORD");
+// WPA2 passphrases are 8 to 63 characters long
+const _: () = if PROVISIONING_PASSWORD.len() < 8 || PROVISIONING_PASSWORD.len() > 63 {
+    core::panic!("PROVISIONING_PASSWORD must be 8 to 63 characters long");
+};
+const PROVISIONING_CHANNEL: u8 = 6;
+const AP_ADDRESS: [u8; 4] = [192, 168, 4, 1];
+const HTTP_PORT: u16 = 80;
+
+/// Open the provisioning access point and serve the setup form until a new
+/// configuration is saved, or until `timeout` expires, then reboot.
+pub async fn provisioning_mode<D: Driver>(
+    control: &SharedControl<'_>,
+    stack: &Stack<D>,
+    store: &mut ConfigStore,
+    timeout: Option<Duration>,
+) -> ! {
+    info!(
+        "Starting the provisioning access point {}",
+        PROVISIONING_SSID
+    );
+    set_status(Status::Provisioning);
+    control
+        .lock()
+        .await
+        .start_ap_wpa2(
+            PROVISIONING_SSID,
+            PROVISIONING_PASSWORD,
+            PROVISIONING_CHANNEL,
+        )
+        .await;
+    stack.set_config_v4(ConfigV4::Static(StaticConfigV4 {
+        address: Ipv4Cidr::new(Ipv4Address::from_bytes(&AP_ADDRESS), 24),
+        gateway: None,
+        dns_servers: Vec::new(),
+    }));
+
+    let servers = select(dhcp_server(stack), http_server(stack, store));
+    match timeout {
+        Some(timeout) => {
+            if with_timeout(timeout, servers).await.is_err() {
+                info!("Provisioning timed out");
+            }
+        }
+        None => {
+            servers.await;
+        }
+    }
+
+    info!("Rebooting...");
+    Timer::after_secs(1).await;
+    reboot::reset(RebootReason::Requested)
+}
+
+async fn dhcp_server<D: Driver>(stack: &Stack<D>) -> ! {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; 2048];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 4 * DHCP_REPLY_LEN];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    unwrap!(socket.bind(DHCP_SERVER_PORT));
+
+    let broadcast = IpEndpoint::new(IpAddress::v4(255, 255, 255, 255), DHCP_CLIENT_PORT);
+    let mut request = [0; 576];
+    let mut reply = [0; DHCP_REPLY_LEN];
+    loop {
+        let len = match socket.recv_from(&mut request).await {
+            Ok((len, _)) => len,
+            Err(err) => {
+                warn!("DHCP receive error: {}", err);
+                continue;
+            }
+        };
+        if let Some(len) = dhcp::reply(&request[..len], AP_ADDRESS, &mut reply) {
+            if let Err(err) = socket.send_to(&reply[..len], broadcast).await {
+                warn!("DHCP send error: {}", err);
+            }
+        }
+    }
+}
+
+/// Serve the setup form, returning once a configuration has been saved.
+async fn http_server<D: Driver>(stack: &Stack<D>, store: &mut ConfigStore) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 2048];
+    let mut request_buffer = [0; 1024];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+
+        if let Err(err) = socket.accept(HTTP_PORT).await {
+            warn!("Accept error: {}", err);
+            continue;
+        }
+
+        let saved = handle_request(&mut socket, &mut request_buffer, store).await;
+        socket.close();
+        let _ = socket.flush().await;
+
+        if saved {
+            return;
+        }
+    }
+}
+
+/// Answer one request, returning whether a new configuration was saved.
+async fn handle_request(
+    socket: &mut TcpSocket<'_>,
+    buffer: &mut [u8],
+    store: &mut ConfigStore,
+) -> bool {
+    let mut len = 0;
+    let request = loop {
+        match socket.read(&mut buffer[len..]).await {
+            Ok(0) | Err(_) => return false,
+            Ok(read) => len += read,
+        }
+        match parse_request(&buffer[..len]) {
+            Err(HttpError::Incomplete) if len < buffer.len() => continue,
+            Err(_) => {
+                respond(socket, "400 Bad Request", "Bad request").await;
+                return false;
+            }
+            Ok(request) => break request,
+        }
+    };
+
+    // Any other path gets the form too, so that captive portal detection
+    // brings it up.
+    if request.method != Method::Post {
+        respond(socket, "200 OK", FORM_PAGE).await;
+        return false;
+    }
+
+    let config = match parse_form(request.body) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("Invalid provisioning form: {}", err);
+            respond(socket, "400 Bad Request", FORM_PAGE).await;
+            return false;
+        }
+    };
+
+    match store.save(&config) {
+        Ok(()) => {
+            info!("Configuration saved for capteur {}", config.capteur_id);
+            respond(socket, "200 OK", SAVED_PAGE).await;
+            true
+        }
+        Err(err) => {
+            error!("Unable to save the configuration: {}", err);
+            respond(socket, "500 Internal Server Error", "Unable to save").await;
+            false
+        }
+    }
+}
+
+async fn respond(socket: &mut TcpSocket<'_>, status: &str, body: &str) {
+    let mut head: String<128> = String::new();
+    let _ = write(
+        &mut head,
+        format_args!(
+            "HTTP/1.1 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ),
+    );
+
+    if write_all(socket, head.as_bytes()).await.is_err()
+        || write_all(socket, body.as_bytes()).await.is_err()
+    {
+        warn!("Unable to send the response");
+    }
+}
+
+async fn write_all(socket: &mut TcpSocket<'_>, mut bytes: &[u8]) -> Result<(), ()> {
+    while !bytes.is_empty() {
+        match socket.write(bytes).await {
+            Ok(0) | Err(_) => return Err(()),
+            Ok(written) => bytes = &bytes[written..],
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! The cyw43 Wi-Fi chip, only powered for the time of a session with the
+//! network stack over it. The low-power mode powers it down between uploads,
+//! and up again, loading its firmware anew, for the next one.
+
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+use cyw43::NetDriver;
+use cyw43_pio::PioSpi;
+use defmt::*;
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_net::{Config, Stack, StackResources};
+use embassy_rp::bind_interrupts;
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::peripherals::{DMA_CH0, PIN_23, PIN_24, PIN_25, PIN_29, PIO0};
+use embassy_rp::pio::{Common, InterruptHandler, Pio};
+use embassy_sync::mutex::Mutex;
+use rand::RngCore;
+
+use crate::led::show_status;
+use crate::wifi::SharedControl;
+
+bind_interrupts!(struct Irqs {
+    PIO0_IRQ_0 => InterruptHandler<PIO0>;
+});
+
+const FIRMWARE: &[u8] = include_bytes!("../cyw43-firmware/43439A0.bin");
+const CLM: &[u8] = include_bytes!("../cyw43-firmware/43439A0_clm.bin");
+
+/// State of the driver and sockets of the network stack, used by one session
+/// at a time and set anew for each.
+static mut STATE: MaybeUninit<cyw43::State> = MaybeUninit::uninit();
+static mut RESOURCES: StackResources<5> = StackResources::new();
+
+type Spi = PioSpi<'static, PIO0, 0, DMA_CH0>;
+
+pub struct Radio {
+    /// WL_ON, powering the chip.
+    pwr: Output<'static>,
+    spi: Spi,
+    /// Keeps the SPI program loaded in the PIO.
+    _pio: Common<'static, PIO0>,
+}
+
+impl Radio {
+    pub fn new(pwr: PIN_23, dio: PIN_24, cs: PIN_25, clk: PIN_29, pio: PIO0, dma: DMA_CH0) -> Self {
+        let Pio {
+            mut common,
+            sm0,
+            irq0,
+            ..
+        } = Pio::new(pio, Irqs);
+        let cs = Output::new(cs, Level::High);
+        let spi = PioSpi::new(&mut common, sm0, irq0, cs, dio, clk, dma);
+        Self {
+            pwr: Output::new(pwr, Level::Low),
+            spi,
+            _pio: common,
+        }
+    }
+
+    /// Power the chip up and run `session` with the network stack over it, the
+    /// status being shown on the LED, then power the chip down.
+    pub async fn run<R>(
+        &mut self,
+        session: impl AsyncFnOnce(&Stack<NetDriver<'static>>, &SharedControl<'static>) -> R,
+    ) -> R {
+        // SAFETY: there is a single radio, the peripherals it owns being
+        // unique, and its sessions do not overlap, `self` being borrowed for
+        // the whole of one. The driver and the stack of the previous session,
+        // the only users of the state and resources, were dropped at its end.
+        let (state, resources) = unsafe {
+            let resources = &mut *addr_of_mut!(RESOURCES);
+            *resources = StackResources::new();
+            ((*addr_of_mut!(STATE)).write(cyw43::State::new()), resources)
+        };
+        let (net_device, mut control, runner) =
+            cyw43::new(state, &mut self.pwr, SpiBus(&mut self.spi), FIRMWARE).await;
+
+        let powered = async {
+            control.init(CLM).await;
+            control
+                .set_power_management(cyw43::PowerManagementMode::PowerSave)
+                .await;
+            let control = Mutex::new(control);
+            let stack = Stack::new(
+                net_device,
+                Config::dhcpv4(Default::default()),
+                resources,
+                RoscRng.next_u64(),
+            );
+
+            match select3(
+                stack.run(),
+                show_status(&control),
+                session(&stack, &control),
+            )
+            .await
+            {
+                Either3::First(never) | Either3::Second(never) => never,
+                Either3::Third(result) => result,
+            }
+        };
+        let result = match select(runner.run(), powered).await {
+            Either::First(never) => never,
+            Either::Second(result) => result,
+        };
+
+        // The driver is dropped, the chip can lose its state
+        self.pwr.set_low();
+        info!("Wi-Fi chip powered down");
+        result
+    }
+}
+
+/// The SPI bus of the chip, lent to the driver of one session.
+struct SpiBus<'s>(&'s mut Spi);
+
+impl cyw43::SpiBusCyw43 for SpiBus<'_> {
+    async fn cmd_write(&mut self, write: &[u32]) -> u32 {
+        self.0.cmd_write(write).await
+    }
+
+    async fn cmd_read(&mut self, write: u32, read: &mut [u32]) -> u32 {
+        self.0.cmd_read(write, read).await
+    }
+
+    async fn wait_for_event(&mut self) {
+        self.0.wait_for_event().await
+    }
+}
@@ -0,0 +1,62 @@
+//! Reboot reasons, see `capteur_core::reboot`.
+
+use core::cell::Cell;
+
+use capteur_core::reboot::{encode_record, reboot_report, ResetFlags};
+use cortex_m::peripheral::SCB;
+use defmt::*;
+use embassy_rp::pac;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use protocol::{CriticalTask, RebootReason, RebootReport};
+
+/// Reason of the last reboot, until it is reported to the server.
+static PENDING_REPORT: Mutex<CriticalSectionRawMutex, Cell<Option<RebootReport>>> =
+    Mutex::new(Cell::new(None));
+
+/// Find out why the capteur rebooted, to be called once at boot.
+pub fn init() {
+    let watchdog = pac::WATCHDOG;
+    let record = [watchdog.scratch0().read(), watchdog.scratch1().read()];
+    let reason = watchdog.reason().read();
+    let chip_reset = pac::VREG_AND_CHIP_RESET.chip_reset().read();
+    let report = reboot_report(
+        record,
+        ResetFlags {
+            watchdog_timeout: reason.timer(),
+            watchdog_forced: reason.force(),
+            power_on: chip_reset.had_por(),
+            run_pin: chip_reset.had_run(),
+            debugger: chip_reset.had_psm_restart(),
+        },
+    );
+    // The record only relates to this boot.
+    watchdog.scratch0().write(|w| *w = 0);
+
+    info!("Rebooted: {}", report);
+    PENDING_REPORT.lock(|pending| pending.set(Some(report)));
+}
+
+/// Record why the capteur is about to reset, for the next boot.
+pub fn record(reason: RebootReason, task: Option<CriticalTask>) {
+    let [reason, uptime] = encode_record(reason, task, Instant::now().as_secs());
+    let watchdog = pac::WATCHDOG;
+    watchdog.scratch1().write(|w| *w = uptime);
+    watchdog.scratch0().write(|w| *w = reason);
+}
+
+/// Reset the capteur, recording `reason`.
+pub fn reset(reason: RebootReason) -> ! {
+    record(reason, None);
+    SCB::sys_reset()
+}
+
+/// Reason of the last reboot, if not reported yet.
+pub fn pending_report() -> Option<RebootReport> {
+    PENDING_REPORT.lock(Cell::get)
+}
+
+pub fn reported() {
+    PENDING_REPORT.lock(|pending| pending.set(None));
+}
@@ -1,134 +1,122 @@
-use core::fmt::write;
-use core::str::Utf8Error;
+use capteur_core::drift::DriftTracker;
+use capteur_core::rtc::Weekday;
+use capteur_core::time_sync::{self, SyncError, Synced};
 use defmt::*;
-use dotenvy_macro::*;
+use embassy_net::driver::Driver;
+use embassy_net::Stack;
 use embassy_rp::peripherals::RTC;
-use embassy_rp::rtc::{DateTime, DayOfWeek, Rtc, RtcError};
+use embassy_rp::rtc::{DateTime, DayOfWeek, Rtc};
+use embassy_time::Instant;
 use embedded_nal_async::{Dns, TcpConnect};
-use heapless::String;
-use reqwless::{client::HttpClient, request::Method};
-use serde::Deserialize;
+use protocol::{ClockDrift, Timestamp, TimestampError};
+use reqwless::client::HttpClient;
 
-const API_URL: &str = dotenv!("API_URL");
-
-#[derive(Deserialize, Debug, Format)]
-struct ApiResponse<'a> {
-    now: &'a str,
-    weekday: u8,
-}
+use crate::health;
+use crate::http::{Api, HttpError};
+use crate::sntp::sntp_now;
+#[cfg(feature = "tls")]
+use crate::tls::set_unix_time;
 
+/// The RP2040 RTC refused the time.
 #[derive(Format, Debug)]
-pub enum RTCInitError {
-    APIError,
-    InvalidAPIResponse,
-    APIUnavailable,
-    RtcError,
-    DateTimeError,
-}
+pub struct InvalidDateTime;
 
-impl From<reqwless::Error> for RTCInitError {
-    fn from(value: reqwless::Error) -> Self {
-        warn!("{}", value);
-        Self::APIError
-    }
-}
+pub type RTCSyncError = SyncError<InvalidDateTime, HttpError>;
 
-impl From<core::fmt::Error> for RTCInitError {
-    fn from(_: core::fmt::Error) -> Self {
-        Self::APIError
-    }
-}
+/// The RP2040 RTC, for the logic of `capteur_core`.
+pub struct BoardRtc<'a>(pub Rtc<'a, RTC>);
+
+impl capteur_core::rtc::Rtc for BoardRtc<'_> {
+    type Error = InvalidDateTime;
 
-impl From<Utf8Error> for RTCInitError {
-    fn from(_: Utf8Error) -> Self {
-        Self::InvalidAPIResponse
+    fn now(&self) -> Option<Timestamp> {
+        self.0
+            .now()
+            .ok()
+            .and_then(|now| timestamp_from_datetime(&now).ok())
     }
-}
 
-impl From<RtcError> for RTCInitError {
-    fn from(_: RtcError) -> Self {
-        Self::RtcError
+    fn set(&mut self, now: Timestamp) -> Result<(), InvalidDateTime> {
+        self.0
+            .set_datetime(datetime_from_timestamp(now))
+            .map_err(|_| InvalidDateTime)
     }
 }
 
-impl TryFrom<ApiResponse<'_>> for DateTime {
-    type Error = RTCInitError;
+fn timestamp_from_datetime(now: &DateTime) -> Result<Timestamp, TimestampError> {
+    Timestamp::new(
+        now.year, now.month, now.day, now.hour, now.minute, now.second,
+    )
+}
 
-    fn try_from(value: ApiResponse<'_>) -> Result<Self, Self::Error> {
-        Ok(Self {
-            year: value.now[0..4]
-                .parse::<u16>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-            month: value.now[5..7]
-                .parse::<u8>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-            day: value.now[8..10]
-                .parse::<u8>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-            day_of_week: day_of_week_from_u8(value.weekday).unwrap_or(DayOfWeek::Monday),
-            hour: value.now[11..13]
-                .parse::<u8>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-            minute: value.now[14..16]
-                .parse::<u8>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-            second: value.now[17..19]
-                .parse::<u8>()
-                .map_err(|_| RTCInitError::DateTimeError)?,
-        })
+fn datetime_from_timestamp(timestamp: Timestamp) -> DateTime {
+    DateTime {
+        year: timestamp.year,
+        month: timestamp.month,
+        day: timestamp.day,
+        day_of_week: day_of_week(Weekday::of(&timestamp)),
+        hour: timestamp.hour,
+        minute: timestamp.minute,
+        second: timestamp.second,
     }
 }
 
-fn day_of_week_from_u8(v: u8) -> Result<DayOfWeek, ()> {
-    Ok(match v {
-        0 => DayOfWeek::Sunday,
-        1 => DayOfWeek::Monday,
-        2 => DayOfWeek::Tuesday,
-        3 => DayOfWeek::Wednesday,
-        4 => DayOfWeek::Thursday,
-        5 => DayOfWeek::Friday,
-        6 => DayOfWeek::Saturday,
-        _ => return Err(()),
-    })
+fn day_of_week(weekday: Weekday) -> DayOfWeek {
+    match weekday {
+        Weekday::Sunday => DayOfWeek::Sunday,
+        Weekday::Monday => DayOfWeek::Monday,
+        Weekday::Tuesday => DayOfWeek::Tuesday,
+        Weekday::Wednesday => DayOfWeek::Wednesday,
+        Weekday::Thursday => DayOfWeek::Thursday,
+        Weekday::Friday => DayOfWeek::Friday,
+        Weekday::Saturday => DayOfWeek::Saturday,
+    }
 }
 
-pub async fn init_rtc<'a, T, U>(
+/// Set the RTC from an SNTP server, falling back to the `/now` endpoint of the
+/// API when SNTP is disabled or fails.
+///
+/// Returns how much the RTC drifted since the previous synchronisation.
+pub async fn sync_rtc<'a, D, T, U>(
+    stack: &Stack<D>,
     http_client: &mut HttpClient<'a, T, U>,
-    rtc: &mut Rtc<'a, RTC>,
-) -> Result<(), RTCInitError>
+    rtc: &mut BoardRtc<'_>,
+    drift_tracker: &mut DriftTracker,
+    api_url: &str,
+) -> Result<Option<ClockDrift>, RTCSyncError>
 where
+    D: Driver,
     T: TcpConnect + 'a,
     U: Dns + 'a,
 {
-    let mut url: String<100> = String::new();
-    write(&mut url, format_args!("{API_URL}/now"))?;
-
-    let mut request = match http_client.request(Method::GET, &url).await {
-        Ok(request) => request,
+    let sntp = match sntp_now(stack).await {
+        Ok(now) => Some(now),
         Err(err) => {
-            warn!("Error when building the request, passing... {}", err);
-            return Err(RTCInitError::APIError);
+            warn!(
+                "SNTP synchronisation failed ({}), using {}/now",
+                err, api_url
+            );
+            None
         }
     };
 
-    let mut rx_buffer = [0; 8192];
-    let body = request
-        .send(&mut rx_buffer)
-        .await?
-        .body()
-        .read_to_end()
-        .await?;
-    let response = match serde_json_core::de::from_slice::<ApiResponse>(body) {
-        Ok((output, _)) => {
-            info!("Api response: {}", output);
-            output
-        }
-        Err(_) => {
-            return Err(RTCInitError::InvalidAPIResponse);
-        }
-    };
+    let Synced { reference, drift } = time_sync::sync_rtc(
+        rtc,
+        &mut Api(http_client),
+        drift_tracker,
+        sntp,
+        api_url,
+        Instant::now().as_secs(),
+    )
+    .await?;
+    info!("RTC set to {}", reference);
+    health::rtc_synced(reference);
+    #[cfg(feature = "tls")]
+    set_unix_time(reference.to_unix(), Instant::now().as_secs());
 
-    rtc.set_datetime(DateTime::try_from(response)?)?;
+    if let Some(drift) = &drift {
+        info!("RTC drifted by {}s in {}s", drift.drift, drift.elapsed);
+    }
 
-    Ok(())
+    Ok(drift)
 }
@@ -0,0 +1,30 @@
+//! Settings set on the server, see `protocol::CapteurSettings`.
+
+use core::cell::Cell;
+
+use defmt::*;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use protocol::CapteurSettings;
+
+static SETTINGS: Mutex<CriticalSectionRawMutex, Cell<CapteurSettings>> =
+    Mutex::new(Cell::new(CapteurSettings::new()));
+
+/// Settings currently applied.
+pub fn settings() -> CapteurSettings {
+    SETTINGS.lock(Cell::get)
+}
+
+/// Whether every sample and measure is logged.
+pub fn verbose() -> bool {
+    SETTINGS.lock(|settings| settings.get().verbose)
+}
+
+pub fn apply(settings: CapteurSettings) {
+    info!(
+        "Applying settings version {}, verbose logs {}",
+        settings.version,
+        if settings.verbose { "on" } else { "off" }
+    );
+    SETTINGS.lock(|current| current.set(settings));
+}
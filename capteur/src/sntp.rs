@@ -0,0 +1,77 @@
+use capteur_core::sntp::{self, SntpError, NTP_PACKET_LEN, NTP_PORT};
+use defmt::*;
+use dotenvy_macro::*;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::driver::Driver;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_rp::clocks::RoscRng;
+use embassy_time::{with_timeout, Duration, Instant};
+use protocol::Timestamp;
+use rand::RngCore;
+
+/// NTP server to synchronise the RTC with, leave empty to only rely on the
+/// `/now` endpoint of the API.
+const NTP_SERVER: &str = dotenv!("NTP_SERVER");
+const SNTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Format, Debug)]
+pub enum SntpClientError {
+    Disabled,
+    DnsError,
+    SocketError,
+    Timeout,
+    InvalidResponse(SntpError),
+}
+
+impl From<SntpError> for SntpClientError {
+    fn from(value: SntpError) -> Self {
+        Self::InvalidResponse(value)
+    }
+}
+
+/// Query the current UTC time from `NTP_SERVER`.
+pub async fn sntp_now<D: Driver>(stack: &Stack<D>) -> Result<Timestamp, SntpClientError> {
+    if NTP_SERVER.is_empty() {
+        return Err(SntpClientError::Disabled);
+    }
+
+    let address = *stack
+        .dns_query(NTP_SERVER, DnsQueryType::A)
+        .await
+        .map_err(|_| SntpClientError::DnsError)?
+        .first()
+        .ok_or(SntpClientError::DnsError)?;
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; NTP_PACKET_LEN];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; NTP_PACKET_LEN];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    socket.bind(0).map_err(|_| SntpClientError::SocketError)?;
+
+    let nonce = RoscRng.next_u64();
+    let start = Instant::now();
+    socket
+        .send_to(&sntp::request(nonce), IpEndpoint::new(address, NTP_PORT))
+        .await
+        .map_err(|_| SntpClientError::SocketError)?;
+
+    let mut response = [0; NTP_PACKET_LEN];
+    let (len, _) = with_timeout(SNTP_TIMEOUT, socket.recv_from(&mut response))
+        .await
+        .map_err(|_| SntpClientError::Timeout)?
+        .map_err(|_| SntpClientError::SocketError)?;
+    let round_trip = start.elapsed().as_millis();
+
+    let time = sntp::parse_response(&response[..len], nonce)?;
+    info!("SNTP response from a stratum {} server", time.stratum);
+
+    Ok(time.to_timestamp(round_trip)?)
+}
@@ -0,0 +1,319 @@
+//! TLS connections to the API, authenticating the server.
+//!
+//! The TLS support of reqwless does not verify the server, so the handshake is
+//! done here, below reqwless, which only sees an already secured connection.
+//! By default the server is pinned by the fingerprint of its public key, set
+//! at build time by `TLS_SERVER_KEY_SHA256`. The `tls-ca` feature verifies its
+//! certificate against the CA in `certs/ca.der` instead, and `insecure-tls`
+//! disables the verification for local development.
+
+use core::cell::{Cell, UnsafeCell};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use defmt::*;
+use embassy_rp::clocks::RoscRng;
+use embedded_io_async::{Error as _, ErrorKind, ErrorType, Read, Write};
+use embedded_nal_async::{SocketAddr, TcpConnect};
+use embedded_tls::{
+    Aes128GcmSha256, CryptoProvider, TlsConfig, TlsConnection, TlsContext, TlsError, TlsVerifier,
+};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{CryptoRngCore, RngCore, SeedableRng};
+
+#[cfg(all(feature = "tls-ca", feature = "insecure-tls"))]
+compile_error!("the `tls-ca` and `insecure-tls` features are mutually exclusive");
+
+#[cfg(not(any(feature = "tls-ca", feature = "insecure-tls")))]
+const SERVER_KEY_PIN: capteur_core::tls::Fingerprint =
+    match capteur_core::tls::parse_fingerprint(dotenvy_macro::dotenv!("TLS_SERVER_KEY_SHA256")) {
+        Ok(pin) => pin,
+        Err(_) => core::panic!("TLS_SERVER_KEY_SHA256 is not a SHA-256 fingerprint"),
+    };
+
+#[cfg(feature = "tls-ca")]
+const CA_CERTIFICATE: &[u8] = include_bytes!("../certs/ca.der");
+/// Largest server certificate accepted when verifying against the CA.
+#[cfg(feature = "tls-ca")]
+const MAX_CERTIFICATE_LEN: usize = 4096;
+
+type CipherSuite = Aes128GcmSha256;
+
+#[cfg(not(any(feature = "tls-ca", feature = "insecure-tls")))]
+type Verifier = capteur_core::tls::PinnedKeyVerifier<CipherSuite>;
+#[cfg(feature = "tls-ca")]
+type Verifier =
+    embedded_tls::pki::CertVerifier<'static, CipherSuite, RtcClock, MAX_CERTIFICATE_LEN>;
+#[cfg(feature = "insecure-tls")]
+type Verifier = embedded_tls::NoVerify;
+
+fn verifier() -> Verifier {
+    #[cfg(not(any(feature = "tls-ca", feature = "insecure-tls")))]
+    return capteur_core::tls::PinnedKeyVerifier::new(SERVER_KEY_PIN);
+    #[cfg(feature = "tls-ca")]
+    return embedded_tls::pki::CertVerifier::new(embedded_tls::Certificate::X509(CA_CERTIFICATE));
+    #[cfg(feature = "insecure-tls")]
+    return embedded_tls::NoVerify;
+}
+
+/// Unix time at boot, known once the RTC is synchronised.
+static UNIX_TIME_AT_BOOT: AtomicU32 = AtomicU32::new(0);
+
+/// Record the current time, to check the validity period of certificates.
+pub fn set_unix_time(unix_now: i64, uptime: u64) {
+    let at_boot = u32::try_from(unix_now - uptime as i64).unwrap_or(0);
+    UNIX_TIME_AT_BOOT.store(at_boot, Ordering::Relaxed);
+}
+
+/// Clock of the certificate verification. Until the RTC is synchronised the
+/// time is unknown and the validity period of certificates is not checked.
+#[cfg(feature = "tls-ca")]
+struct RtcClock;
+
+#[cfg(feature = "tls-ca")]
+impl embedded_tls::TlsClock for RtcClock {
+    fn now() -> Option<u64> {
+        match UNIX_TIME_AT_BOOT.load(Ordering::Relaxed) {
+            0 => None,
+            at_boot => Some(at_boot as u64 + embassy_time::Instant::now().as_secs()),
+        }
+    }
+}
+
+struct Provider {
+    verifier: Verifier,
+    rng: ChaCha20Rng,
+}
+
+impl CryptoProvider for Provider {
+    type CipherSuite = CipherSuite;
+    type Signature = [u8; 0];
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<CipherSuite>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+}
+
+/// Record buffers of the TLS connection, one connection at a time.
+pub struct TlsBuffers<const RX: usize, const TX: usize> {
+    in_use: Cell<bool>,
+    read: UnsafeCell<[u8; RX]>,
+    write: UnsafeCell<[u8; TX]>,
+}
+
+impl<const RX: usize, const TX: usize> TlsBuffers<RX, TX> {
+    pub const fn new() -> Self {
+        Self {
+            in_use: Cell::new(false),
+            read: UnsafeCell::new([0; RX]),
+            write: UnsafeCell::new([0; TX]),
+        }
+    }
+}
+
+impl<const RX: usize, const TX: usize> Default for TlsBuffers<RX, TX> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// TCP connector securing the connections with TLS when the API is served
+/// over HTTPS.
+pub struct TlsConnector<'a, T, const RX: usize, const TX: usize> {
+    tcp: &'a T,
+    buffers: &'a TlsBuffers<RX, TX>,
+    server_name: &'a str,
+    tls: bool,
+}
+
+impl<'a, T: TcpConnect, const RX: usize, const TX: usize> TlsConnector<'a, T, RX, TX> {
+    pub fn new(
+        tcp: &'a T,
+        buffers: &'a TlsBuffers<RX, TX>,
+        server_name: &'a str,
+        tls: bool,
+    ) -> Self {
+        #[cfg(feature = "insecure-tls")]
+        if tls {
+            warn!("The certificate of {} will not be verified", server_name);
+        }
+
+        Self {
+            tcp,
+            buffers,
+            server_name,
+            tls,
+        }
+    }
+}
+
+impl<T: TcpConnect, const RX: usize, const TX: usize> TcpConnect for TlsConnector<'_, T, RX, TX> {
+    type Error = ConnectionError;
+    type Connection<'m>
+        = Connection<'m, T::Connection<'m>>
+    where
+        Self: 'm;
+
+    async fn connect<'m>(
+        &'m self,
+        remote: SocketAddr,
+    ) -> Result<Self::Connection<'m>, Self::Error> {
+        let connection = self
+            .tcp
+            .connect(remote)
+            .await
+            .map_err(|err| ConnectionError::Tcp(err.kind()))?;
+        if !self.tls {
+            return Ok(Connection::Plain(connection));
+        }
+
+        if self.buffers.in_use.replace(true) {
+            return Err(ConnectionError::Busy);
+        }
+        // SAFETY: `in_use` ensures the buffers are borrowed by a single
+        // connection, until its `TlsStream` is dropped.
+        let (read, write) = unsafe {
+            (
+                &mut *self.buffers.read.get(),
+                &mut *self.buffers.write.get(),
+            )
+        };
+        let mut stream = TlsStream {
+            connection: TlsConnection::new(Io07(connection), read, write),
+            in_use: &self.buffers.in_use,
+        };
+
+        let mut seed = [0; 32];
+        RoscRng.fill_bytes(&mut seed);
+        let provider = Provider {
+            verifier: verifier(),
+            rng: ChaCha20Rng::from_seed(seed),
+        };
+        let config = TlsConfig::new().with_server_name(self.server_name);
+        if let Err(err) = stream
+            .connection
+            .open(TlsContext::new(&config, provider))
+            .await
+        {
+            warn!("TLS handshake with {} failed: {}", self.server_name, err);
+            return Err(ConnectionError::Tls(err));
+        }
+
+        Ok(Connection::Tls(stream))
+    }
+}
+
+#[derive(Format, Debug)]
+pub enum ConnectionError {
+    Tcp(ErrorKind),
+    Tls(TlsError),
+    /// Another TLS connection holds the buffers.
+    Busy,
+}
+
+impl embedded_io_async::Error for ConnectionError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Tcp(kind) => *kind,
+            Self::Tls(_) | Self::Busy => ErrorKind::Other,
+        }
+    }
+}
+
+// There is no allocator to box the TLS state, and a single connection is open
+// at a time.
+#[allow(clippy::large_enum_variant)]
+pub enum Connection<'a, C: Read + Write> {
+    Plain(C),
+    Tls(TlsStream<'a, C>),
+}
+
+pub struct TlsStream<'a, C: Read + Write> {
+    connection: TlsConnection<'a, Io07<C>, CipherSuite>,
+    in_use: &'a Cell<bool>,
+}
+
+impl<C: Read + Write> Drop for TlsStream<'_, C> {
+    fn drop(&mut self) {
+        self.in_use.set(false);
+    }
+}
+
+impl<C: Read + Write> ErrorType for Connection<'_, C> {
+    type Error = ConnectionError;
+}
+
+impl<C: Read + Write> Read for Connection<'_, C> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self {
+            Self::Plain(connection) => connection
+                .read(buf)
+                .await
+                .map_err(|err| ConnectionError::Tcp(err.kind())),
+            Self::Tls(stream) => embedded_io_async_07::Read::read(&mut stream.connection, buf)
+                .await
+                .map_err(ConnectionError::Tls),
+        }
+    }
+}
+
+impl<C: Read + Write> Write for Connection<'_, C> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        match self {
+            Self::Plain(connection) => connection
+                .write(buf)
+                .await
+                .map_err(|err| ConnectionError::Tcp(err.kind())),
+            Self::Tls(stream) => embedded_io_async_07::Write::write(&mut stream.connection, buf)
+                .await
+                .map_err(ConnectionError::Tls),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        match self {
+            Self::Plain(connection) => connection
+                .flush()
+                .await
+                .map_err(|err| ConnectionError::Tcp(err.kind())),
+            Self::Tls(stream) => embedded_io_async_07::Write::flush(&mut stream.connection)
+                .await
+                .map_err(ConnectionError::Tls),
+        }
+    }
+}
+
+/// Adapter of the sockets, implementing the `embedded-io-async` 0.6 traits, to
+/// the 0.7 traits used by `embedded-tls`.
+struct Io07<C>(C);
+
+impl<C: ErrorType> embedded_io_07::ErrorType for Io07<C> {
+    type Error = embedded_io_07::ErrorKind;
+}
+
+impl<C: Read> embedded_io_async_07::Read for Io07<C> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.0.read(buf).await.map_err(io_error)
+    }
+}
+
+impl<C: Write> embedded_io_async_07::Write for Io07<C> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf).await.map_err(io_error)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush().await.map_err(io_error)
+    }
+}
+
+fn io_error(err: impl embedded_io_async::Error) -> embedded_io_07::ErrorKind {
+    match err.kind() {
+        ErrorKind::ConnectionReset => embedded_io_07::ErrorKind::ConnectionReset,
+        ErrorKind::TimedOut => embedded_io_07::ErrorKind::TimedOut,
+        _ => embedded_io_07::ErrorKind::Other,
+    }
+}
@@ -0,0 +1,54 @@
+//! Hardware watchdog, only fed while the critical tasks make progress, see
+//! `capteur_core::liveness`.
+//!
+//! The bootloader leaves the watchdog running when it starts the
+//! application, so that an image hanging before it checks in is rolled back.
+
+use core::cell::RefCell;
+
+use capteur_core::liveness::LivenessTracker;
+use defmt::*;
+use embassy_rp::watchdog::Watchdog;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
+use protocol::{CriticalTask, RebootReason};
+
+use crate::reboot;
+
+/// Same timeout as the bootloader.
+pub const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(8);
+const FEED_PERIOD: Duration = Duration::from_secs(2);
+
+static LIVENESS: Mutex<CriticalSectionRawMutex, RefCell<LivenessTracker>> =
+    Mutex::new(RefCell::new(LivenessTracker::new()));
+
+/// Record that `task` made progress and will report again within `timeout`.
+pub fn progress(task: CriticalTask, timeout: Duration) {
+    let now = Instant::now().as_secs();
+    LIVENESS.lock(|liveness| liveness.borrow_mut().progress(task, now, timeout.as_secs()));
+}
+
+/// Stop watching `task`, while it waits for another watched task or for the
+/// user.
+pub fn idle(task: CriticalTask) {
+    LIVENESS.lock(|liveness| liveness.borrow_mut().idle(task));
+}
+
+/// Feed the watchdog, started in `main`, as long as no critical task is late.
+/// Otherwise let it reset the capteur, recording which task got stuck.
+#[embassy_executor::task]
+pub async fn watchdog_task(mut watchdog: Watchdog) -> ! {
+    loop {
+        let now = Instant::now().as_secs();
+        if let Some(task) = LIVENESS.lock(|liveness| liveness.borrow().late(now)) {
+            error!("The {} task is stuck, waiting for the watchdog", task);
+            reboot::record(RebootReason::Watchdog, Some(task));
+            loop {
+                Timer::after(FEED_PERIOD).await;
+            }
+        }
+        watchdog.feed();
+        Timer::after(FEED_PERIOD).await;
+    }
+}
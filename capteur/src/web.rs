@@ -1,51 +1,97 @@
-use cyw43_pio::PioSpi;
+use capteur_core::config::DeviceConfig;
+use capteur_core::drift::DriftTracker;
+use capteur_core::payload::{self, Encoding};
+use capteur_core::rtc::Rtc as _;
+use capteur_core::status::Status;
+use capteur_core::time_sync::{SyncSchedule, RTC_SYNC_PERIOD};
 use defmt::*;
-use dotenvy_macro::*;
 use embassy_executor::Spawner;
-use embassy_net::{Config, Stack, StackResources};
-use embassy_rp::bind_interrupts;
-use embassy_rp::clocks::RoscRng;
-use embassy_rp::gpio::{Level, Output};
-use embassy_rp::peripherals::{DMA_CH0, PIN_23, PIN_24, PIN_25, PIN_29, PIO0, RTC};
-use embassy_rp::pio::{InterruptHandler, Pio};
-use embassy_rp::rtc::{DateTime, Rtc};
-use rand::RngCore;
+#[cfg(not(feature = "low-power"))]
+use embassy_futures::select::{select4, Either4};
+use embassy_net::driver::Driver;
+use embassy_net::Stack;
+use embassy_rp::peripherals::{DMA_CH0, FLASH, PIN_23, PIN_24, PIN_25, PIN_29, PIO0, RTC};
+use embassy_rp::rtc::Rtc;
+#[cfg(feature = "tls")]
 use static_cell::StaticCell;
 
 use core::fmt::write;
 use embassy_net::dns::DnsSocket;
 
-use reqwless::client::{HttpClient, TlsConfig, TlsVerify};
-use reqwless::request::{Method, RequestBuilder};
+use reqwless::client::HttpClient;
+use reqwless::headers::ContentType;
+use reqwless::request::{Method, RequestBody, RequestBuilder};
 
 use embassy_net::tcp::client::{TcpClient, TcpClientState};
-use embassy_time::Timer;
+use embassy_time::{Duration, Instant, Timer};
 use embedded_nal_async::{Dns, TcpConnect};
 use heapless::String;
-
-use crate::rtc::init_rtc;
-use crate::{Measure, MEASURE_SIGNAL, NETWORK_STACK_SIGNAL};
-
-bind_interrupts!(struct Irqs {
-    PIO0_IRQ_0 => InterruptHandler<PIO0>;
-});
-
-const WIFI_NETWORK: &str = dotenv!("WIFI_NETWORK");
-const WIFI_PASSWORD: &str = dotenv!("WIFI_PASSWORD");
-const CAPTEUR_ID: &str = dotenv!("CAPTEUR_ID");
-const API_URL: &str = dotenv!("API_URL");
-
-#[embassy_executor::task]
-pub async fn wifi_task(
-    runner: cyw43::Runner<'static, Output<'static>, PioSpi<'static, PIO0, 0, DMA_CH0>>,
-) -> ! {
-    runner.run().await
-}
-
-#[embassy_executor::task]
-pub async fn net_task(stack: &'static Stack<cyw43::NetDriver<'static>>) -> ! {
-    stack.run().await
-}
+use protocol::crash::CRASH_JSON_LEN;
+use protocol::health::HEARTBEAT_JSON_LEN;
+use protocol::{CapteurSettings, ClockDrift, ConfigAck, ConnectionEvent, CriticalTask};
+use serde::Serialize;
+
+use crate::config::ConfigStore;
+use crate::crash;
+use crate::health;
+use crate::http::Api;
+use crate::led::set_status;
+use crate::ota;
+use crate::provisioning::provisioning_mode;
+use crate::radio::Radio;
+use crate::reboot;
+use crate::rtc::{sync_rtc, BoardRtc};
+use crate::settings::{apply, settings};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsBuffers, TlsConnector};
+use crate::watchdog;
+use crate::wifi::{WifiSupervisor, CONNECTION_EVENTS};
+use crate::NETWORK_STACK_SIGNAL;
+#[cfg(feature = "low-power")]
+use {
+    crate::low_power::{batch_len, buffered, READINGS, UPLOAD_DONE, UPLOAD_REQUEST},
+    capteur_core::duty_cycle,
+    capteur_core::payload::{Batch, PostError},
+    embassy_time::with_timeout,
+    protocol::batch::Reading,
+};
+#[cfg(not(feature = "low-power"))]
+use {
+    crate::{Measure, MEASURES},
+    protocol::{Aggregate, Timestamp},
+};
+
+/// Period between two updates of the settings and firmware from the server.
+#[cfg(not(feature = "low-power"))]
+const SETTINGS_UPDATE_PERIOD: Duration = Duration::from_secs(15 * 60);
+
+/// Period between two heartbeats, the low-power mode sending one with each
+/// batch of measures instead.
+#[cfg(not(feature = "low-power"))]
+const HEARTBEAT_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// Longest the network task may go without reporting progress, on top of the
+/// time it sleeps, as long as the exchanges with the server can take.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Longest a request to the server may take.
+const POST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the low-power mode waits for the network after powering the Wi-Fi
+/// chip up.
+#[cfg(feature = "low-power")]
+const WAKE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Buffers of the connections to the API, kept from one Wi-Fi session to the
+/// next.
+#[cfg(feature = "tls")]
+type ConnectionBuffers = TlsBuffers<16640, 16640>;
+#[cfg(not(feature = "tls"))]
+type ConnectionBuffers = ();
+
+#[cfg(feature = "tls")]
+type Connector<'a, D> = TlsConnector<'a, TcpClient<'a, D, 1, 1024, 1024>, 16640, 16640>;
+#[cfg(not(feature = "tls"))]
+type Connector<'a, D> = TcpClient<'a, D, 1, 1024, 1024>;
 
 pub struct NetworkPeriphals {
     pub pin23: PIN_23,
@@ -55,199 +101,721 @@ pub struct NetworkPeriphals {
     pub pio: PIO0,
     pub dma: DMA_CH0,
     pub rtc: RTC,
+    pub flash: FLASH,
 }
 
 #[embassy_executor::task]
 pub async fn network_stack(spawner: Spawner, p: NetworkPeriphals) {
-    let mut rng = RoscRng;
-    let mut rtc = Rtc::new(p.rtc);
-
-    let fw = include_bytes!("../cyw43-firmware/43439A0.bin");
-    let clm = include_bytes!("../cyw43-firmware/43439A0_clm.bin");
-
-    let pwr = Output::new(p.pin23, Level::Low);
-    let cs = Output::new(p.pin25, Level::High);
-    let mut pio = Pio::new(p.pio, Irqs);
-    let spi = PioSpi::new(
-        &mut pio.common,
-        pio.sm0,
-        pio.irq0,
-        cs,
-        p.pin24,
-        p.pin29,
-        p.dma,
-    );
+    watchdog::progress(CriticalTask::Network, NETWORK_TIMEOUT);
+    let mut rtc = BoardRtc(Rtc::new(p.rtc));
+    let mut config_store = ConfigStore::new(p.flash);
+    if let Some(settings) = config_store.load_settings() {
+        apply(settings);
+    }
+    if ota::is_new_image(config_store) {
+        unwrap!(spawner.spawn(ota::rollback_task()));
+    }
 
-    static STATE: StaticCell<cyw43::State> = StaticCell::new();
-    let state = STATE.init(cyw43::State::new());
-    let (net_device, mut control, runner) = cyw43::new(state, pwr, spi, fw).await;
+    let mut radio = Radio::new(p.pin23, p.pin24, p.pin25, p.pin29, p.pio, p.dma);
+
+    let device_config = match config_store.load() {
+        Some(device_config) => device_config,
+        None => {
+            info!("Capteur not provisioned yet");
+            watchdog::idle(CriticalTask::Network);
+            radio
+                .run(async |stack, control| -> DeviceConfig {
+                    provisioning_mode(control, stack, &mut config_store, None).await
+                })
+                .await
+        }
+    };
+    let device_config = &device_config;
 
-    unwrap!(spawner.spawn(wifi_task(runner)));
+    #[cfg(feature = "tls")]
+    let buffers = {
+        static TLS_BUFFERS: StaticCell<ConnectionBuffers> = StaticCell::new();
+        &*TLS_BUFFERS.init(TlsBuffers::new())
+    };
+    #[cfg(not(feature = "tls"))]
+    let buffers = {
+        if device_config.api_uses_tls() {
+            error!(
+                "{} needs TLS, which is not built in the firmware",
+                device_config.api_url
+            );
+        }
+        &()
+    };
 
-    control.init(clm).await;
-    control
-        .set_power_management(cyw43::PowerManagementMode::PowerSave)
-        .await;
+    let mut supervisor = WifiSupervisor::new(device_config, config_store);
+    let mut drift_tracker = DriftTracker::new();
+    let mut rtc_sync_schedule = SyncSchedule::new(RTC_SYNC_PERIOD);
+    let mut settings_sync = SettingsSync {
+        store: config_store,
+        acked_version: None,
+    };
 
-    let config = Config::dhcpv4(Default::default());
+    radio
+        .run(async |stack, control| {
+            let session = async {
+                info!("waiting for the network to be up...");
+                // Joining the network is up to the Wi-Fi supervisor, which may
+                // fall back to the provisioning access point
+                watchdog::idle(CriticalTask::Network);
+                stack.wait_config_up().await;
+                info!("Network is up!");
+                watchdog::progress(CriticalTask::Network, NETWORK_TIMEOUT);
+
+                with_api_client(stack, device_config, buffers, async |http_client| {
+                    init_rtc(
+                        stack,
+                        http_client,
+                        &mut rtc,
+                        &mut drift_tracker,
+                        &mut rtc_sync_schedule,
+                        device_config,
+                    )
+                    .await;
+                    sync_with_server(http_client, device_config, &mut settings_sync).await;
+
+                    NETWORK_STACK_SIGNAL.signal(true);
+
+                    #[cfg(not(feature = "low-power"))]
+                    always_on_loop(
+                        stack,
+                        http_client,
+                        &mut rtc,
+                        &mut drift_tracker,
+                        &mut rtc_sync_schedule,
+                        device_config,
+                        &mut settings_sync,
+                    )
+                    .await;
+                })
+                .await
+            };
+            supervisor.supervise(control, stack, session).await
+        })
+        .await;
 
-    // Generate random seed
-    let seed = rng.next_u64();
+    // Upload the buffered measures whenever the measure loop asks for it,
+    // powering the Wi-Fi chip up for the time of the upload only.
+    #[cfg(feature = "low-power")]
+    {
+        let mut next_rtc_sync = Instant::now() + Duration::from_secs(rtc_sync_schedule.synced());
+        loop {
+            // The measure task is watched until it requests an upload
+            watchdog::idle(CriticalTask::Network);
+            UPLOAD_REQUEST.wait().await;
+            watchdog::progress(CriticalTask::Network, WAKE_TIMEOUT + NETWORK_TIMEOUT);
+
+            radio
+                .run(async |stack, control| {
+                    let session = async {
+                        if with_timeout(WAKE_TIMEOUT, stack.wait_config_up())
+                            .await
+                            .is_err()
+                        {
+                            warn!("Network unavailable, keeping {} measures", buffered());
+                            return;
+                        }
+                        with_api_client(stack, device_config, buffers, async |http_client| {
+                            if Instant::now() >= next_rtc_sync {
+                                next_rtc_sync = resync_rtc(
+                                    stack,
+                                    http_client,
+                                    &mut rtc,
+                                    &mut drift_tracker,
+                                    &mut rtc_sync_schedule,
+                                    device_config,
+                                )
+                                .await;
+                            }
+                            upload_readings(http_client, &rtc, device_config).await;
+                            post_heartbeat(http_client, device_config).await;
+                            sync_with_server(http_client, device_config, &mut settings_sync).await;
+                            while let Ok(event) = CONNECTION_EVENTS.try_receive() {
+                                post_event(http_client, device_config, event).await;
+                            }
+                        })
+                        .await
+                    };
+                    supervisor.supervise(control, stack, session).await
+                })
+                .await;
+
+            UPLOAD_DONE.signal(());
+        }
+    }
+}
 
-    // Init network stack
-    static STACK: StaticCell<Stack<cyw43::NetDriver<'static>>> = StaticCell::new();
-    static RESOURCES: StaticCell<StackResources<5>> = StaticCell::new();
-    let stack = &*STACK.init(Stack::new(
-        net_device,
-        config,
-        RESOURCES.init(StackResources::new()),
-        seed,
-    ));
+/// Run `f` with a client of the API over `stack`.
+async fn with_api_client<D: Driver + 'static, R>(
+    stack: &Stack<D>,
+    device_config: &DeviceConfig,
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))] buffers: &ConnectionBuffers,
+    f: impl AsyncFnOnce(&mut HttpClient<'_, Connector<'_, D>, DnsSocket<'_, D>>) -> R,
+) -> R {
+    let client_state = TcpClientState::<1, 1024, 1024>::new();
+    let tcp_client = TcpClient::new(stack, &client_state);
+    #[cfg(feature = "tls")]
+    let connector = TlsConnector::new(
+        &tcp_client,
+        buffers,
+        device_config.api_host(),
+        device_config.api_uses_tls(),
+    );
+    #[cfg(not(feature = "tls"))]
+    let connector = {
+        let _ = device_config;
+        tcp_client
+    };
+    let dns_client = DnsSocket::new(stack);
 
-    unwrap!(spawner.spawn(net_task(stack)));
+    let mut http_client = HttpClient::new(&connector, &dns_client);
+    f(&mut http_client).await
+}
 
+/// Set the RTC from the server, retrying until it answers.
+async fn init_rtc<'a, D, T, U>(
+    stack: &Stack<D>,
+    http_client: &mut HttpClient<'a, T, U>,
+    rtc: &mut BoardRtc<'_>,
+    drift_tracker: &mut DriftTracker,
+    rtc_sync_schedule: &mut SyncSchedule,
+    device_config: &DeviceConfig,
+) where
+    D: Driver,
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
     loop {
-        match control.join_wpa2(WIFI_NETWORK, WIFI_PASSWORD).await {
-            Ok(_) => break,
+        match sync_rtc(
+            stack,
+            http_client,
+            rtc,
+            drift_tracker,
+            &device_config.api_url,
+        )
+        .await
+        {
+            Ok(_) => {
+                info!("RTC successfuly initialized.");
+                return;
+            }
             Err(err) => {
-                info!("join failed with status={}", err.status);
+                set_status(Status::RtcSyncFailed);
+                let delay = rtc_sync_schedule.failed();
+                warn!(
+                    "Error when initializing the RTC: {}, retrying in {}s",
+                    err, delay
+                );
+                watchdog::progress(
+                    CriticalTask::Network,
+                    Duration::from_secs(delay) + NETWORK_TIMEOUT,
+                );
+                Timer::after_secs(delay).await;
             }
         }
     }
+}
 
-    // Wait for DHCP, not necessary when using static IP
-    info!("waiting for DHCP...");
-    while !stack.is_config_up() {
-        Timer::after_millis(100).await;
+/// Post the measures as they are taken, with the heartbeats, the connection
+/// events and the updates from the server, the Wi-Fi being always on.
+#[cfg(not(feature = "low-power"))]
+async fn always_on_loop<'a, D, T, U>(
+    stack: &Stack<D>,
+    http_client: &mut HttpClient<'a, T, U>,
+    rtc: &mut BoardRtc<'_>,
+    drift_tracker: &mut DriftTracker,
+    rtc_sync_schedule: &mut SyncSchedule,
+    device_config: &DeviceConfig,
+    settings_sync: &mut SettingsSync,
+) -> !
+where
+    D: Driver,
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let mut next_rtc_sync = Instant::now() + Duration::from_secs(rtc_sync_schedule.synced());
+    let mut next_settings_update = Instant::now() + SETTINGS_UPDATE_PERIOD;
+    let mut next_heartbeat = Instant::now();
+    loop {
+        // The loop wakes up at least for every heartbeat
+        watchdog::progress(CriticalTask::Network, HEARTBEAT_PERIOD + NETWORK_TIMEOUT);
+        match select4(
+            MEASURES.receive(),
+            Timer::at(next_rtc_sync),
+            CONNECTION_EVENTS.receive(),
+            Timer::at(next_settings_update.min(next_heartbeat)),
+        )
+        .await
+        {
+            Either4::First((measure, aggregate)) => {
+                let Some(timestamp) = rtc.now() else {
+                    error!("RTC is not running");
+                    continue;
+                };
+                post_measure(
+                    http_client,
+                    device_config,
+                    measure,
+                    Some(aggregate),
+                    timestamp,
+                )
+                .await;
+            }
+            Either4::Second(_) => {
+                next_rtc_sync = resync_rtc(
+                    stack,
+                    http_client,
+                    rtc,
+                    drift_tracker,
+                    rtc_sync_schedule,
+                    device_config,
+                )
+                .await;
+            }
+            Either4::Third(event) => {
+                // Events are mostly about the connection going down, hold
+                // them until it is back.
+                watchdog::idle(CriticalTask::Network);
+                stack.wait_config_up().await;
+                watchdog::progress(CriticalTask::Network, NETWORK_TIMEOUT);
+                post_event(http_client, device_config, event).await;
+            }
+            Either4::Fourth(_) => {
+                if Instant::now() >= next_heartbeat {
+                    post_heartbeat(http_client, device_config).await;
+                    next_heartbeat = Instant::now() + HEARTBEAT_PERIOD;
+                }
+                if Instant::now() >= next_settings_update {
+                    sync_with_server(http_client, device_config, settings_sync).await;
+                    next_settings_update = Instant::now() + SETTINGS_UPDATE_PERIOD;
+                }
+            }
+        }
     }
-    info!("DHCP is now up!");
+}
 
-    info!("waiting for link up...");
-    while !stack.is_link_up() {
-        Timer::after_millis(500).await;
+/// Post the buffered measures, oldest first, up to the batch length per
+/// request, stopping at the first failure.
+#[cfg(feature = "low-power")]
+async fn upload_readings<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    rtc: &BoardRtc<'_>,
+    device_config: &DeviceConfig,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let mut sent = 0;
+    loop {
+        let len = buffered().min(batch_len());
+        if len == 0 {
+            break;
+        }
+        let Some(now) = rtc.now() else {
+            error!("RTC is not running");
+            break;
+        };
+        let uptime = Instant::now().as_secs();
+        let batch = Batch {
+            capteur_id: &device_config.capteur_id,
+            sent_at: now,
+            len,
+            encoding: BODY_ENCODING,
+            reading: |index| {
+                let (taken_at, measure) =
+                    READINGS.lock(|readings| readings.borrow().get(index).cloned())?;
+                Some(Reading {
+                    timestamp: duty_cycle::taken_at(now, uptime, taken_at).ok()?,
+                    temperature: measure.temperature,
+                    humidity: measure.humidity,
+                    samples: Some(measure.samples),
+                })
+            },
+        };
+        match payload::post_batch(&mut Api(http_client), &device_config.api_url, &batch).await {
+            Ok(()) => {
+                READINGS.lock(|readings| {
+                    let mut readings = readings.borrow_mut();
+                    for _ in 0..len {
+                        readings.pop();
+                    }
+                });
+                sent += len;
+            }
+            Err(PostError::Encode(_)) => {
+                // The times of the oldest measures come first to be invalid
+                warn!("Invalid measure time, dropping the oldest measure");
+                READINGS.lock(|readings| readings.borrow_mut().pop());
+            }
+            Err(err) => {
+                warn!("Unable to post the measures: {}", err);
+                break;
+            }
+        }
+        watchdog::progress(CriticalTask::Network, NETWORK_TIMEOUT);
     }
-    info!("Link is up!");
+    info!("Uploaded {} measures, {} left", sent, buffered());
+}
 
-    info!("waiting for stack to be up...");
-    stack.wait_config_up().await;
-    info!("Stack is up!");
+/// Update the settings and the firmware from the server.
+async fn sync_with_server<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    settings_sync: &mut SettingsSync,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    report_reboot(http_client, device_config).await;
+    report_crash(http_client, device_config).await;
+    update_settings(http_client, device_config, settings_sync).await;
+    ota::check_in(http_client, device_config, settings_sync.store).await;
+    ota::update_firmware(http_client, device_config, settings_sync.store).await;
+}
 
-    let mut tls_read_buffer = [0; 16640];
-    let mut tls_write_buffer = [0; 16640];
+/// Where the settings set on the server are persisted, and the last version
+/// acknowledged to the server since the boot.
+struct SettingsSync {
+    store: ConfigStore,
+    acked_version: Option<u32>,
+}
 
-    let client_state = TcpClientState::<1, 1024, 1024>::new();
-    let tcp_client = TcpClient::new(stack, &client_state);
-    let dns_client = DnsSocket::new(stack);
-    let tls_config: TlsConfig<'_> = TlsConfig::new(
-        seed,
-        &mut tls_read_buffer,
-        &mut tls_write_buffer,
-        TlsVerify::None,
+/// Fetch the settings of the capteur from the server, apply and persist them
+/// when they changed, then acknowledge their version. The current settings
+/// are kept when the server is unreachable or sends invalid ones.
+async fn update_settings<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    settings_sync: &mut SettingsSync,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let mut url: String<160> = String::new();
+    let _ = write(
+        &mut url,
+        format_args!(
+            "{}/capteurs/{}/config",
+            device_config.api_url, device_config.capteur_id
+        ),
     );
 
-    let mut http_client = HttpClient::new_with_tls(&tcp_client, &dns_client, tls_config);
+    let Some(settings_update) = fetch_settings(http_client, &url).await else {
+        return;
+    };
 
-    match init_rtc(&mut http_client, &mut rtc).await {
-        Ok(_) => {
-            info!("RTC successfuly initialized.");
-        }
-        Err(err) => {
-            warn!("Error when initializing the RTC: {}", err);
-            return;
+    if settings_update != settings() {
+        apply(settings_update);
+        if let Err(err) = settings_sync.store.save_settings(&settings_update) {
+            error!("Unable to save the settings: {}", err);
         }
     }
 
-    NETWORK_STACK_SIGNAL.signal(true);
-
-    loop {
-        let now = match rtc.now() {
-            Ok(now) => now,
-            Err(_) => {
-                error!("RTC is not running");
-                continue;
-            }
-        };
-        let measure = MEASURE_SIGNAL.wait().await;
-        post_measure(&mut http_client, measure, now).await;
+    if settings_sync.acked_version == Some(settings_update.version) {
+        return;
+    }
+    let ack = ConfigAck {
+        version: settings_update.version,
+    };
+    if post_json(http_client, device_config, "config/ack", &ack, &mut [0; 32]).await {
+        settings_sync.acked_version = Some(settings_update.version);
     }
 }
 
-async fn post_measure<'a, T, U>(
+/// Fetch the settings document at `url`, `None` if it is unavailable or
+/// invalid.
+async fn fetch_settings<'a, T, U>(
     http_client: &mut HttpClient<'a, T, U>,
-    measure: Measure,
-    now: DateTime,
-) where
+    url: &str,
+) -> Option<CapteurSettings>
+where
     T: TcpConnect + 'a,
     U: Dns + 'a,
 {
-    let mut url: String<100> = String::new();
-    let _ = write(&mut url, format_args!("{API_URL}/measure"));
-
-    let body = match build_body(measure, now) {
-        Ok(body) => body,
+    let mut request = match http_client.request(Method::GET, url).await {
+        Ok(request) => request,
+        Err(_) => {
+            warn!("Error when building the request, passing...");
+            return None;
+        }
+    };
+    let mut rx_buffer = [0; 1024];
+    let response = match request.send(&mut rx_buffer).await {
+        Ok(response) if response.status.is_successful() => response,
         _ => {
-            warn!("Unable to build body, passing...");
-            return;
+            warn!("Unable to fetch the settings, passing...");
+            return None;
         }
     };
+    let Ok(body) = response.body().read_to_end().await else {
+        warn!("Unable to read the settings, passing...");
+        return None;
+    };
+    match serde_json_core::from_slice::<CapteurSettings>(body) {
+        Ok((settings, _)) if settings.is_valid() => Some(settings),
+        _ => {
+            warn!("Invalid settings, passing...");
+            None
+        }
+    }
+}
 
-    let request = match http_client.request(Method::POST, &url).await {
-        Ok(request) => request,
-        Err(_) => {
-            warn!("Error when building the request, passing...");
-            return;
+/// Synchronise the RTC again, posting its drift, and return when to do it
+/// next.
+async fn resync_rtc<'a, D, T, U>(
+    stack: &Stack<D>,
+    http_client: &mut HttpClient<'a, T, U>,
+    rtc: &mut BoardRtc<'_>,
+    drift_tracker: &mut DriftTracker,
+    rtc_sync_schedule: &mut SyncSchedule,
+    device_config: &DeviceConfig,
+) -> Instant
+where
+    D: Driver,
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    match sync_rtc(
+        stack,
+        http_client,
+        rtc,
+        drift_tracker,
+        &device_config.api_url,
+    )
+    .await
+    {
+        Ok(drift) => {
+            info!("RTC successfuly resynchronised.");
+            if let Some(drift) = drift {
+                post_drift(http_client, device_config, drift).await;
+            }
+            Instant::now() + Duration::from_secs(rtc_sync_schedule.synced())
+        }
+        Err(err) => {
+            set_status(Status::RtcSyncFailed);
+            let delay = rtc_sync_schedule.failed();
+            warn!(
+                "Error when resynchronising the RTC: {}, retrying in {}s",
+                err, delay
+            );
+            Instant::now() + Duration::from_secs(delay)
         }
+    }
+}
+
+/// Post a measure taken at `timestamp`, with the statistics of the measures
+/// not sent if any, returning whether the server accepted it.
+#[cfg(not(feature = "low-power"))]
+async fn post_measure<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    measure: Measure,
+    aggregate: Option<Aggregate>,
+    timestamp: Timestamp,
+) -> bool
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let payload = protocol::Measure {
+        timestamp,
+        temperature: measure.temperature,
+        humidity: measure.humidity,
+        capteur_id: device_config.capteur_id.clone(),
+        samples: Some(measure.samples),
+        aggregate,
     };
-    let mut request = request
-        .body(body.as_bytes())
-        .content_type(reqwless::headers::ContentType::ApplicationJson);
 
-    let mut rx_buffer = [0; 8192];
-    if (request.send(&mut rx_buffer).await).is_err() {
-        warn!("Unable to send request, passing...");
+    match payload::post_measure(
+        &mut Api(http_client),
+        &device_config.api_url,
+        &payload,
+        BODY_ENCODING,
+    )
+    .await
+    {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("Unable to post the measure: {}", err);
+            false
+        }
     }
 }
 
-enum BuildBodyError {
-    CannotBuildBodyErr,
+async fn post_drift<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    drift: ClockDrift,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    post_json(http_client, device_config, "drift", &drift, &mut [0; 128]).await;
 }
 
-impl From<core::fmt::Error> for BuildBodyError {
-    fn from(_value: core::fmt::Error) -> Self {
-        Self::CannotBuildBodyErr
+async fn post_event<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    event: ConnectionEvent,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    post_json(http_client, device_config, "events", &event, &mut [0; 128]).await;
+}
+
+async fn post_heartbeat<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    post_json(
+        http_client,
+        device_config,
+        "heartbeat",
+        &health::heartbeat(),
+        &mut [0; HEARTBEAT_JSON_LEN],
+    )
+    .await;
+}
+
+/// Send the reason of the last reboot, once.
+async fn report_reboot<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let Some(report) = reboot::pending_report() else {
+        return;
+    };
+    if post_json(
+        http_client,
+        device_config,
+        "reboots",
+        &report,
+        &mut [0; 128],
+    )
+    .await
+    {
+        reboot::reported();
     }
 }
 
-impl From<()> for BuildBodyError {
-    fn from(_: ()) -> Self {
-        Self::CannotBuildBodyErr
+/// Send the last panic, until the server stores it.
+async fn report_crash<'a, T, U>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+) where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+{
+    let Some(report) = crash::pending_report() else {
+        return;
+    };
+    warn!("Reporting a panic: {}", report);
+    if post_json(
+        http_client,
+        device_config,
+        "crash",
+        &report,
+        &mut [0; CRASH_JSON_LEN],
+    )
+    .await
+    {
+        crash::reported();
     }
 }
 
-fn build_body(measure: Measure, now: DateTime) -> Result<String<100>, BuildBodyError> {
-    let mut body: String<100> = String::new();
+/// Post `body`, encoded as JSON into `buffer`, to `path` under the capteur,
+/// returning whether the server accepted it.
+pub(crate) async fn post_json<'a, T, U, B>(
+    http_client: &mut HttpClient<'a, T, U>,
+    device_config: &DeviceConfig,
+    path: &str,
+    body: &B,
+    buffer: &mut [u8],
+) -> bool
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+    B: Serialize,
+{
+    match payload::post_json(&mut Api(http_client), device_config, path, body, buffer).await {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("Unable to post to {}: {}", path, err);
+            false
+        }
+    }
+}
 
-    body.push('{')?;
-    write(
-        &mut body,
-        format_args!(
-            "\"timestamp\": \"{}-{}-{}T{}:{}:{}Z\",",
-            now.year, now.month, now.day, now.hour, now.minute, now.second
-        ),
-    )?;
-    write(
-        &mut body,
-        format_args!("\"temperature\": {:.2},", measure.temperature),
-    )?;
-    write(
-        &mut body,
-        format_args!("\"humidity\": {:.2},", measure.humidity),
-    )?;
-    write(
-        &mut body,
-        format_args!("\"capteur_id\": \"{}\"", CAPTEUR_ID),
-    )?;
-    body.push('}')?;
-
-    Ok(body)
+/// Post `body` to `url`, returning whether the server accepted it.
+pub(crate) async fn post<'a, T, U, B>(
+    http_client: &mut HttpClient<'a, T, U>,
+    url: &str,
+    body: B,
+    content_type: ContentType,
+) -> bool
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+    B: RequestBody,
+{
+    watchdog::progress(CriticalTask::Post, POST_TIMEOUT);
+    let accepted = send_post(http_client, url, body, content_type).await;
+    watchdog::idle(CriticalTask::Post);
+    set_status(if accepted {
+        Status::PostOk
+    } else {
+        Status::PostFailing
+    });
+    accepted
 }
+
+async fn send_post<'a, T, U, B>(
+    http_client: &mut HttpClient<'a, T, U>,
+    url: &str,
+    body: B,
+    content_type: ContentType,
+) -> bool
+where
+    T: TcpConnect + 'a,
+    U: Dns + 'a,
+    B: RequestBody,
+{
+    let request = match http_client.request(Method::POST, url).await {
+        Ok(request) => request,
+        Err(_) => {
+            warn!("Error when building the request, passing...");
+            return false;
+        }
+    };
+    let mut request = request.body(body).content_type(content_type);
+
+    let mut rx_buffer = [0; 8192];
+    match request.send(&mut rx_buffer).await {
+        Ok(response) if response.status.is_successful() => true,
+        Ok(response) => {
+            warn!("Request to {} rejected: {}", url, response.status);
+            false
+        }
+        Err(_) => {
+            warn!("Unable to send request, passing...");
+            false
+        }
+    }
+}
+
+#[cfg(not(feature = "cbor"))]
+const BODY_ENCODING: Encoding = Encoding::Json;
+#[cfg(feature = "cbor")]
+const BODY_ENCODING: Encoding = Encoding::Cbor;
@@ -0,0 +1,197 @@
+use core::future::Future;
+
+use capteur_core::backoff::Backoff;
+use capteur_core::config::{DeviceConfig, WifiNetwork};
+use capteur_core::status::Status;
+use capteur_core::wifi::{JoinScheduler, LinkMonitor};
+use defmt::*;
+use embassy_futures::select::{select, Either};
+use embassy_net::Stack;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use protocol::connection::Ssid;
+use protocol::{ConnectionEvent, ConnectionEventKind};
+
+use crate::config::ConfigStore;
+use crate::health::{self, measure_rssi};
+use crate::led::set_status;
+#[cfg(feature = "provisioning-fallback")]
+use crate::provisioning::provisioning_mode;
+
+/// The cyw43 chip, shared by the Wi-Fi supervisor and the status LED. It is
+/// only locked for the time of a command, so that the LED keeps blinking.
+pub type SharedControl<'a> = Mutex<CriticalSectionRawMutex, cyw43::Control<'a>>;
+
+/// Connection events waiting to be sent to the server.
+pub static CONNECTION_EVENTS: Channel<CriticalSectionRawMutex, ConnectionEvent, 8> = Channel::new();
+
+/// Period between two checks of the link and DHCP lease.
+const LINK_CHECK_PERIOD: Duration = Duration::from_secs(5);
+/// How long to wait for a DHCP lease once a network is joined.
+const DHCP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Period between two measures of the signal strength while connected.
+const RSSI_PERIOD: Duration = Duration::from_secs(5 * 60);
+/// How long the scan measuring the signal strength may take.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bounds, in seconds, of the delay between two rounds over all the networks.
+const JOIN_RETRY_MIN: u64 = 5;
+const JOIN_RETRY_MAX: u64 = 300;
+/// Number of rounds without joining any network before falling back to the
+/// provisioning access point.
+#[cfg(feature = "provisioning-fallback")]
+const MAX_FAILED_ROUNDS: u32 = 5;
+/// How long the provisioning access point stays open after failing to join the
+/// configured networks, before rebooting to try again.
+#[cfg(feature = "provisioning-fallback")]
+const PROVISIONING_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Keeps the capteur connected: joins the configured networks by priority,
+/// watches the link and rejoins with an exponential backoff when it is lost.
+///
+/// It only runs while the radio is powered, its state being kept from one
+/// session to the next, so that powering the chip down is not reported as a
+/// disconnection. With the `provisioning-fallback` feature, the provisioning
+/// access point is opened after `MAX_FAILED_ROUNDS`.
+pub struct WifiSupervisor<'a> {
+    device_config: &'a DeviceConfig,
+    #[cfg_attr(not(feature = "provisioning-fallback"), allow(dead_code))]
+    config_store: ConfigStore,
+    scheduler: JoinScheduler,
+    monitor: LinkMonitor,
+}
+
+impl<'a> WifiSupervisor<'a> {
+    pub fn new(device_config: &'a DeviceConfig, config_store: ConfigStore) -> Self {
+        Self {
+            device_config,
+            config_store,
+            scheduler: JoinScheduler::new(
+                device_config.networks.len(),
+                Backoff::new(JOIN_RETRY_MIN, JOIN_RETRY_MAX),
+            ),
+            monitor: LinkMonitor::new(),
+        }
+    }
+
+    /// Run `task` while keeping the capteur connected.
+    pub async fn supervise<R>(
+        &mut self,
+        control: &SharedControl<'_>,
+        stack: &Stack<cyw43::NetDriver<'_>>,
+        task: impl Future<Output = R>,
+    ) -> R {
+        match select(self.run(control, stack), task).await {
+            Either::First(never) => never,
+            Either::Second(result) => result,
+        }
+    }
+
+    async fn run(&mut self, control: &SharedControl<'_>, stack: &Stack<cyw43::NetDriver<'_>>) -> ! {
+        loop {
+            let network = &self.device_config.networks[self.scheduler.network()];
+            if let Err(err) = connect(control, stack, network).await {
+                warn!("Unable to connect to {}: {}", network.ssid, err);
+                let delay = self.scheduler.failed();
+                if delay > 0 {
+                    warn!("No network available, retrying in {}s", delay);
+                    report(ConnectionEvent {
+                        kind: ConnectionEventKind::JoinFailed,
+                        ssid: Ssid::new(),
+                        uptime: Instant::now().as_secs(),
+                        downtime: 0,
+                    });
+                    #[cfg(feature = "provisioning-fallback")]
+                    if self.scheduler.failed_rounds() >= MAX_FAILED_ROUNDS {
+                        provisioning_mode(
+                            control,
+                            stack,
+                            &mut self.config_store,
+                            Some(PROVISIONING_TIMEOUT),
+                        )
+                        .await
+                    }
+                    Timer::after_secs(delay).await;
+                }
+                continue;
+            }
+
+            info!("Connected to {}", network.ssid);
+            set_status(Status::Connected);
+            self.scheduler.connected();
+            if let Some(event) = self
+                .monitor
+                .update(true, &network.ssid, Instant::now().as_secs())
+            {
+                report(event);
+            }
+
+            let mut next_rssi_check = Instant::now();
+            while stack.is_link_up() && stack.is_config_up() {
+                if Instant::now() >= next_rssi_check {
+                    let rssi = with_timeout(SCAN_TIMEOUT, async {
+                        measure_rssi(&mut *control.lock().await, &network.ssid).await
+                    });
+                    match rssi.await {
+                        Ok(rssi) => health::set_rssi(rssi),
+                        Err(_) => warn!("Unable to measure the signal strength"),
+                    }
+                    next_rssi_check = Instant::now() + RSSI_PERIOD;
+                }
+                Timer::after(LINK_CHECK_PERIOD).await;
+            }
+            health::set_rssi(None);
+
+            warn!("Connection to {} lost", network.ssid);
+            if let Some(event) = self
+                .monitor
+                .update(false, &network.ssid, Instant::now().as_secs())
+            {
+                report(event);
+            }
+            control.lock().await.leave().await;
+        }
+    }
+}
+
+#[derive(Format, Debug)]
+enum ConnectError {
+    JoinFailed(u32),
+    DhcpTimeout,
+}
+
+async fn connect(
+    control: &SharedControl<'_>,
+    stack: &Stack<cyw43::NetDriver<'_>>,
+    network: &WifiNetwork,
+) -> Result<(), ConnectError> {
+    info!("Joining {}", network.ssid);
+    set_status(Status::JoiningWifi);
+    let joined = {
+        let mut control = control.lock().await;
+        if network.password.is_empty() {
+            control.join_open(&network.ssid).await
+        } else {
+            control.join_wpa2(&network.ssid, &network.password).await
+        }
+    };
+    joined.map_err(|err| ConnectError::JoinFailed(err.status))?;
+
+    set_status(Status::WaitingDhcp);
+    if with_timeout(DHCP_TIMEOUT, stack.wait_config_up())
+        .await
+        .is_err()
+    {
+        control.lock().await.leave().await;
+        return Err(ConnectError::DhcpTimeout);
+    }
+
+    Ok(())
+}
+
+fn report(event: ConnectionEvent) {
+    if CONNECTION_EVENTS.try_send(event).is_err() {
+        warn!("Connection event queue full, dropping event");
+    }
+}
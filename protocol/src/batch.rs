@@ -0,0 +1,240 @@
+//! Readings buffered by a capteur, sent at once to `POST /measures`.
+//!
+//! The capteurs cannot hold a whole batch in memory, so they encode it piece
+//! by piece: the head, each reading, then the tail, see the [`json`] and
+//! `cbor` modules. The server decodes it as a `MeasureBatch`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::time::Timestamp;
+
+/// A reading of a batch, taken by the capteur sending the batch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "cbor",
+    derive(minicbor::Encode, minicbor::Decode),
+    cbor(map)
+)]
+pub struct Reading {
+    #[cfg_attr(feature = "cbor", n(0))]
+    pub timestamp: Timestamp,
+    #[cfg_attr(feature = "cbor", n(1))]
+    pub temperature: f64,
+    #[cfg_attr(feature = "cbor", n(2))]
+    pub humidity: f64,
+    /// Number of valid sensor samples the values are the median of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "cbor", n(3))]
+    pub samples: Option<u8>,
+}
+
+/// Readings of a capteur, oldest first, as sent to `POST /measures`.
+///
+/// `sent_at` is the time of the capteur when sending them, from which the
+/// server tells the skew of its clock however old the readings are.
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "cbor",
+    derive(minicbor::Encode, minicbor::Decode),
+    cbor(map)
+)]
+pub struct MeasureBatch {
+    #[cfg_attr(feature = "cbor", n(0), cbor(with = "crate::cbor::string"))]
+    pub capteur_id: crate::CapteurId,
+    #[cfg_attr(feature = "cbor", n(1))]
+    pub sent_at: Timestamp,
+    #[cfg_attr(feature = "cbor", n(2))]
+    pub measures: std::vec::Vec<Reading>,
+}
+
+/// Size of the buffer needed to encode any piece of a batch.
+pub const PIECE_LEN: usize = 192;
+
+/// JSON encoding of the pieces of a batch.
+pub mod json {
+    use serde::Serialize;
+    use serde_json_core::ser::Error;
+
+    use super::Reading;
+    use crate::time::Timestamp;
+
+    /// Closes the batch, after its last reading.
+    pub const TAIL: &[u8] = b"]}";
+
+    /// Opens the readings array, replacing the end of the head object.
+    const MEASURES: &[u8] = br#","measures":["#;
+
+    #[derive(Serialize)]
+    struct Head<'a> {
+        capteur_id: &'a str,
+        sent_at: Timestamp,
+    }
+
+    /// Encode the start of the batch into `buffer`, returning the encoded
+    /// length.
+    pub fn head(capteur_id: &str, sent_at: Timestamp, buffer: &mut [u8]) -> Result<usize, Error> {
+        let len = serde_json_core::to_slice(
+            &Head {
+                capteur_id,
+                sent_at,
+            },
+            buffer,
+        )?;
+        let end = len - 1 + MEASURES.len();
+        buffer
+            .get_mut(len - 1..end)
+            .ok_or(Error::BufferFull)?
+            .copy_from_slice(MEASURES);
+        Ok(end)
+    }
+
+    /// Encode the reading at `index` in the batch into `buffer`, returning the
+    /// encoded length.
+    pub fn reading(reading: &Reading, index: usize, buffer: &mut [u8]) -> Result<usize, Error> {
+        if index == 0 {
+            return serde_json_core::to_slice(reading, buffer);
+        }
+        *buffer.first_mut().ok_or(Error::BufferFull)? = b',';
+        Ok(1 + serde_json_core::to_slice(reading, &mut buffer[1..])?)
+    }
+}
+
+/// CBOR encoding of the pieces of a batch, which has no tail.
+#[cfg(feature = "cbor")]
+pub mod cbor {
+    use minicbor::encode::write::Cursor;
+    use minicbor::Encoder;
+
+    use super::Reading;
+    use crate::cbor::Error;
+    use crate::time::Timestamp;
+
+    /// Encode the start of a batch of `len` readings into `buffer`, returning
+    /// the encoded length.
+    pub fn head(
+        capteur_id: &str,
+        sent_at: Timestamp,
+        len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut cursor = Cursor::new(buffer);
+        Encoder::new(&mut cursor)
+            .map(3)
+            .and_then(|e| e.u8(0)?.str(capteur_id)?.u8(1)?.encode(sent_at))
+            .and_then(|e| e.u8(2)?.array(len as u64))
+            .map_err(|_| Error::BufferTooSmall)?;
+        Ok(cursor.position())
+    }
+
+    /// Encode a reading into `buffer`, returning the encoded length.
+    pub fn reading(reading: &Reading, buffer: &mut [u8]) -> Result<usize, Error> {
+        crate::cbor::to_slice(reading, buffer)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn readings() -> Vec<Reading> {
+        (0..3)
+            .map(|i| Reading {
+                timestamp: Timestamp::new(2025, 1, 5, 3, 4 + i, 5).unwrap(),
+                temperature: 21.5 + i as f64,
+                humidity: 45.,
+                samples: (i > 0).then_some(3),
+            })
+            .collect()
+    }
+
+    fn batch() -> MeasureBatch {
+        MeasureBatch {
+            capteur_id: "salon".try_into().unwrap(),
+            sent_at: Timestamp::new(2025, 1, 5, 3, 10, 0).unwrap(),
+            measures: readings(),
+        }
+    }
+
+    #[test]
+    fn test_json_pieces() {
+        let mut body = Vec::new();
+        let mut buffer = [0; PIECE_LEN];
+        let len = json::head("salon", batch().sent_at, &mut buffer).unwrap();
+        body.extend_from_slice(&buffer[..len]);
+        for (index, reading) in readings().iter().enumerate() {
+            let len = json::reading(reading, index, &mut buffer).unwrap();
+            body.extend_from_slice(&buffer[..len]);
+        }
+        body.extend_from_slice(json::TAIL);
+
+        assert!(body.starts_with(
+            br#"{"capteur_id":"salon","sent_at":"2025-01-05T03:10:00Z","measures":[{"timestamp""#
+        ));
+        let (decoded, _) = serde_json_core::from_slice::<MeasureBatch>(&body).unwrap();
+        assert_eq!(decoded, batch());
+    }
+
+    #[test]
+    fn test_json_empty_batch() {
+        let mut buffer = [0; PIECE_LEN];
+        let len = json::head("salon", batch().sent_at, &mut buffer).unwrap();
+        let body = [&buffer[..len], json::TAIL].concat();
+
+        let (decoded, _) = serde_json_core::from_slice::<MeasureBatch>(&body).unwrap();
+        assert!(decoded.measures.is_empty());
+    }
+
+    #[test]
+    fn test_longest_pieces_fit_in_buffer() {
+        let mut buffer = [0; PIECE_LEN];
+        let sent_at = Timestamp::new(2025, 12, 31, 23, 59, 59).unwrap();
+        let reading = Reading {
+            timestamp: sent_at,
+            temperature: -123.456789012345,
+            humidity: 100.00000000000001,
+            samples: Some(u8::MAX),
+        };
+
+        assert!(json::head("capteur-with-a-32-char-long-name", sent_at, &mut buffer).is_ok());
+        assert!(json::reading(&reading, 1, &mut buffer).is_ok());
+        #[cfg(feature = "cbor")]
+        {
+            assert!(cbor::head(
+                "capteur-with-a-32-char-long-name",
+                sent_at,
+                256,
+                &mut buffer
+            )
+            .is_ok());
+            assert!(cbor::reading(&reading, &mut buffer).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_json_buffer_too_small() {
+        assert!(json::head("salon", batch().sent_at, &mut [0; 48]).is_err());
+        assert!(json::reading(&readings()[0], 1, &mut []).is_err());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_pieces() {
+        let mut body = Vec::new();
+        let mut buffer = [0; PIECE_LEN];
+        let len = cbor::head("salon", batch().sent_at, 3, &mut buffer).unwrap();
+        body.extend_from_slice(&buffer[..len]);
+        for reading in readings() {
+            let len = cbor::reading(&reading, &mut buffer).unwrap();
+            body.extend_from_slice(&buffer[..len]);
+        }
+
+        assert_eq!(
+            crate::cbor::from_slice::<MeasureBatch>(&body).unwrap(),
+            batch()
+        );
+    }
+}
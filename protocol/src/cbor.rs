@@ -0,0 +1,61 @@
+//! Compact binary encoding of the measures.
+//!
+//! Structs are encoded as maps keyed by the index of their fields (`#[n(i)]`)
+//! instead of their name, absent optional fields being left out: indexes must
+//! never be reused, new fields take the next one.
+
+use minicbor::decode::{self, Decoder};
+use minicbor::encode::{self, write::Cursor, Encoder, Write};
+use minicbor::{Decode, Encode};
+
+/// Content type of CBOR encoded bodies.
+pub const CONTENT_TYPE: &str = "application/cbor";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The buffer is too small for the encoded value.
+    BufferTooSmall,
+    Decode(decode::Error),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::BufferTooSmall => f.write_str("buffer too small"),
+            Error::Decode(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// Encode `value` into `buffer`, returning the number of bytes written.
+pub fn to_slice<T: Encode<()>>(value: &T, buffer: &mut [u8]) -> Result<usize, Error> {
+    let mut cursor = Cursor::new(buffer);
+    minicbor::encode(value, &mut cursor).map_err(|_| Error::BufferTooSmall)?;
+    Ok(cursor.position())
+}
+
+pub fn from_slice<'a, T: Decode<'a, ()>>(bytes: &'a [u8]) -> Result<T, Error> {
+    minicbor::decode(bytes).map_err(Error::Decode)
+}
+
+/// Encoding of the `heapless` strings, for `#[cbor(with = "crate::cbor::string")]`.
+pub(crate) mod string {
+    use super::*;
+
+    pub fn encode<Ctx, W: Write, const N: usize>(
+        value: &heapless::String<N>,
+        e: &mut Encoder<W>,
+        _ctx: &mut Ctx,
+    ) -> Result<(), encode::Error<W::Error>> {
+        e.str(value)?.ok()
+    }
+
+    pub fn decode<Ctx, const N: usize>(
+        d: &mut Decoder<'_>,
+        _ctx: &mut Ctx,
+    ) -> Result<heapless::String<N>, decode::Error> {
+        d.str()?
+            .try_into()
+            .map_err(|_| decode::Error::message("string too long"))
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use crate::time::Timestamp;
+
+/// Drift of a capteur's RTC, measured when it is resynchronised and sent to
+/// `POST /capteurs/{id}/drift`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockDrift {
+    /// Reference time the RTC has been synchronised to.
+    pub timestamp: Timestamp,
+    /// RTC time minus reference time, in seconds.
+    pub drift: i32,
+    /// Seconds elapsed since the previous synchronisation.
+    pub elapsed: u32,
+}
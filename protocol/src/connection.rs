@@ -0,0 +1,58 @@
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+/// Longest SSID allowed by 802.11.
+pub const SSID_LEN: usize = 32;
+
+pub type Ssid = String<SSID_LEN>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionEventKind {
+    /// The capteur joined a network and got an address.
+    Connected,
+    /// The link or the DHCP lease was lost.
+    Disconnected,
+    /// No network of the list could be joined.
+    JoinFailed,
+}
+
+/// Change of the Wi-Fi connection state of a capteur, sent to
+/// `POST /capteurs/{id}/events` once it is connected again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConnectionEvent {
+    pub kind: ConnectionEventKind,
+    /// Network the event relates to, empty for `JoinFailed`.
+    pub ssid: Ssid,
+    /// Seconds since the capteur booted when the event happened.
+    pub uptime: u64,
+    /// Seconds spent without connection before a `Connected` event, 0 for
+    /// the other kinds.
+    pub downtime: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let event = ConnectionEvent {
+            kind: ConnectionEventKind::Connected,
+            ssid: Ssid::try_from("maison").unwrap(),
+            uptime: 3600,
+            downtime: 42,
+        };
+        let mut buffer = [0; 128];
+        let len = serde_json_core::to_slice(&event, &mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            br#"{"kind":"connected","ssid":"maison","uptime":3600,"downtime":42}"#
+        );
+        let (decoded, _) = serde_json_core::from_slice::<ConnectionEvent>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, event);
+    }
+}
@@ -0,0 +1,67 @@
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use crate::firmware::FirmwareVersion;
+
+/// Longest source path kept, the start of longer ones being cut.
+pub const CRASH_FILE_LEN: usize = 64;
+/// Longest panic message kept, the end of longer ones being cut.
+pub const CRASH_MESSAGE_LEN: usize = 128;
+/// Size of the buffer needed to serialize any [`CrashReport`] as JSON, even
+/// when every character of the file and message is escaped.
+pub const CRASH_JSON_LEN: usize = 1280;
+
+/// Panic of a capteur, kept across the reset and sent to
+/// `POST /capteurs/{id}/crash` once it reached the server again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CrashReport {
+    /// Firmware which panicked, not necessarily the one reporting it after an
+    /// update or a rollback.
+    pub firmware_version: FirmwareVersion,
+    /// Seconds the capteur had been up when it panicked.
+    pub uptime: u64,
+    /// Source file of the panic, empty when unknown.
+    pub file: String<CRASH_FILE_LEN>,
+    pub line: u32,
+    pub message: String<CRASH_MESSAGE_LEN>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = CrashReport {
+            firmware_version: FirmwareVersion::parse("0.2.0").unwrap(),
+            uptime: 3600,
+            file: String::try_from("src/web.rs").unwrap(),
+            line: 42,
+            message: String::try_from("called `Option::unwrap()` on a `None` value").unwrap(),
+        };
+        let mut buffer = [0; CRASH_JSON_LEN];
+        let len = serde_json_core::to_slice(&report, &mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            br#"{"firmware_version":"0.2.0","uptime":3600,"file":"src/web.rs","line":42,"message":"called `Option::unwrap()` on a `None` value"}"#
+        );
+        let (decoded, _) = serde_json_core::from_slice::<CrashReport>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, report);
+    }
+
+    #[test]
+    fn test_escaped_message_fits() {
+        let report = CrashReport {
+            firmware_version: FirmwareVersion::parse("65535.65535.65535").unwrap(),
+            uptime: u64::MAX,
+            file: String::try_from("\u{1}".repeat(CRASH_FILE_LEN).as_str()).unwrap(),
+            line: u32::MAX,
+            message: String::try_from("\u{1}".repeat(CRASH_MESSAGE_LEN).as_str()).unwrap(),
+        };
+        let mut buffer = [0; CRASH_JSON_LEN];
+
+        assert!(serde_json_core::to_slice(&report, &mut buffer).is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+use core::fmt::{self, Write};
+
+use heapless::String;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Length of an Ed25519 signature, in hexadecimal.
+pub const SIGNATURE_HEX_LEN: usize = 128;
+/// Maximum length of the path an image is downloaded from.
+pub const IMAGE_URL_LEN: usize = 96;
+/// Size of the buffer needed to serialize any [`FirmwareManifest`] as JSON.
+pub const MANIFEST_JSON_LEN: usize = 320;
+
+/// Version of a firmware, `major.minor.patch` as in the `Cargo.toml` of the
+/// capteur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl FirmwareVersion {
+    /// Parse a `major.minor.patch` version. Being `const`, the version of the
+    /// firmware is parsed at build time.
+    pub const fn parse(version: &str) -> Option<Self> {
+        let version = version.as_bytes();
+        let mut parts = [0u16; 3];
+        let mut part = 0;
+        let mut digits = 0;
+        let mut i = 0;
+        while i < version.len() {
+            match version[i] {
+                b'.' if digits > 0 && part < 2 => {
+                    part += 1;
+                    digits = 0;
+                }
+                digit @ b'0'..=b'9' if digits < 5 => {
+                    let value = parts[part] as u32 * 10 + (digit - b'0') as u32;
+                    if value > u16::MAX as u32 {
+                        return None;
+                    }
+                    parts[part] = value as u16;
+                    digits += 1;
+                }
+                _ => return None,
+            }
+            i += 1;
+        }
+        if part != 2 || digits == 0 {
+            return None;
+        }
+        Some(Self {
+            major: parts[0],
+            minor: parts[1],
+            patch: parts[2],
+        })
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Serialize for FirmwareVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut buffer: String<17> = String::new();
+        write!(buffer, "{}", self).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&buffer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FirmwareVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct VersionVisitor;
+
+        impl Visitor<'_> for VersionVisitor {
+            type Value = FirmwareVersion;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a major.minor.patch version")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                FirmwareVersion::parse(v)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(VersionVisitor)
+    }
+}
+
+/// Latest firmware of the group of a capteur, served by
+/// `GET /capteurs/{id}/firmware`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareManifest {
+    pub version: FirmwareVersion,
+    /// Size of the image, in bytes.
+    pub size: u32,
+    /// Ed25519 signature of the image, in hexadecimal, see [`signed_header`].
+    pub signature: String<SIGNATURE_HEX_LEN>,
+    /// Path of the image on the server.
+    pub url: String<IMAGE_URL_LEN>,
+}
+
+/// Sent to `POST /capteurs/{id}/firmware` once a capteur runs a firmware and
+/// reached the server.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareReport {
+    pub version: FirmwareVersion,
+}
+
+/// Bytes hashed before the image to compute the signed digest: the
+/// signature covers `SHA-512(signed_header(version, size) || image)`, so that
+/// a signed image cannot be advertised with another version to downgrade a
+/// capteur.
+///
+/// | offset | size | content                            |
+/// |--------|------|------------------------------------|
+/// | 0      | 4    | magic `ENVF`                       |
+/// | 4      | 6    | major, minor, patch, little endian |
+/// | 10     | 4    | image size, little endian          |
+pub fn signed_header(version: FirmwareVersion, size: u32) -> [u8; 14] {
+    let mut header = [0; 14];
+    header[0..4].copy_from_slice(b"ENVF");
+    header[4..6].copy_from_slice(&version.major.to_le_bytes());
+    header[6..8].copy_from_slice(&version.minor.to_le_bytes());
+    header[8..10].copy_from_slice(&version.patch.to_le_bytes());
+    header[10..14].copy_from_slice(&size.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u16, minor: u16, patch: u16) -> FirmwareVersion {
+        FirmwareVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(FirmwareVersion::parse("0.1.0"), Some(version(0, 1, 0)));
+        assert_eq!(
+            FirmwareVersion::parse("12.345.65535"),
+            Some(version(12, 345, 65535))
+        );
+        for invalid in [
+            "",
+            "1",
+            "1.2",
+            "1.2.",
+            "1..2",
+            "1.2.3.4",
+            "1.2.x",
+            "1.2.65536",
+        ] {
+            assert_eq!(FirmwareVersion::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_versions_are_ordered() {
+        assert!(version(0, 10, 0) > version(0, 9, 12));
+        assert!(version(1, 0, 0) > version(0, 10, 0));
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = FirmwareManifest {
+            version: version(0, 2, 1),
+            size: 4096,
+            signature: String::try_from("ab".repeat(64).as_str()).unwrap(),
+            url: String::try_from("/firmware/default/0.2.1").unwrap(),
+        };
+        let mut buffer = [0; MANIFEST_JSON_LEN];
+        let len = serde_json_core::to_slice(&manifest, &mut buffer).unwrap();
+
+        assert!(buffer[..len].starts_with(br#"{"version":"0.2.1","size":4096,"signature":"abab"#));
+        let (decoded, _) = serde_json_core::from_slice::<FirmwareManifest>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, manifest);
+    }
+
+    #[test]
+    fn test_signed_header() {
+        assert_eq!(
+            &signed_header(version(1, 2, 3), 0x010203),
+            b"ENVF\x01\x00\x02\x00\x03\x00\x03\x02\x01\x00"
+        );
+    }
+}
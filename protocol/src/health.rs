@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+use crate::firmware::FirmwareVersion;
+use crate::time::Timestamp;
+
+/// Size of the buffer needed to serialize any [`Heartbeat`] as JSON.
+pub const HEARTBEAT_JSON_LEN: usize = 320;
+
+/// Failed measures of the AM2301 sensor since the boot, by kind of error.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SensorErrors {
+    /// The sensor did not answer in time, usually a wiring issue.
+    pub timeout: u32,
+    /// The checksum of the answer did not match, usually noise on the line.
+    pub checksum: u32,
+    /// The answer could not be decoded.
+    pub invalid: u32,
+    /// The answer was decoded but out of the range of the sensor, the sample
+    /// being discarded.
+    #[serde(default)]
+    pub implausible: u32,
+}
+
+/// Health of a capteur, sent periodically to
+/// `POST /capteurs/{id}/heartbeat`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Heartbeat {
+    /// Seconds since the capteur booted.
+    pub uptime: u64,
+    pub firmware_version: FirmwareVersion,
+    /// Signal strength of the joined network, in dBm, if measured since the
+    /// capteur joined it.
+    pub rssi: Option<i16>,
+    /// Measures that can still be buffered before the oldest ones are
+    /// dropped, `None` when the firmware does not buffer measures.
+    pub free_buffer_slots: Option<u16>,
+    pub sensor_errors: SensorErrors,
+    /// Last successful synchronisation of the RTC, if any.
+    pub last_rtc_sync: Option<Timestamp>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let heartbeat = Heartbeat {
+            uptime: 86_400,
+            firmware_version: FirmwareVersion::parse("0.2.0").unwrap(),
+            rssi: Some(-67),
+            free_buffer_slots: None,
+            sensor_errors: SensorErrors {
+                timeout: 3,
+                checksum: 1,
+                invalid: 0,
+                implausible: 2,
+            },
+            last_rtc_sync: Some(Timestamp::new(2025, 1, 22, 18, 7, 55).unwrap()),
+        };
+        let mut buffer = [0; HEARTBEAT_JSON_LEN];
+        let len = serde_json_core::to_slice(&heartbeat, &mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            br#"{"uptime":86400,"firmware_version":"0.2.0","rssi":-67,"free_buffer_slots":null,"sensor_errors":{"timeout":3,"checksum":1,"invalid":0,"implausible":2},"last_rtc_sync":"2025-01-22T18:07:55Z"}"#
+        );
+        let (decoded, _) = serde_json_core::from_slice::<Heartbeat>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, heartbeat);
+    }
+}
@@ -0,0 +1,31 @@
+//! Types exchanged between the capteurs and the web server.
+//!
+//! The crate is `no_std` so the firmware and the server share the exact same
+//! serde definitions instead of maintaining two versions of the JSON contract.
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod batch;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod clock;
+pub mod connection;
+pub mod crash;
+pub mod firmware;
+pub mod health;
+pub mod measure;
+pub mod reboot;
+pub mod settings;
+pub mod time;
+
+pub use clock::ClockDrift;
+pub use connection::{ConnectionEvent, ConnectionEventKind};
+pub use crash::CrashReport;
+pub use firmware::{FirmwareManifest, FirmwareReport, FirmwareVersion};
+pub use health::{Heartbeat, SensorErrors};
+pub use measure::{Aggregate, CapteurId, Measure};
+pub use reboot::{CriticalTask, RebootReason, RebootReport};
+pub use settings::{CapteurSettings, ConfigAck};
+pub use time::{Timestamp, TimestampError};
@@ -0,0 +1,273 @@
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use crate::time::Timestamp;
+
+/// Maximum length of a capteur identifier.
+pub const CAPTEUR_ID_LEN: usize = 32;
+
+/// Size of the buffer needed to serialize any [`Measure`] as JSON.
+pub const MEASURE_JSON_LEN: usize = 448;
+
+/// Size of the buffer needed to serialize any [`Measure`] as CBOR.
+#[cfg(feature = "cbor")]
+pub const MEASURE_CBOR_LEN: usize = 128;
+
+pub type CapteurId = String<CAPTEUR_ID_LEN>;
+
+/// Statistics of the readings a [`Measure`] sums up, kept at the resolution
+/// of the sensor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "cbor",
+    derive(minicbor::Encode, minicbor::Decode),
+    cbor(map)
+)]
+pub struct Aggregate {
+    /// Number of readings, the reported one included.
+    #[cfg_attr(feature = "cbor", n(0))]
+    pub count: u16,
+    /// Seconds between the first and the last reading.
+    #[cfg_attr(feature = "cbor", n(1))]
+    pub duration: u32,
+    #[cfg_attr(feature = "cbor", n(2))]
+    pub mean_temperature: f32,
+    #[cfg_attr(feature = "cbor", n(3))]
+    pub min_temperature: f32,
+    #[cfg_attr(feature = "cbor", n(4))]
+    pub max_temperature: f32,
+    #[cfg_attr(feature = "cbor", n(5))]
+    pub mean_humidity: f32,
+    #[cfg_attr(feature = "cbor", n(6))]
+    pub min_humidity: f32,
+    #[cfg_attr(feature = "cbor", n(7))]
+    pub max_humidity: f32,
+}
+
+/// A temperature and humidity reading, as sent to `POST /measure`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "cbor",
+    derive(minicbor::Encode, minicbor::Decode),
+    cbor(map)
+)]
+pub struct Measure {
+    #[cfg_attr(feature = "cbor", n(0))]
+    pub timestamp: Timestamp,
+    #[cfg_attr(feature = "cbor", n(1))]
+    pub temperature: f64,
+    #[cfg_attr(feature = "cbor", n(2))]
+    pub humidity: f64,
+    #[cfg_attr(feature = "cbor", n(3), cbor(with = "crate::cbor::string"))]
+    pub capteur_id: CapteurId,
+    /// Number of valid sensor samples the values are the median of, unknown
+    /// for capteurs not filtering their samples.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "cbor", n(4))]
+    pub samples: Option<u8>,
+    /// Readings taken since the previous measure was sent, the capteur only
+    /// sending some of them. `timestamp`, `temperature` and `humidity` are the
+    /// last of these readings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "cbor", n(5))]
+    pub aggregate: Option<Aggregate>,
+}
+
+impl Measure {
+    pub fn to_json(&self) -> Result<String<MEASURE_JSON_LEN>, serde_json_core::ser::Error> {
+        serde_json_core::to_string(self)
+    }
+
+    pub fn from_json(body: &[u8]) -> Result<Self, serde_json_core::de::Error> {
+        serde_json_core::from_slice(body).map(|(measure, _)| measure)
+    }
+
+    /// Encode the measure as CBOR into `buffer`, returning the encoded length.
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self, buffer: &mut [u8]) -> Result<usize, crate::cbor::Error> {
+        crate::cbor::to_slice(self, buffer)
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(body: &[u8]) -> Result<Self, crate::cbor::Error> {
+        crate::cbor::from_slice(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure() -> Measure {
+        Measure {
+            timestamp: Timestamp::new(2025, 1, 5, 3, 4, 5).unwrap(),
+            temperature: 21.3,
+            humidity: 45.8,
+            capteur_id: CapteurId::try_from("salon").unwrap(),
+            samples: None,
+            aggregate: None,
+        }
+    }
+
+    fn aggregate() -> Aggregate {
+        Aggregate {
+            count: 60,
+            duration: 295,
+            mean_temperature: 21.25,
+            min_temperature: 21.,
+            max_temperature: 21.5,
+            mean_humidity: 45.5,
+            min_humidity: 45.,
+            max_humidity: 46.,
+        }
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            measure().to_json().unwrap(),
+            r#"{"timestamp":"2025-01-05T03:04:05Z","temperature":21.3,"humidity":45.8,"capteur_id":"salon"}"#
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let body = measure().to_json().unwrap();
+        assert_eq!(Measure::from_json(body.as_bytes()), Ok(measure()));
+    }
+
+    #[test]
+    fn test_longest_measure_fits_in_buffer() {
+        let measure = Measure {
+            timestamp: Timestamp::new(2025, 12, 31, 23, 59, 59).unwrap(),
+            temperature: -123.456789012345,
+            humidity: 100.00000000000001,
+            capteur_id: CapteurId::try_from("capteur-with-a-32-char-long-name").unwrap(),
+            samples: Some(u8::MAX),
+            aggregate: Some(Aggregate {
+                count: u16::MAX,
+                duration: u32::MAX,
+                mean_temperature: -1.2345678e-38,
+                min_temperature: -1.2345678e-38,
+                max_temperature: -1.2345678e-38,
+                mean_humidity: f32::MIN_POSITIVE,
+                min_humidity: f32::MIN_POSITIVE,
+                max_humidity: f32::MIN_POSITIVE,
+            }),
+        };
+        assert!(measure.to_json().is_ok());
+
+        #[cfg(feature = "cbor")]
+        assert!(measure.to_cbor(&mut [0; MEASURE_CBOR_LEN]).is_ok());
+    }
+
+    #[test]
+    fn test_samples_round_trip() {
+        let measure = Measure {
+            samples: Some(3),
+            ..measure()
+        };
+        let body = measure.to_json().unwrap();
+
+        assert!(body.ends_with(r#","samples":3}"#));
+        assert_eq!(Measure::from_json(body.as_bytes()), Ok(measure));
+    }
+
+    #[test]
+    fn test_aggregate_round_trip() {
+        let measure = Measure {
+            samples: Some(3),
+            aggregate: Some(aggregate()),
+            ..measure()
+        };
+        let body = measure.to_json().unwrap();
+
+        assert!(body.ends_with(
+            r#","aggregate":{"count":60,"duration":295,"mean_temperature":21.25,"min_temperature":21.0,"max_temperature":21.5,"mean_humidity":45.5,"min_humidity":45.0,"max_humidity":46.0}}"#
+        ));
+        assert_eq!(Measure::from_json(body.as_bytes()), Ok(measure));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_aggregate_round_trip() {
+        let measure = Measure {
+            samples: Some(3),
+            aggregate: Some(aggregate()),
+            ..measure()
+        };
+        let mut buffer = [0; MEASURE_CBOR_LEN];
+        let len = measure.to_cbor(&mut buffer).unwrap();
+
+        assert_eq!(Measure::from_cbor(&buffer[..len]).unwrap(), measure);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_samples_round_trip() {
+        let measure = Measure {
+            samples: Some(2),
+            ..measure()
+        };
+        let mut buffer = [0; MEASURE_CBOR_LEN];
+        let len = measure.to_cbor(&mut buffer).unwrap();
+
+        assert_eq!(Measure::from_cbor(&buffer[..len]).unwrap(), measure);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_round_trip() {
+        let mut buffer = [0; MEASURE_CBOR_LEN];
+        let len = measure().to_cbor(&mut buffer).unwrap();
+
+        assert_eq!(Measure::from_cbor(&buffer[..len]).unwrap(), measure());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_is_smaller_than_json() {
+        let mut buffer = [0; MEASURE_CBOR_LEN];
+        let len = measure().to_cbor(&mut buffer).unwrap();
+
+        assert!(len < measure().to_json().unwrap().len() / 2);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_decodes_half_floats_and_unknown_fields() {
+        // As encoded by earlier firmwares, which shrank the floats
+        let body = [
+            0xa6, // map(6)
+            0x00, 0x1a, 0x67, 0x79, 0xf6, 0xa5, // 0: timestamp = 1736046245
+            0x01, 0xf9, 0x4d, 0x60, // 1: temperature = 21.5 as f16
+            0x02, 0xf9, 0x51, 0xa0, // 2: humidity = 45 as f16
+            0x03, 0x65, 0x73, 0x61, 0x6c, 0x6f, 0x6e, // 3: capteur_id = "salon"
+            0x04, 0x03, // 4: samples = 3
+            0x09, 0x61, 0x78, // 9: a field of a later firmware
+        ];
+
+        assert_eq!(
+            Measure::from_cbor(&body).unwrap(),
+            Measure {
+                timestamp: Timestamp::new(2025, 1, 5, 3, 4, 5).unwrap(),
+                temperature: 21.5,
+                humidity: 45.,
+                capteur_id: "salon".try_into().unwrap(),
+                samples: Some(3),
+                aggregate: None,
+            }
+        );
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_buffer_too_small() {
+        assert!(matches!(
+            measure().to_cbor(&mut [0; 16]),
+            Err(crate::cbor::Error::BufferTooSmall)
+        ));
+    }
+}
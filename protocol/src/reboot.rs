@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// Tasks of the firmware watched by the watchdog, which stops being fed as
+/// soon as one of them stops reporting progress.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum CriticalTask {
+    /// Reads the sensor.
+    Measure,
+    /// Brings the network up and schedules the exchanges with the server.
+    Network,
+    /// A request to the server in progress.
+    Post,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum RebootReason {
+    /// The capteur was powered up, or its supply dropped below the brown-out
+    /// threshold: the RP2040 does not tell them apart.
+    PowerOn,
+    /// The RUN pin was pulled low, e.g. by a reset button.
+    ResetPin,
+    /// Reset from the debug port.
+    Debugger,
+    /// The watchdog expired, the firmware hung.
+    Watchdog,
+    Panic,
+    /// A new firmware was installed.
+    FirmwareUpdate,
+    /// A new firmware did not check in and is being rolled back.
+    Rollback,
+    /// The firmware reset itself, e.g. once provisioned.
+    Requested,
+    Unknown,
+}
+
+/// Why a capteur rebooted, sent to `POST /capteurs/{id}/reboots` once it
+/// reached the server after the boot.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RebootReport {
+    pub reason: RebootReason,
+    /// Task which stopped reporting progress, for a `Watchdog` reboot caused
+    /// by a stuck task rather than the whole firmware hanging.
+    pub task: Option<CriticalTask>,
+    /// Seconds the capteur had been up before rebooting, when it rebooted
+    /// itself.
+    pub uptime: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = RebootReport {
+            reason: RebootReason::Watchdog,
+            task: Some(CriticalTask::Post),
+            uptime: Some(3600),
+        };
+        let mut buffer = [0; 64];
+        let len = serde_json_core::to_slice(&report, &mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            br#"{"reason":"watchdog","task":"post","uptime":3600}"#
+        );
+        let (decoded, _) = serde_json_core::from_slice::<RebootReport>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, report);
+    }
+}
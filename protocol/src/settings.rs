@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+/// Bounds, in seconds, of the measure interval. The AM2301 needs 2 seconds
+/// between two readings.
+pub const MIN_MEASURE_INTERVAL: u32 = 2;
+pub const MAX_MEASURE_INTERVAL: u32 = 24 * 3600;
+/// Largest number of measures uploaded at once in low-power mode.
+pub const MAX_UPLOAD_BATCH_LEN: u16 = 256;
+
+/// Versioned settings document of a capteur, served by
+/// `GET /capteurs/{id}/config` and acknowledged by the capteur once applied.
+///
+/// The measures are sent as read, their corrections being the calibrations
+/// kept by the server.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapteurSettings {
+    /// Incremented by the server on every change, 0 until the settings are
+    /// first changed.
+    #[serde(default)]
+    pub version: u32,
+    /// Seconds between two measures, the capteur default when not set.
+    pub measure_interval: Option<u32>,
+    /// Number of measures uploaded at once in low-power mode, the capteur
+    /// default when not set.
+    #[serde(default)]
+    pub upload_batch_len: Option<u16>,
+    /// Whether the capteur logs every sample it reads and every measure it
+    /// takes, on top of the logs of the level the firmware was built with.
+    /// The logs only reach a debug probe, so a flag switching on the logs of
+    /// the measures, the ones worth following on a bench, is all the server
+    /// sets.
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+impl Default for CapteurSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CapteurSettings {
+    pub const fn new() -> Self {
+        Self {
+            version: 0,
+            measure_interval: None,
+            upload_batch_len: None,
+            verbose: false,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.measure_interval.is_none_or(|interval| {
+            (MIN_MEASURE_INTERVAL..=MAX_MEASURE_INTERVAL).contains(&interval)
+        }) && self
+            .upload_batch_len
+            .is_none_or(|len| (1..=MAX_UPLOAD_BATCH_LEN).contains(&len))
+    }
+
+    /// Measure interval to apply, brought within bounds.
+    pub fn bounded_measure_interval(&self) -> Option<u32> {
+        self.measure_interval
+            .map(|interval| interval.clamp(MIN_MEASURE_INTERVAL, MAX_MEASURE_INTERVAL))
+    }
+}
+
+/// Sent to `POST /capteurs/{id}/config/ack` once a capteur applied a version
+/// of its settings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigAck {
+    pub version: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let settings = CapteurSettings {
+            version: 3,
+            measure_interval: Some(60),
+            upload_batch_len: None,
+            verbose: true,
+        };
+        let mut buffer = [0; 160];
+        let len = serde_json_core::to_slice(&settings, &mut buffer).unwrap();
+
+        assert_eq!(
+            &buffer[..len],
+            br#"{"version":3,"measure_interval":60,"upload_batch_len":null,"verbose":true}"#
+        );
+        let (decoded, _) = serde_json_core::from_slice::<CapteurSettings>(&buffer[..len]).unwrap();
+        assert_eq!(decoded, settings);
+    }
+
+    #[test]
+    fn test_missing_fields_have_defaults() {
+        let (settings, _) =
+            serde_json_core::from_slice::<CapteurSettings>(br#"{"measure_interval":null}"#)
+                .unwrap();
+
+        assert_eq!(settings, CapteurSettings::default());
+        assert!(settings.is_valid());
+        assert_eq!(settings.bounded_measure_interval(), None);
+    }
+
+    #[test]
+    fn test_offsets_are_ignored() {
+        // Sent by the servers, and saved by the capteurs, that applied them
+        // on the capteur
+        let (settings, _) = serde_json_core::from_slice::<CapteurSettings>(
+            br#"{"version":2,"measure_interval":60,"temperature_offset":-0.5,"humidity_offset":2.0}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.version, 2);
+        assert_eq!(settings.measure_interval, Some(60));
+    }
+
+    #[test]
+    fn test_interval_bounds() {
+        let settings = |interval| CapteurSettings {
+            measure_interval: Some(interval),
+            ..CapteurSettings::new()
+        };
+
+        assert!(settings(2).is_valid());
+        assert!(!settings(1).is_valid());
+        assert!(!settings(MAX_MEASURE_INTERVAL + 1).is_valid());
+        assert_eq!(settings(0).bounded_measure_interval(), Some(2));
+        assert_eq!(
+            settings(u32::MAX).bounded_measure_interval(),
+            Some(MAX_MEASURE_INTERVAL)
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let settings = CapteurSettings::new();
+
+        assert!(!CapteurSettings {
+            upload_batch_len: Some(0),
+            ..settings
+        }
+        .is_valid());
+        assert!(CapteurSettings {
+            upload_batch_len: Some(MAX_UPLOAD_BATCH_LEN),
+            ..settings
+        }
+        .is_valid());
+    }
+}
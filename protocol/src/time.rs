@@ -0,0 +1,467 @@
+use core::fmt::{self, Write};
+use core::str::FromStr;
+
+use heapless::String;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A UTC date and time with a one second resolution.
+///
+/// Serialized as a zero-padded RFC 3339 string (`2025-01-05T03:04:05Z`) in
+/// human readable formats and as seconds since the Unix epoch in binary
+/// ones. Parsing is more lenient: fields can be unpadded (as sent by older
+/// firmwares), fractional seconds are truncated, a leap second is read as the
+/// second before it and a numeric UTC offset is converted back to UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimestampError {
+    /// The string does not look like `YYYY-MM-DDTHH:MM:SS[.fraction]<offset>`.
+    InvalidFormat,
+    /// A field is outside of its valid range (e.g. month 13).
+    OutOfRange,
+    /// The UTC offset is missing or malformed.
+    InvalidOffset,
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+impl Timestamp {
+    /// Build a timestamp after checking that every field is in range.
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, TimestampError> {
+        if !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(TimestampError::OutOfRange);
+        }
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// Number of seconds since the Unix epoch.
+    pub fn to_unix(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        days * SECONDS_PER_DAY
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+    }
+
+    /// Day of the week, from 0 for Sunday to 6 for Saturday.
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 was a Thursday.
+        (self.to_unix().div_euclid(SECONDS_PER_DAY) + 4).rem_euclid(7) as u8
+    }
+
+    /// Build a timestamp from a number of seconds since the Unix epoch.
+    pub fn from_unix(secs: i64) -> Result<Self, TimestampError> {
+        let days = secs.div_euclid(SECONDS_PER_DAY);
+        let rem = secs.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let year = u16::try_from(year).map_err(|_| TimestampError::OutOfRange)?;
+        Ok(Self {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem % 3600 / 60) as u8,
+            second: (rem % 60) as u8,
+        })
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = TimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor(s.as_bytes());
+
+        let year = cursor.number(4)?;
+        cursor.expect(b'-')?;
+        let month = cursor.number(2)?;
+        cursor.expect(b'-')?;
+        let day = cursor.number(2)?;
+        match cursor.next() {
+            Some(b'T' | b't' | b' ') => {}
+            _ => return Err(TimestampError::InvalidFormat),
+        }
+        let hour = cursor.number(2)?;
+        cursor.expect(b':')?;
+        let minute = cursor.number(2)?;
+        cursor.expect(b':')?;
+        let second = cursor.number(2)?;
+        cursor.fraction()?;
+        let offset = cursor.offset()?;
+        if !cursor.0.is_empty() {
+            return Err(TimestampError::InvalidFormat);
+        }
+
+        // The clock has no room for a leap second, nor for its fraction, so
+        // that 23:59:60 UTC is read as 23:59:59
+        let leap_second = second == 60;
+        let local = Timestamp::new(
+            year,
+            month as u8,
+            day as u8,
+            hour as u8,
+            minute as u8,
+            if leap_second { 59 } else { second as u8 },
+        )?;
+        let utc = if offset == 0 {
+            local
+        } else {
+            Timestamp::from_unix(local.to_unix() - offset)?
+        };
+        if leap_second && (utc.hour, utc.minute) != (23, 59) {
+            return Err(TimestampError::OutOfRange);
+        }
+        Ok(utc)
+    }
+}
+
+/// Minimal byte cursor used by the timestamp parser.
+struct Cursor<'a>(&'a [u8]);
+
+impl Cursor<'_> {
+    fn next(&mut self) -> Option<u8> {
+        let (first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*first)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), TimestampError> {
+        match self.next() {
+            Some(b) if b == byte => Ok(()),
+            _ => Err(TimestampError::InvalidFormat),
+        }
+    }
+
+    /// Read between 1 and `max_digits` decimal digits.
+    fn number(&mut self, max_digits: usize) -> Result<u16, TimestampError> {
+        let len = self
+            .0
+            .iter()
+            .take(max_digits)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if len == 0 {
+            return Err(TimestampError::InvalidFormat);
+        }
+        let value = self.0[..len]
+            .iter()
+            .fold(0u16, |acc, b| acc * 10 + (b - b'0') as u16);
+        self.0 = &self.0[len..];
+        Ok(value)
+    }
+
+    /// Skip the fraction of a second, if any.
+    fn fraction(&mut self) -> Result<(), TimestampError> {
+        if !matches!(self.0.first(), Some(b'.' | b',')) {
+            return Ok(());
+        }
+        self.next();
+        let len = self.0.iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return Err(TimestampError::InvalidFormat);
+        }
+        self.0 = &self.0[len..];
+        Ok(())
+    }
+
+    /// Read a `Z`, `±HH:MM` or `±HHMM` UTC offset, returned in seconds.
+    fn offset(&mut self) -> Result<i64, TimestampError> {
+        let sign = match self.next() {
+            Some(b'Z' | b'z') => return Ok(0),
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(TimestampError::InvalidOffset),
+        };
+        let hours = self.fixed_number(2)?;
+        if self.0.first() == Some(&b':') {
+            self.next();
+        }
+        let minutes = self.fixed_number(2)?;
+        if hours > 23 || minutes > 59 {
+            return Err(TimestampError::InvalidOffset);
+        }
+        Ok(sign * (hours as i64 * 3600 + minutes as i64 * 60))
+    }
+
+    fn fixed_number(&mut self, digits: usize) -> Result<u16, TimestampError> {
+        if self.0.len() < digits || !self.0[..digits].iter().all(u8::is_ascii_digit) {
+            return Err(TimestampError::InvalidOffset);
+        }
+        self.number(digits)
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+// Conversions between civil dates and days since 1970-01-01, from
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// As seconds since the Unix epoch, like the binary serde formats.
+#[cfg(feature = "cbor")]
+impl<C> minicbor::Encode<C> for Timestamp {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+        _ctx: &mut C,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.i64(self.to_unix())?.ok()
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<C> minicbor::Decode<'_, C> for Timestamp {
+    fn decode(
+        d: &mut minicbor::Decoder<'_>,
+        _ctx: &mut C,
+    ) -> Result<Self, minicbor::decode::Error> {
+        Timestamp::from_unix(d.i64()?)
+            .map_err(|_| minicbor::decode::Error::message("timestamp out of range"))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_i64(self.to_unix());
+        }
+        let mut buffer: String<20> = String::new();
+        write!(buffer, "{}", self).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&buffer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TimestampVisitor;
+
+        impl Visitor<'_> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an RFC 3339 timestamp or a Unix timestamp")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Timestamp::from_unix(v)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(v), &self))
+                    .and_then(|v| self.visit_i64(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse()
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(TimestampVisitor)
+        } else {
+            deserializer.deserialize_i64(TimestampVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Timestamp {
+        Timestamp::new(year, month, day, hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn test_display_is_zero_padded() {
+        let mut out: String<20> = String::new();
+        write!(out, "{}", ts(2025, 1, 5, 3, 4, 5)).unwrap();
+        assert_eq!(out, "2025-01-05T03:04:05Z");
+    }
+
+    #[test]
+    fn test_parse_padded_and_unpadded() {
+        let expected = ts(2025, 1, 5, 3, 4, 5);
+        assert_eq!("2025-01-05T03:04:05Z".parse(), Ok(expected));
+        assert_eq!("2025-1-5T3:4:5Z".parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_offset_is_converted_to_utc() {
+        assert_eq!(
+            "2025-01-22T18:07:55+0000".parse(),
+            Ok(ts(2025, 1, 22, 18, 7, 55))
+        );
+        assert_eq!(
+            "2025-01-01T00:30:00+01:00".parse(),
+            Ok(ts(2024, 12, 31, 23, 30, 0))
+        );
+        assert_eq!(
+            "2024-02-28T22:00:00-02:30".parse(),
+            Ok(ts(2024, 2, 29, 0, 30, 0))
+        );
+    }
+
+    #[test]
+    fn test_parse_fractional_seconds() {
+        let expected = ts(2025, 1, 22, 18, 7, 55);
+        assert_eq!("2025-01-22T18:07:55.123Z".parse(), Ok(expected));
+        assert_eq!("2025-01-22T18:07:55.999999999Z".parse(), Ok(expected));
+        assert_eq!("2025-01-22T19:07:55,5+01:00".parse(), Ok(expected));
+        assert_eq!(
+            "2025-01-22T18:07:55.Z".parse::<Timestamp>(),
+            Err(TimestampError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_parse_leap_second() {
+        assert_eq!(
+            "2016-12-31T23:59:60.5Z".parse(),
+            Ok(ts(2016, 12, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            "2017-01-01T00:59:60+01:00".parse(),
+            Ok(ts(2016, 12, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            "2016-12-31T23:59:60+01:00".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "2016-12-31T12:30:60Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_parse_seconds_out_of_range() {
+        assert_eq!(
+            "2016-12-31T23:59:61Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "2016-12-31T23:59:99Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<Timestamp>(), Err(TimestampError::InvalidFormat));
+        assert_eq!(
+            "2025-01-05".parse::<Timestamp>(),
+            Err(TimestampError::InvalidFormat)
+        );
+        assert_eq!(
+            "2025-13-05T03:04:05Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "2025-02-29T03:04:05Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "2025-01-05T03:04:05".parse::<Timestamp>(),
+            Err(TimestampError::InvalidOffset)
+        );
+        assert_eq!(
+            "2025-01-05T03:04:05+1".parse::<Timestamp>(),
+            Err(TimestampError::InvalidOffset)
+        );
+    }
+
+    #[test]
+    fn test_weekday() {
+        assert_eq!(ts(1970, 1, 1, 0, 0, 0).weekday(), 4);
+        assert_eq!(ts(2025, 1, 5, 23, 59, 59).weekday(), 0);
+        assert_eq!(ts(2025, 1, 22, 18, 7, 55).weekday(), 3);
+    }
+
+    #[test]
+    fn test_unix_round_trip() {
+        assert_eq!(ts(1970, 1, 1, 0, 0, 0).to_unix(), 0);
+        assert_eq!(ts(2025, 1, 22, 18, 7, 55).to_unix(), 1_737_569_275);
+        assert_eq!(
+            Timestamp::from_unix(1_737_569_275),
+            Ok(ts(2025, 1, 22, 18, 7, 55))
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Requests of the capteurs to the API.
+
+use std::fmt;
+
+use capteur_core::now::{parse_now, NowError};
+use capteur_core::payload::{encode_measure, Encoding};
+use protocol::measure::MEASURE_JSON_LEN;
+use protocol::{CapteurId, ClockDrift, Measure, Timestamp};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server could not be reached.
+    Request(reqwest::Error),
+    /// The server answered with an error.
+    Status(StatusCode),
+    /// The response of `/now` is invalid.
+    Now(NowError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Request(err) => write!(f, "{err}"),
+            Error::Status(status) => write!(f, "server answered {status}"),
+            Error::Now(err) => write!(f, "invalid time: {err:?}"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+pub struct Api {
+    client: Client,
+    url: String,
+}
+
+impl Api {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Current time, from the `/now` endpoint the capteurs fall back to.
+    pub async fn now(&self) -> Result<Timestamp, Error> {
+        let response = self.client.get(format!("{}/now", self.url)).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::Status(response.status()));
+        }
+        parse_now(&response.bytes().await?).map_err(Error::Now)
+    }
+
+    /// Send `measure`, cut in half when `corrupt`. Returns the status the
+    /// server answered.
+    pub async fn post_measure(
+        &self,
+        measure: &Measure,
+        encoding: Encoding,
+        corrupt: bool,
+    ) -> Result<StatusCode, Error> {
+        let mut buffer = [0; MEASURE_JSON_LEN];
+        let mut body = encode_measure(measure, encoding, &mut buffer)
+            .expect("MEASURE_JSON_LEN fits any measure");
+        if corrupt {
+            body = &body[..body.len() / 2];
+        }
+        let response = self
+            .client
+            .post(format!("{}/measure", self.url))
+            .header(CONTENT_TYPE, encoding.content_type())
+            .body(body.to_vec())
+            .send()
+            .await?;
+        Ok(response.status())
+    }
+
+    pub async fn post_drift(
+        &self,
+        capteur_id: &CapteurId,
+        drift: &ClockDrift,
+    ) -> Result<(), Error> {
+        let response = self
+            .client
+            .post(format!("{}/capteurs/{capteur_id}/drift", self.url))
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(drift).expect("a drift is always serializable"))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::Status(response.status()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! A virtual capteur: the room it measures, its sensor and its RTC, each
+//! misbehaving as configured in its [`Profile`].
+
+use capteur_core::drift::DriftTracker;
+use capteur_core::reporting::{Reporter, ReportingPolicy};
+use capteur_core::sampling::{Filtered, Sample, Sampler};
+use protocol::{Aggregate, CapteurId, ClockDrift, Timestamp};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// How a virtual capteur behaves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    /// Amplitude of the noise of each sample, in °C.
+    pub temperature_noise: f64,
+    /// Amplitude of the noise of each sample, in %.
+    pub humidity_noise: f64,
+    /// How fast the RTC runs, in parts per million.
+    pub drift: f64,
+    /// Offset of the RTC from the reference it is set to, in seconds.
+    pub skew: i64,
+    /// Probability for a read of the sensor to fail.
+    pub dropout: f64,
+    /// Probability for a measure to be lost on the way to the server.
+    pub outage: f64,
+    /// Probability for a measure to be sent truncated.
+    pub corrupt: f64,
+    /// Probability for a synchronisation of the RTC to fail.
+    pub sync_failure: f64,
+}
+
+/// The room a capteur is in: the temperature and humidity wander slowly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Environment {
+    temperature: f64,
+    humidity: f64,
+}
+
+impl Environment {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            temperature: rng.gen_range(18.0..24.0),
+            humidity: rng.gen_range(35.0..60.0),
+        }
+    }
+
+    /// Let some time pass.
+    pub fn step(&mut self, rng: &mut impl Rng) {
+        self.temperature = (self.temperature + rng.gen_range(-0.05..=0.05)).clamp(10., 35.);
+        self.humidity = (self.humidity + rng.gen_range(-0.2..=0.2)).clamp(20., 80.);
+    }
+
+    /// A read of the sensor.
+    pub fn sample(&self, rng: &mut impl Rng, profile: &Profile) -> Sample {
+        let noise =
+            |amplitude: f64, rng: &mut dyn rand::RngCore| rng.gen_range(-amplitude..=amplitude);
+        Sample {
+            temperature: self.temperature + noise(profile.temperature_noise, rng),
+            humidity: self.humidity + noise(profile.humidity_noise, rng),
+        }
+    }
+}
+
+/// RTC of a capteur, not running until first set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    drift: f64,
+    skew: i64,
+    /// Unix time it was last set to, and the uptime at which it was.
+    set: Option<(i64, f64)>,
+}
+
+impl Clock {
+    pub fn new(drift: f64, skew: i64) -> Self {
+        Self {
+            drift,
+            skew,
+            set: None,
+        }
+    }
+
+    /// Time at `uptime`, in seconds, `None` until set.
+    pub fn now(&self, uptime: f64) -> Option<Timestamp> {
+        let (unix, set_at) = self.set?;
+        let elapsed = (uptime - set_at) * (1. + self.drift / 1e6);
+        Timestamp::from_unix(unix + elapsed.floor() as i64).ok()
+    }
+
+    /// Set the clock to `reference` at `uptime`, in seconds.
+    pub fn set(&mut self, reference: Timestamp, uptime: f64) {
+        self.set = Some((reference.to_unix() + self.skew, uptime));
+    }
+}
+
+pub struct VirtualCapteur {
+    pub id: CapteurId,
+    pub profile: Profile,
+    rng: StdRng,
+    environment: Environment,
+    clock: Clock,
+    drift_tracker: DriftTracker,
+    reporter: Reporter,
+}
+
+impl VirtualCapteur {
+    pub fn new(id: CapteurId, profile: Profile, policy: ReportingPolicy, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self {
+            id,
+            profile,
+            environment: Environment::new(&mut rng),
+            rng,
+            clock: Clock::new(profile.drift, profile.skew),
+            drift_tracker: DriftTracker::new(),
+            reporter: Reporter::new(policy),
+        }
+    }
+
+    /// Draw whether something happening with `probability` does.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.rng.gen_bool(probability)
+    }
+
+    /// Take a measure like the firmware does, from several samples, failed
+    /// reads being retried. `None` when no read succeeded.
+    pub fn measure(&mut self) -> Option<Filtered> {
+        self.environment.step(&mut self.rng);
+        let mut sampler = Sampler::new();
+        while !sampler.is_done() {
+            if self.rng.gen_bool(self.profile.dropout) {
+                sampler.failed();
+            } else {
+                sampler.read(self.environment.sample(&mut self.rng, &self.profile));
+            }
+        }
+        sampler.filtered()
+    }
+
+    /// The measure taken at `uptime`, in seconds, and the statistics to send
+    /// with it when it is to be sent.
+    pub fn report(&mut self, uptime: u64, measure: &Filtered) -> Option<Aggregate> {
+        self.reporter
+            .reading(uptime, measure.temperature, measure.humidity)
+    }
+
+    /// Time of the RTC at `uptime`, `None` until it is synchronised.
+    pub fn now(&self, uptime: f64) -> Option<Timestamp> {
+        self.clock.now(uptime)
+    }
+
+    /// The RTC is set to `reference` at `uptime`, returns how much it drifted
+    /// since the previous synchronisation.
+    pub fn synced(&mut self, reference: Timestamp, uptime: f64) -> Option<ClockDrift> {
+        let rtc_now = self.clock.now(uptime);
+        self.clock.set(reference, uptime);
+        self.drift_tracker.record(rtc_now, reference, uptime as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: Profile = Profile {
+        temperature_noise: 0.2,
+        humidity_noise: 1.,
+        drift: 0.,
+        skew: 0,
+        dropout: 0.,
+        outage: 0.,
+        corrupt: 0.,
+        sync_failure: 0.,
+    };
+
+    const POLICY: ReportingPolicy = ReportingPolicy {
+        period: Some(0),
+        deadband: None,
+        max_silence: 0,
+    };
+
+    fn ts(unix: i64) -> Timestamp {
+        Timestamp::from_unix(unix).unwrap()
+    }
+
+    fn capteur(profile: Profile) -> VirtualCapteur {
+        VirtualCapteur::new("sim-0".try_into().unwrap(), profile, POLICY, 42)
+    }
+
+    #[test]
+    fn test_clock_not_running_until_set() {
+        let mut clock = Clock::new(0., 0);
+        assert_eq!(clock.now(10.), None);
+
+        clock.set(ts(1_737_569_275), 10.);
+
+        assert_eq!(clock.now(10.), Some(ts(1_737_569_275)));
+        assert_eq!(clock.now(70.5), Some(ts(1_737_569_335)));
+    }
+
+    #[test]
+    fn test_clock_drift_and_skew() {
+        let mut clock = Clock::new(100., -30);
+        clock.set(ts(1_737_569_275), 0.);
+
+        assert_eq!(clock.now(0.), Some(ts(1_737_569_245)));
+        assert_eq!(clock.now(10_000.), Some(ts(1_737_569_245 + 10_001)));
+    }
+
+    #[test]
+    fn test_drift_reported_on_resync() {
+        let mut capteur = capteur(Profile {
+            drift: 50.,
+            ..PROFILE
+        });
+
+        assert_eq!(capteur.synced(ts(1_737_569_275), 0.), None);
+        let drift = capteur.synced(ts(1_737_569_275 + 21_600), 21_600.).unwrap();
+
+        assert_eq!(drift.drift, 1);
+        assert_eq!(drift.elapsed, 21_600);
+    }
+
+    #[test]
+    fn test_measures_stay_plausible() {
+        let mut capteur = capteur(Profile {
+            temperature_noise: 5.,
+            humidity_noise: 30.,
+            ..PROFILE
+        });
+
+        for _ in 0..1000 {
+            let measure = capteur.measure().unwrap();
+            assert!(Sample {
+                temperature: measure.temperature,
+                humidity: measure.humidity
+            }
+            .is_plausible());
+            assert!(measure.samples > 0);
+        }
+    }
+
+    #[test]
+    fn test_dropouts() {
+        let mut always = capteur(Profile {
+            dropout: 1.,
+            ..PROFILE
+        });
+        assert_eq!(always.measure(), None);
+
+        let mut sometimes = capteur(Profile {
+            dropout: 0.5,
+            ..PROFILE
+        });
+        let measures: Vec<_> = (0..1000).map(|_| sometimes.measure()).collect();
+        assert!(measures.iter().any(Option::is_none));
+        assert!(measures.iter().flatten().any(|measure| measure.samples < 3));
+    }
+
+    #[test]
+    fn test_same_seed_same_measures() {
+        let mut first = capteur(PROFILE);
+        let mut second = capteur(PROFILE);
+
+        for _ in 0..10 {
+            assert_eq!(first.measure(), second.measure());
+        }
+    }
+}
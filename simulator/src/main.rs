@@ -0,0 +1,311 @@
+//! Simulate capteurs on the host, to test the server end to end or load test
+//! it without any hardware.
+//!
+//! The virtual capteurs share the firmware's logic from `capteur-core`: they
+//! sample and filter their readings, aggregate them following a reporting
+//! policy, set their RTC from `/now` and encode their measures the same way.
+//! Noise, clock drift and skew, sensor dropouts, network outages, corrupted
+//! payloads and failed synchronisations can be added to exercise the server.
+//!
+//! For instance, 20 capteurs sending a measure every 100ms, a tenth of them
+//! corrupted, and failing unless the server behaved:
+//!
+//! ```sh
+//! cargo run -- --url http://localhost:3000 --capteurs 20 --interval 0.1 \
+//!     --measures 50 --corrupt 0.1 --strict
+//! ```
+
+mod api;
+mod capteur;
+mod stats;
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use capteur_core::payload::Encoding;
+use capteur_core::reporting::ReportingPolicy;
+use capteur_core::time_sync::SyncSchedule;
+use clap::Parser;
+use protocol::measure::CAPTEUR_ID_LEN;
+use protocol::Measure;
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Instant};
+
+use crate::api::Api;
+use crate::capteur::{Profile, VirtualCapteur};
+use crate::stats::{increment, Stats};
+
+fn probability(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err("expected a probability between 0 and 1".to_string()),
+    }
+}
+
+fn interval(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(interval) if interval > 0. && interval.is_finite() => Ok(interval),
+        _ => Err("expected a positive number of seconds".to_string()),
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Simulate capteurs sending their measures to the server")]
+struct Args {
+    /// URL of the API
+    #[arg(long, default_value = "http://localhost:3000")]
+    url: String,
+    /// Number of capteurs
+    #[arg(long, default_value_t = 1)]
+    capteurs: u32,
+    /// Identifiers of the capteurs, followed by their number
+    #[arg(long, default_value = "sim-")]
+    prefix: String,
+    /// Seconds between two measures of a capteur
+    #[arg(long, default_value_t = 5., value_parser = interval)]
+    interval: f64,
+    /// Measures taken by each capteur before stopping, run until interrupted
+    /// otherwise
+    #[arg(long)]
+    measures: Option<u64>,
+    /// Seconds over which measures are aggregated, each one being sent when 0
+    #[arg(long, default_value_t = 0)]
+    period: u64,
+    /// Seconds between two synchronisations of the RTCs
+    #[arg(long, default_value_t = 6 * 3600)]
+    sync_period: u64,
+    /// Send the measures as CBOR instead of JSON
+    #[arg(long)]
+    cbor: bool,
+    /// Amplitude of the noise of the temperature, in °C
+    #[arg(long, default_value_t = 0.2)]
+    temperature_noise: f64,
+    /// Amplitude of the noise of the humidity, in %
+    #[arg(long, default_value_t = 1.)]
+    humidity_noise: f64,
+    /// How fast the RTCs run, in parts per million
+    #[arg(long, default_value_t = 0., allow_negative_numbers = true)]
+    drift: f64,
+    /// Offset of the RTCs from the server time, in seconds
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    skew: i64,
+    /// Probability for a read of the sensor to fail
+    #[arg(long, default_value_t = 0., value_parser = probability)]
+    dropout: f64,
+    /// Probability for a measure to be lost on the way to the server
+    #[arg(long, default_value_t = 0., value_parser = probability)]
+    outage: f64,
+    /// Probability for a measure to be sent truncated
+    #[arg(long, default_value_t = 0., value_parser = probability)]
+    corrupt: f64,
+    /// Probability for a synchronisation of the RTC to fail
+    #[arg(long, default_value_t = 0., value_parser = probability)]
+    sync_failure: f64,
+    /// Seed of the simulation, random otherwise
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Exit with an error when the server did not behave: a valid measure
+    /// refused, a corrupted one accepted, or any request failing
+    #[arg(long)]
+    strict: bool,
+    /// Print every measure sent
+    #[arg(long, short)]
+    verbose: bool,
+}
+
+struct Simulation {
+    api: Api,
+    stats: Stats,
+    encoding: Encoding,
+    interval: Duration,
+    measures: Option<u64>,
+    /// Seconds between two synchronisations of the RTC.
+    sync_period: u64,
+    start: Instant,
+    verbose: bool,
+}
+
+impl Simulation {
+    fn uptime(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Set the RTC of `capteur` from the server, reporting its drift. Returns
+    /// when to synchronise it next.
+    async fn sync(&self, capteur: &mut VirtualCapteur, schedule: &mut SyncSchedule) -> Instant {
+        let retry =
+            |schedule: &mut SyncSchedule| Instant::now() + Duration::from_secs(schedule.failed());
+        if capteur.chance(capteur.profile.sync_failure) {
+            increment(&self.stats.sync_failures);
+            return retry(schedule);
+        }
+
+        let reference = match self.api.now().await {
+            Ok(reference) => reference,
+            Err(err) => {
+                eprintln!("{}: unable to get the time: {err}", capteur.id);
+                increment(match err {
+                    api::Error::Request(_) => &self.stats.unreachable,
+                    _ => &self.stats.sync_errors,
+                });
+                return retry(schedule);
+            }
+        };
+        increment(&self.stats.syncs);
+
+        if let Some(drift) = capteur.synced(reference, self.uptime().as_secs_f64()) {
+            match self.api.post_drift(&capteur.id, &drift).await {
+                Ok(()) => increment(&self.stats.drifts),
+                Err(err) => {
+                    eprintln!("{}: unable to report the drift: {err}", capteur.id);
+                    increment(match err {
+                        api::Error::Request(_) => &self.stats.unreachable,
+                        _ => &self.stats.server_errors,
+                    });
+                }
+            }
+        }
+        Instant::now() + Duration::from_secs(schedule.synced())
+    }
+
+    /// Take a measure with `capteur` and send it when due.
+    async fn measure(&self, capteur: &mut VirtualCapteur) {
+        increment(&self.stats.measures);
+        let uptime = self.uptime();
+        let Some(timestamp) = capteur.now(uptime.as_secs_f64()) else {
+            increment(&self.stats.unsynced);
+            return;
+        };
+        let Some(filtered) = capteur.measure() else {
+            increment(&self.stats.lost);
+            return;
+        };
+        let Some(aggregate) = capteur.report(uptime.as_secs(), &filtered) else {
+            increment(&self.stats.aggregated);
+            return;
+        };
+        if capteur.chance(capteur.profile.outage) {
+            increment(&self.stats.outages);
+            return;
+        }
+
+        let measure = Measure {
+            timestamp,
+            temperature: filtered.temperature,
+            humidity: filtered.humidity,
+            capteur_id: capteur.id.clone(),
+            samples: Some(filtered.samples),
+            aggregate: Some(aggregate),
+        };
+        let corrupted = capteur.chance(capteur.profile.corrupt);
+        let sent_at = Instant::now();
+        match self
+            .api
+            .post_measure(&measure, self.encoding, corrupted)
+            .await
+        {
+            Ok(status) => {
+                if self.verbose || (!status.is_success() && !corrupted) {
+                    println!(
+                        "{} ({}): T = {:.2}, humidity = {:.2}{} -> {status}",
+                        measure.timestamp,
+                        measure.capteur_id,
+                        measure.temperature,
+                        measure.humidity,
+                        if corrupted { " (corrupted)" } else { "" },
+                    );
+                }
+                self.stats
+                    .measure_sent(status, sent_at.elapsed(), corrupted);
+            }
+            Err(err) => {
+                eprintln!("{}: unable to send the measure: {err}", capteur.id);
+                increment(&self.stats.unreachable);
+            }
+        }
+    }
+
+    /// Run `capteur`, its first measure being taken after `offset`.
+    async fn run(&self, mut capteur: VirtualCapteur, offset: Duration) {
+        let mut schedule = SyncSchedule::new(self.sync_period);
+        let mut next_sync = self.start;
+        let mut next_measure = self.start + offset;
+        let mut taken = 0;
+        while self.measures.is_none_or(|measures| taken < measures) {
+            sleep_until(next_measure).await;
+            if Instant::now() >= next_sync {
+                next_sync = self.sync(&mut capteur, &mut schedule).await;
+            }
+            self.measure(&mut capteur).await;
+            taken += 1;
+            next_measure += self.interval;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.prefix.len() + args.capteurs.max(1).ilog10() as usize + 1 > CAPTEUR_ID_LEN {
+        eprintln!("The prefix is too long for the identifiers of the capteurs");
+        return ExitCode::FAILURE;
+    }
+    let seed = args.seed.unwrap_or_else(rand::random);
+    println!("Simulating {} capteurs, seed {seed}", args.capteurs);
+
+    let profile = Profile {
+        temperature_noise: args.temperature_noise,
+        humidity_noise: args.humidity_noise,
+        drift: args.drift,
+        skew: args.skew,
+        dropout: args.dropout,
+        outage: args.outage,
+        corrupt: args.corrupt,
+        sync_failure: args.sync_failure,
+    };
+    let policy = ReportingPolicy {
+        period: Some(args.period),
+        deadband: None,
+        max_silence: args.period,
+    };
+    let interval = Duration::from_secs_f64(args.interval);
+    let simulation = Arc::new(Simulation {
+        api: Api::new(&args.url),
+        stats: Stats::default(),
+        encoding: if args.cbor {
+            Encoding::Cbor
+        } else {
+            Encoding::Json
+        },
+        interval,
+        measures: args.measures,
+        sync_period: args.sync_period,
+        start: Instant::now(),
+        verbose: args.verbose,
+    });
+
+    let mut capteurs = JoinSet::new();
+    for i in 0..args.capteurs {
+        let id = format!("{}{i}", args.prefix)
+            .as_str()
+            .try_into()
+            .expect("the length of the identifiers is checked");
+        let capteur = VirtualCapteur::new(id, profile, policy, seed.wrapping_add(i.into()));
+        // Spread the capteurs over the interval
+        let offset = interval.mul_f64(f64::from(i) / f64::from(args.capteurs));
+        let simulation = simulation.clone();
+        capteurs.spawn(async move { simulation.run(capteur, offset).await });
+    }
+
+    tokio::select! {
+        _ = capteurs.join_all() => {}
+        _ = tokio::signal::ctrl_c() => println!("Interrupted"),
+    }
+
+    println!("{}", simulation.stats.summary(simulation.uptime()));
+    if args.strict && !simulation.stats.is_clean() {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,199 @@
+//! Counters of what the virtual capteurs did, shared by all of them.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Measures taken, lost ones included.
+    pub measures: AtomicU64,
+    /// Measures without any successful read of the sensor.
+    pub lost: AtomicU64,
+    /// Measures not taken because the RTC was not set yet.
+    pub unsynced: AtomicU64,
+    /// Measures aggregated into a later one instead of being sent.
+    pub aggregated: AtomicU64,
+    /// Measures dropped on the way to the server.
+    pub outages: AtomicU64,
+    pub accepted: AtomicU64,
+    /// Valid measures the server refused.
+    pub rejected: AtomicU64,
+    pub corrupted_rejected: AtomicU64,
+    pub corrupted_accepted: AtomicU64,
+    /// Requests failing with a 5xx status.
+    pub server_errors: AtomicU64,
+    /// Requests not reaching the server.
+    pub unreachable: AtomicU64,
+    pub syncs: AtomicU64,
+    /// Synchronisations of the RTC made to fail.
+    pub sync_failures: AtomicU64,
+    /// Synchronisations of the RTC failing on the server side.
+    pub sync_errors: AtomicU64,
+    pub drifts: AtomicU64,
+    /// Sum and maximum of the time taken by the measures sent, in
+    /// microseconds.
+    latency_total: AtomicU64,
+    latency_max: AtomicU64,
+}
+
+pub fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn get(counter: &AtomicU64) -> u64 {
+    counter.load(Ordering::Relaxed)
+}
+
+impl Stats {
+    /// The server answered `status` after `latency` to a measure, `corrupted`
+    /// or not.
+    pub fn measure_sent(&self, status: StatusCode, latency: Duration, corrupted: bool) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.latency_total.fetch_add(micros, Ordering::Relaxed);
+        self.latency_max.fetch_max(micros, Ordering::Relaxed);
+
+        increment(match (status, corrupted) {
+            (status, _) if status.is_server_error() => &self.server_errors,
+            (status, false) if status.is_success() => &self.accepted,
+            (_, false) => &self.rejected,
+            (status, true) if status.is_success() => &self.corrupted_accepted,
+            (_, true) => &self.corrupted_rejected,
+        });
+    }
+
+    /// Whether the server behaved as expected: valid measures accepted,
+    /// corrupted ones refused, and no request failed on its side.
+    pub fn is_clean(&self) -> bool {
+        [
+            &self.rejected,
+            &self.corrupted_accepted,
+            &self.server_errors,
+            &self.unreachable,
+            &self.sync_errors,
+        ]
+        .into_iter()
+        .all(|counter| get(counter) == 0)
+    }
+
+    /// Summary of a run of `elapsed`.
+    pub fn summary(&self, elapsed: Duration) -> Summary<'_> {
+        Summary {
+            stats: self,
+            elapsed,
+        }
+    }
+}
+
+pub struct Summary<'a> {
+    stats: &'a Stats,
+    elapsed: Duration,
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.stats;
+        let sent = [
+            &stats.accepted,
+            &stats.rejected,
+            &stats.corrupted_accepted,
+            &stats.corrupted_rejected,
+            &stats.server_errors,
+        ]
+        .into_iter()
+        .map(get)
+        .sum::<u64>();
+        let seconds = self.elapsed.as_secs_f64();
+
+        writeln!(f, "Ran for {seconds:.1}s")?;
+        writeln!(
+            f,
+            "Measures: {} taken, {} lost, {} before the RTC was set, {} aggregated",
+            get(&stats.measures),
+            get(&stats.lost),
+            get(&stats.unsynced),
+            get(&stats.aggregated),
+        )?;
+        writeln!(
+            f,
+            "Sent: {sent} ({:.1}/s), {} dropped on the way, {} unreachable",
+            sent as f64 / seconds.max(f64::EPSILON),
+            get(&stats.outages),
+            get(&stats.unreachable),
+        )?;
+        writeln!(
+            f,
+            "Server: {} accepted, {} rejected, {} server errors",
+            get(&stats.accepted),
+            get(&stats.rejected),
+            get(&stats.server_errors),
+        )?;
+        writeln!(
+            f,
+            "Corrupted: {} rejected, {} accepted",
+            get(&stats.corrupted_rejected),
+            get(&stats.corrupted_accepted),
+        )?;
+        writeln!(
+            f,
+            "RTC: {} synchronisations, {} made to fail, {} errors, {} drifts reported",
+            get(&stats.syncs),
+            get(&stats.sync_failures),
+            get(&stats.sync_errors),
+            get(&stats.drifts),
+        )?;
+        if sent > 0 {
+            write!(
+                f,
+                "Latency: {:.1}ms mean, {:.1}ms max",
+                get(&stats.latency_total) as f64 / sent as f64 / 1000.,
+                get(&stats.latency_max) as f64 / 1000.,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_responses_are_classified() {
+        let stats = Stats::default();
+        let latency = Duration::from_millis(3);
+
+        stats.measure_sent(StatusCode::OK, latency, false);
+        stats.measure_sent(StatusCode::UNPROCESSABLE_ENTITY, latency, true);
+        assert!(stats.is_clean());
+
+        stats.measure_sent(StatusCode::INTERNAL_SERVER_ERROR, latency, true);
+        assert_eq!(get(&stats.server_errors), 1);
+        assert!(!stats.is_clean());
+    }
+
+    #[test]
+    fn test_corrupted_measure_accepted() {
+        let stats = Stats::default();
+
+        stats.measure_sent(StatusCode::OK, Duration::ZERO, true);
+
+        assert_eq!(get(&stats.corrupted_accepted), 1);
+        assert!(!stats.is_clean());
+    }
+
+    #[test]
+    fn test_summary() {
+        let stats = Stats::default();
+        stats.measure_sent(StatusCode::OK, Duration::from_millis(2), false);
+        stats.measure_sent(StatusCode::BAD_REQUEST, Duration::from_millis(4), false);
+
+        let summary = stats.summary(Duration::from_secs(2)).to_string();
+
+        assert!(summary.contains("Sent: 2 (1.0/s)"), "{summary}");
+        assert!(summary.contains("1 accepted, 1 rejected"), "{summary}");
+        assert!(summary.contains("3.0ms mean, 4.0ms max"), "{summary}");
+    }
+}
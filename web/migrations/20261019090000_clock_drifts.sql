@@ -0,0 +1,6 @@
+CREATE TABLE t_clock_drifts (
+    timestamp TIMESTAMP,
+    capteur VARCHAR,
+    drift_seconds INTEGER,
+    elapsed_seconds BIGINT
+)
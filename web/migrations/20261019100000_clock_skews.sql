@@ -0,0 +1,8 @@
+ALTER TABLE t_measures ADD COLUMN received_at TIMESTAMP;
+
+CREATE TABLE t_clock_skews (
+    capteur VARCHAR NOT NULL,
+    received_at TIMESTAMP NOT NULL,
+    skew_seconds FLOAT NOT NULL,
+    PRIMARY KEY (capteur, received_at)
+)
@@ -0,0 +1,8 @@
+CREATE TABLE t_connection_events (
+    received_at TIMESTAMP,
+    capteur VARCHAR,
+    kind VARCHAR,
+    ssid VARCHAR,
+    uptime_seconds BIGINT,
+    downtime_seconds BIGINT
+)
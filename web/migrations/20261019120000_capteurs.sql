@@ -0,0 +1,4 @@
+CREATE TABLE t_capteurs (
+    id VARCHAR PRIMARY KEY,
+    measure_interval_seconds INTEGER
+)
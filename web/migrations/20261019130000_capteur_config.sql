@@ -0,0 +1,5 @@
+ALTER TABLE t_capteurs
+    ADD COLUMN config_version INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN upload_batch_len INTEGER,
+    ADD COLUMN applied_version INTEGER,
+    ADD COLUMN applied_at TIMESTAMP;
@@ -0,0 +1,10 @@
+CREATE TABLE t_calibrations (
+    capteur VARCHAR NOT NULL,
+    valid_from TIMESTAMP NOT NULL,
+    valid_until TIMESTAMP,
+    temperature_offset FLOAT NOT NULL DEFAULT 0,
+    temperature_gain FLOAT NOT NULL DEFAULT 1,
+    humidity_offset FLOAT NOT NULL DEFAULT 0,
+    humidity_gain FLOAT NOT NULL DEFAULT 1,
+    PRIMARY KEY (capteur, valid_from)
+)
@@ -0,0 +1,15 @@
+CREATE TABLE t_firmwares (
+    capteur_group VARCHAR NOT NULL,
+    major INTEGER NOT NULL,
+    minor INTEGER NOT NULL,
+    patch INTEGER NOT NULL,
+    image BYTEA NOT NULL,
+    signature VARCHAR NOT NULL,
+    uploaded_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (capteur_group, major, minor, patch)
+);
+
+ALTER TABLE t_capteurs
+    ADD COLUMN capteur_group VARCHAR NOT NULL DEFAULT 'default',
+    ADD COLUMN firmware_version VARCHAR,
+    ADD COLUMN firmware_reported_at TIMESTAMP;
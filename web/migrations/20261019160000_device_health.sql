@@ -0,0 +1,14 @@
+CREATE TABLE t_device_health (
+    received_at TIMESTAMP NOT NULL,
+    capteur VARCHAR NOT NULL,
+    uptime_seconds BIGINT NOT NULL,
+    firmware_version VARCHAR NOT NULL,
+    rssi SMALLINT,
+    free_buffer_slots INTEGER,
+    sensor_timeout_errors BIGINT NOT NULL,
+    sensor_checksum_errors BIGINT NOT NULL,
+    sensor_invalid_errors BIGINT NOT NULL,
+    last_rtc_sync TIMESTAMP
+);
+
+CREATE INDEX t_device_health_capteur ON t_device_health (capteur, received_at);
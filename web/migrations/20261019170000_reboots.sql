@@ -0,0 +1,7 @@
+CREATE TABLE t_reboots (
+    received_at TIMESTAMP NOT NULL,
+    capteur VARCHAR NOT NULL,
+    reason VARCHAR NOT NULL,
+    task VARCHAR,
+    uptime_seconds BIGINT
+)
@@ -0,0 +1,11 @@
+CREATE TABLE t_crashes (
+    received_at TIMESTAMP NOT NULL,
+    capteur VARCHAR NOT NULL,
+    firmware_version VARCHAR NOT NULL,
+    uptime_seconds BIGINT NOT NULL,
+    file VARCHAR NOT NULL,
+    line INTEGER NOT NULL,
+    message VARCHAR NOT NULL
+);
+
+CREATE INDEX t_crashes_capteur ON t_crashes (capteur, received_at);
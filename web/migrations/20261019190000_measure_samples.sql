@@ -0,0 +1,3 @@
+-- Number of valid sensor samples a measure is the median of, NULL when the
+-- capteur does not report it.
+ALTER TABLE t_measures ADD COLUMN samples SMALLINT;
@@ -0,0 +1,11 @@
+-- Statistics of the readings a measure sums up, NULL when the capteur sent
+-- every reading.
+ALTER TABLE t_measures
+    ADD COLUMN aggregate_count INTEGER,
+    ADD COLUMN aggregate_duration_seconds BIGINT,
+    ADD COLUMN mean_temperature REAL,
+    ADD COLUMN min_temperature REAL,
+    ADD COLUMN max_temperature REAL,
+    ADD COLUMN mean_humidity REAL,
+    ADD COLUMN min_humidity REAL,
+    ADD COLUMN max_humidity REAL;
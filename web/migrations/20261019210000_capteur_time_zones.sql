@@ -0,0 +1,2 @@
+ALTER TABLE t_capteurs
+    ADD COLUMN time_zone VARCHAR;
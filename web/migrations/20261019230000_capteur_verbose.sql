@@ -0,0 +1 @@
+ALTER TABLE t_capteurs ADD COLUMN verbose_logs BOOLEAN NOT NULL DEFAULT false;
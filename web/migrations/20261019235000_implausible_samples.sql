@@ -0,0 +1 @@
+ALTER TABLE t_device_health ADD COLUMN sensor_implausible_samples BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,755 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Postgres};
+
+use crate::AppState;
+
+/// Fewest measure pairs needed to derive a calibration from a co-location
+/// period.
+const MIN_CALIBRATION_PAIRS: usize = 10;
+/// Largest time between a measure and the reference measure it is compared
+/// to, unless set in the request.
+const DEFAULT_MAX_PAIRING_GAP_SECONDS: i64 = 60;
+/// Largest pairing gap accepted in a request, beyond which the measures
+/// compared are no longer taken in the same conditions.
+const MAX_PAIRING_GAP_SECONDS: i64 = 3600;
+/// Most measures read at once, longer periods being refused rather than
+/// truncated.
+const MAX_MEASURES: usize = 10_000;
+
+fn default_gain() -> f64 {
+    1.
+}
+
+/// Linear correction of the measures of a capteur, `gain * raw + offset`,
+/// applied to the measures taken in `valid_from..valid_until`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Calibration {
+    pub valid_from: DateTime<Utc>,
+    /// `None` while the calibration is the current one.
+    #[serde(default)]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub temperature_offset: f64,
+    #[serde(default = "default_gain")]
+    pub temperature_gain: f64,
+    #[serde(default)]
+    pub humidity_offset: f64,
+    #[serde(default = "default_gain")]
+    pub humidity_gain: f64,
+}
+
+type CalibrationRow = (NaiveDateTime, Option<NaiveDateTime>, f64, f64, f64, f64);
+
+impl From<CalibrationRow> for Calibration {
+    fn from(
+        (
+            valid_from,
+            valid_until,
+            temperature_offset,
+            temperature_gain,
+            humidity_offset,
+            humidity_gain,
+        ): CalibrationRow,
+    ) -> Self {
+        Self {
+            valid_from: valid_from.and_utc(),
+            valid_until: valid_until.map(|valid_until| valid_until.and_utc()),
+            temperature_offset,
+            temperature_gain,
+            humidity_offset,
+            humidity_gain,
+        }
+    }
+}
+
+impl Calibration {
+    fn is_valid(&self) -> bool {
+        let gain_is_valid = |gain: f64| gain.is_finite() && gain > 0.;
+        gain_is_valid(self.temperature_gain)
+            && gain_is_valid(self.humidity_gain)
+            && self.temperature_offset.is_finite()
+            && self.humidity_offset.is_finite()
+            && self
+                .valid_until
+                .is_none_or(|valid_until| valid_until > self.valid_from)
+    }
+
+    fn applies_at(&self, timestamp: DateTime<Utc>) -> bool {
+        self.valid_from <= timestamp
+            && self
+                .valid_until
+                .is_none_or(|valid_until| timestamp < valid_until)
+    }
+
+    fn temperature(&self, raw: f64) -> f64 {
+        self.temperature_gain * raw + self.temperature_offset
+    }
+
+    fn humidity(&self, raw: f64) -> f64 {
+        (self.humidity_gain * raw + self.humidity_offset).clamp(0., 100.)
+    }
+}
+
+/// Calibration applying at `timestamp`, the most recent one when several
+/// overlap.
+fn calibration_at(calibrations: &[Calibration], timestamp: DateTime<Utc>) -> Option<&Calibration> {
+    calibrations
+        .iter()
+        .filter(|calibration| calibration.applies_at(timestamp))
+        .max_by_key(|calibration| calibration.valid_from)
+}
+
+async fn fetch_calibrations(
+    db_pool: &Pool<Postgres>,
+    capteur_id: &str,
+) -> Result<Vec<Calibration>, sqlx::Error> {
+    sqlx::query_as::<_, CalibrationRow>(
+        "SELECT valid_from, valid_until, temperature_offset, temperature_gain, humidity_offset, humidity_gain FROM t_calibrations WHERE capteur = $1 ORDER BY valid_from",
+    )
+    .bind(capteur_id)
+    .fetch_all(db_pool)
+    .await
+    .map(|rows| rows.into_iter().map(Calibration::from).collect())
+}
+
+async fn store_calibration(
+    db_pool: &Pool<Postgres>,
+    capteur_id: &str,
+    calibration: &Calibration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO t_calibrations (capteur, valid_from, valid_until, temperature_offset, temperature_gain, humidity_offset, humidity_gain) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (capteur, valid_from) DO UPDATE SET valid_until = $3, temperature_offset = $4, temperature_gain = $5, humidity_offset = $6, humidity_gain = $7",
+    )
+    .bind(capteur_id)
+    .bind(calibration.valid_from.naive_utc())
+    .bind(calibration.valid_until.map(|valid_until| valid_until.naive_utc()))
+    .bind(calibration.temperature_offset)
+    .bind(calibration.temperature_gain)
+    .bind(calibration.humidity_offset)
+    .bind(calibration.humidity_gain)
+    .execute(db_pool)
+    .await
+    .map(|_| ())
+}
+
+/// Calibrations of a capteur, oldest first.
+pub async fn get_calibrations(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<Calibration>>, StatusCode> {
+    fetch_calibrations(&state.db_pool, &capteur_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Add a calibration, replacing the one starting at the same time if any.
+pub async fn add_calibration(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Json(calibration): Json<Calibration>,
+) -> StatusCode {
+    if !calibration.is_valid() {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    match store_calibration(&state.db_pool, &capteur_id, &calibration).await {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Period {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+/// Statistics of the readings a measure sums up, corrected like the measure.
+/// The calibrations being increasing functions, the minimum and maximum stay
+/// exact, as does the mean unless the humidity was brought within 0..=100.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CalibratedAggregate {
+    count: i32,
+    duration: i64,
+    mean_temperature: f64,
+    min_temperature: f64,
+    max_temperature: f64,
+    mean_humidity: f64,
+    min_humidity: f64,
+    max_humidity: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CalibratedMeasure {
+    timestamp: DateTime<Utc>,
+    temperature: f64,
+    humidity: f64,
+    raw_temperature: f64,
+    raw_humidity: f64,
+    /// `None` when the capteur sent every reading.
+    aggregate: Option<CalibratedAggregate>,
+}
+
+type MeasureRow = (NaiveDateTime, f64, f64);
+
+/// A measure with the statistics of the readings it sums up, all NULL when
+/// the capteur sent every reading.
+#[derive(FromRow)]
+struct AggregateMeasureRow {
+    timestamp: NaiveDateTime,
+    temperature: f64,
+    humidity: f64,
+    aggregate_count: Option<i32>,
+    aggregate_duration_seconds: Option<i64>,
+    mean_temperature: Option<f32>,
+    min_temperature: Option<f32>,
+    max_temperature: Option<f32>,
+    mean_humidity: Option<f32>,
+    min_humidity: Option<f32>,
+    max_humidity: Option<f32>,
+}
+
+/// The measures read, 422 when there are more than `MAX_MEASURES`, 500 when
+/// they could not be read.
+fn within_limit<T>(measures: Result<Vec<T>, sqlx::Error>) -> Result<Vec<T>, StatusCode> {
+    match measures {
+        Ok(measures) if measures.len() > MAX_MEASURES => Err(StatusCode::UNPROCESSABLE_ENTITY),
+        Ok(measures) => Ok(measures),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+async fn fetch_measures(
+    db_pool: &Pool<Postgres>,
+    capteur_id: &str,
+    period: &Period,
+) -> Result<Vec<MeasureRow>, StatusCode> {
+    within_limit(
+        sqlx::query_as::<_, MeasureRow>(
+            "SELECT timestamp, temperature, humidity FROM t_measures WHERE capteur = $1 AND timestamp >= $2 AND timestamp < $3 ORDER BY timestamp LIMIT $4",
+        )
+        .bind(capteur_id)
+        .bind(period.from.naive_utc())
+        .bind(period.to.naive_utc())
+        .bind(MAX_MEASURES as i64 + 1)
+        .fetch_all(db_pool)
+        .await,
+    )
+}
+
+async fn fetch_aggregate_measures(
+    db_pool: &Pool<Postgres>,
+    capteur_id: &str,
+    period: &Period,
+) -> Result<Vec<AggregateMeasureRow>, StatusCode> {
+    within_limit(
+        sqlx::query_as::<_, AggregateMeasureRow>(
+            "SELECT timestamp, temperature, humidity, aggregate_count, aggregate_duration_seconds, mean_temperature, min_temperature, max_temperature, mean_humidity, min_humidity, max_humidity
+            FROM t_measures WHERE capteur = $1 AND timestamp >= $2 AND timestamp < $3 ORDER BY timestamp LIMIT $4",
+        )
+        .bind(capteur_id)
+        .bind(period.from.naive_utc())
+        .bind(period.to.naive_utc())
+        .bind(MAX_MEASURES as i64 + 1)
+        .fetch_all(db_pool)
+        .await,
+    )
+}
+
+impl AggregateMeasureRow {
+    fn calibrated_aggregate(
+        &self,
+        calibration: Option<&Calibration>,
+    ) -> Option<CalibratedAggregate> {
+        let temperature = |raw: Option<f32>| {
+            raw.map(|raw| calibration.map_or(raw.into(), |c| c.temperature(raw.into())))
+        };
+        let humidity = |raw: Option<f32>| {
+            raw.map(|raw| calibration.map_or(raw.into(), |c| c.humidity(raw.into())))
+        };
+        Some(CalibratedAggregate {
+            count: self.aggregate_count?,
+            duration: self.aggregate_duration_seconds?,
+            mean_temperature: temperature(self.mean_temperature)?,
+            min_temperature: temperature(self.min_temperature)?,
+            max_temperature: temperature(self.max_temperature)?,
+            mean_humidity: humidity(self.mean_humidity)?,
+            min_humidity: humidity(self.min_humidity)?,
+            max_humidity: humidity(self.max_humidity)?,
+        })
+    }
+}
+
+fn calibrate(
+    calibrations: &[Calibration],
+    measures: Vec<AggregateMeasureRow>,
+) -> Vec<CalibratedMeasure> {
+    measures
+        .into_iter()
+        .map(|measure| {
+            let timestamp = measure.timestamp.and_utc();
+            let calibration = calibration_at(calibrations, timestamp);
+            let (temperature, humidity) = match calibration {
+                Some(calibration) => (
+                    calibration.temperature(measure.temperature),
+                    calibration.humidity(measure.humidity),
+                ),
+                None => (measure.temperature, measure.humidity),
+            };
+            CalibratedMeasure {
+                timestamp,
+                temperature,
+                humidity,
+                raw_temperature: measure.temperature,
+                raw_humidity: measure.humidity,
+                aggregate: measure.calibrated_aggregate(calibration),
+            }
+        })
+        .collect()
+}
+
+/// Measures of a capteur taken in `from..to`, with the statistics of the
+/// readings they sum up, corrected by the calibration applying when each was
+/// taken. The stored measures stay raw. Periods with more than
+/// `MAX_MEASURES` measures are refused with 422.
+pub async fn get_measures(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Query(period): Query<Period>,
+) -> Result<Json<Vec<CalibratedMeasure>>, StatusCode> {
+    let calibrations = fetch_calibrations(&state.db_pool, &capteur_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let measures = fetch_aggregate_measures(&state.db_pool, &capteur_id, &period).await?;
+
+    Ok(Json(calibrate(&calibrations, measures)))
+}
+
+#[derive(Deserialize)]
+pub struct CoLocation {
+    /// Capteur taken as reference, its raw measures are trusted.
+    reference: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    max_gap_seconds: Option<i64>,
+}
+
+/// Pair each measure with the closest reference measure taken at most
+/// `max_gap` apart. Both lists are sorted by timestamp.
+fn pair_measures<'a>(
+    measures: &'a [MeasureRow],
+    references: &'a [MeasureRow],
+    max_gap: TimeDelta,
+) -> Vec<(&'a MeasureRow, &'a MeasureRow)> {
+    let mut pairs = Vec::new();
+    let mut next = 0;
+    for measure in measures {
+        while next + 1 < references.len() && references[next + 1].0 <= measure.0 {
+            next += 1;
+        }
+        let closest = references[next..]
+            .iter()
+            .take(2)
+            .min_by_key(|reference| (reference.0 - measure.0).abs());
+        if let Some(reference) =
+            closest.filter(|reference| (reference.0 - measure.0).abs() <= max_gap)
+        {
+            pairs.push((measure, reference));
+        }
+    }
+    pairs
+}
+
+/// Offsets bringing the measures to the reference ones on average, `None`
+/// when there are too few pairs.
+fn derive_offsets(pairs: &[(&MeasureRow, &MeasureRow)]) -> Option<(f64, f64)> {
+    if pairs.len() < MIN_CALIBRATION_PAIRS {
+        return None;
+    }
+    let count = pairs.len() as f64;
+    let temperature_offset = pairs
+        .iter()
+        .map(|(measure, reference)| reference.1 - measure.1)
+        .sum::<f64>()
+        / count;
+    let humidity_offset = pairs
+        .iter()
+        .map(|(measure, reference)| reference.2 - measure.2)
+        .sum::<f64>()
+        / count;
+    Some((temperature_offset, humidity_offset))
+}
+
+/// Derive the offsets of a capteur from a period spent next to a reference
+/// capteur, and store them as a calibration starting with that period.
+pub async fn derive_calibration(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Json(co_location): Json<CoLocation>,
+) -> Result<(StatusCode, Json<Calibration>), StatusCode> {
+    if co_location.to <= co_location.from || co_location.reference == capteur_id {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let max_gap = Some(
+        co_location
+            .max_gap_seconds
+            .unwrap_or(DEFAULT_MAX_PAIRING_GAP_SECONDS),
+    )
+    .filter(|seconds| (1..=MAX_PAIRING_GAP_SECONDS).contains(seconds))
+    .and_then(TimeDelta::try_seconds)
+    .ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+    let period = Period {
+        from: co_location.from,
+        to: co_location.to,
+    };
+    let measures = fetch_measures(&state.db_pool, &capteur_id, &period).await?;
+    let references = fetch_measures(&state.db_pool, &co_location.reference, &period).await?;
+
+    let pairs = pair_measures(&measures, &references, max_gap);
+    let Some((temperature_offset, humidity_offset)) = derive_offsets(&pairs) else {
+        println!(
+            "{}: only {} measures to compare with {}, not calibrating",
+            capteur_id,
+            pairs.len(),
+            co_location.reference
+        );
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    };
+
+    let calibration = Calibration {
+        valid_from: co_location.from,
+        valid_until: None,
+        temperature_offset,
+        temperature_gain: 1.,
+        humidity_offset,
+        humidity_gain: 1.,
+    };
+    println!(
+        "{}: calibrated against {} over {} measures, T {:+.2}, humidity {:+.2}",
+        capteur_id,
+        co_location.reference,
+        pairs.len(),
+        temperature_offset,
+        humidity_offset
+    );
+    store_calibration(&state.db_pool, &capteur_id, &calibration)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    Ok((StatusCode::CREATED, Json(calibration)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::{get, post},
+        Router,
+    };
+    use chrono::{NaiveDateTime, TimeDelta, TimeZone, Utc};
+    use http_body_util::BodyExt;
+    use sqlx::{Pool, Postgres};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn build_test_app() -> (Router, Pool<Postgres>) {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool: db_pool.clone(),
+            measure_config: MeasureConfig::default(),
+        });
+        let app = Router::new()
+            .route(
+                "/capteurs/{id}/calibrations",
+                get(get_calibrations).post(add_calibration),
+            )
+            .route(
+                "/capteurs/{id}/calibrations/derive",
+                post(derive_calibration),
+            )
+            .route("/capteurs/{id}/measures", get(get_measures))
+            .with_state(app_state);
+        (app, db_pool)
+    }
+
+    fn at(minutes: i64) -> NaiveDateTime {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0)
+            .unwrap()
+            .naive_utc()
+            + TimeDelta::minutes(minutes)
+    }
+
+    async fn insert_measures(db_pool: &Pool<Postgres>, capteur: &str, measures: &[MeasureRow]) {
+        sqlx::query("DELETE FROM t_measures WHERE capteur = $1")
+            .bind(capteur)
+            .execute(db_pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM t_calibrations WHERE capteur = $1")
+            .bind(capteur)
+            .execute(db_pool)
+            .await
+            .unwrap();
+        for (timestamp, temperature, humidity) in measures {
+            sqlx::query(
+                "INSERT INTO t_measures (timestamp, capteur, temperature, humidity) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(timestamp)
+            .bind(capteur)
+            .bind(temperature)
+            .bind(humidity)
+            .execute(db_pool)
+            .await
+            .unwrap();
+        }
+    }
+
+    fn json_request(method: http::Method, uri: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn fetch_json(app: Router, uri: &str) -> serde_json::Value {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_calibration_applied_at_query_time() {
+        let (app, db_pool) = build_test_app().await;
+        insert_measures(
+            &db_pool,
+            "test_calibration",
+            &[(at(0), 20., 50.), (at(60), 20., 99.)],
+        )
+        .await;
+        sqlx::query(
+            "UPDATE t_measures SET aggregate_count = 12, aggregate_duration_seconds = 55, mean_temperature = 19.5, min_temperature = 19, max_temperature = 20, mean_humidity = 90, min_humidity = 80, max_humidity = 99
+            WHERE capteur = 'test_calibration' AND timestamp = $1",
+        )
+        .bind(at(60))
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                http::Method::POST,
+                "/capteurs/test_calibration/calibrations",
+                r#"{"valid_from": "2024-03-01T12:30:00Z", "temperature_offset": -1.5, "temperature_gain": 1.1, "humidity_offset": 5}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let measures = fetch_json(
+            app,
+            "/capteurs/test_calibration/measures?from=2024-03-01T00:00:00Z&to=2024-03-02T00:00:00Z",
+        )
+        .await;
+        assert_eq!(measures[0]["temperature"], 20.);
+        assert_eq!(measures[0]["humidity"], 50.);
+        assert!((measures[1]["temperature"].as_f64().unwrap() - 20.5).abs() < 1e-9);
+        assert_eq!(measures[1]["humidity"], 100.);
+        assert_eq!(measures[1]["raw_temperature"], 20.);
+        assert_eq!(measures[1]["raw_humidity"], 99.);
+        assert_eq!(measures[0]["aggregate"], serde_json::Value::Null);
+        let aggregate = &measures[1]["aggregate"];
+        assert_eq!(aggregate["count"], 12);
+        assert!((aggregate["mean_temperature"].as_f64().unwrap() - 19.95).abs() < 1e-9);
+        assert!((aggregate["min_temperature"].as_f64().unwrap() - 19.4).abs() < 1e-9);
+        assert_eq!(aggregate["min_humidity"], 85.);
+        assert_eq!(aggregate["max_humidity"], 100.);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_measures() {
+        let (app, db_pool) = build_test_app().await;
+        insert_measures(&db_pool, "test_calibration_many", &[]).await;
+        sqlx::query(
+            "INSERT INTO t_measures (timestamp, capteur, temperature, humidity)
+            SELECT $1 + make_interval(secs => i), 'test_calibration_many', 20, 50 FROM generate_series(1, $2) AS i",
+        )
+        .bind(at(0))
+        .bind(MAX_MEASURES as i32 + 1)
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_calibration_many/measures?from=2024-03-01T00:00:00Z&to=2024-03-02T00:00:00Z")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_calibration() {
+        let (app, _) = build_test_app().await;
+
+        let response = app
+            .oneshot(json_request(
+                http::Method::POST,
+                "/capteurs/test_calibration_invalid/calibrations",
+                r#"{"valid_from": "2024-03-01T12:30:00Z", "temperature_gain": 0}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_derive_calibration() {
+        let (app, db_pool) = build_test_app().await;
+        let measures: Vec<_> = (0..12)
+            .map(|i| (at(i) + TimeDelta::seconds(10), 21. + i as f64 / 10., 45.))
+            .collect();
+        let references: Vec<_> = (0..12)
+            .map(|i| (at(i), 20. + i as f64 / 10., 50.))
+            .collect();
+        insert_measures(&db_pool, "test_calibration_derived", &measures).await;
+        insert_measures(&db_pool, "test_calibration_reference", &references).await;
+
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                http::Method::POST,
+                "/capteurs/test_calibration_derived/calibrations/derive",
+                r#"{"reference": "test_calibration_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let calibrations = fetch_json(app, "/capteurs/test_calibration_derived/calibrations").await;
+        assert_eq!(calibrations[0]["valid_from"], "2024-03-01T12:00:00Z");
+        assert!((calibrations[0]["temperature_offset"].as_f64().unwrap() + 1.).abs() < 1e-9);
+        assert!((calibrations[0]["humidity_offset"].as_f64().unwrap() - 5.).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_derive_calibration_max_gap() {
+        let (app, db_pool) = build_test_app().await;
+        let measures: Vec<_> = (0..12)
+            .map(|i| (at(5 * i) + TimeDelta::seconds(90), 21., 45.))
+            .collect();
+        let references: Vec<_> = (0..12).map(|i| (at(5 * i), 20., 50.)).collect();
+        insert_measures(&db_pool, "test_calibration_gap", &measures).await;
+        insert_measures(&db_pool, "test_calibration_gap_reference", &references).await;
+        let derive = |body| {
+            app.clone().oneshot(json_request(
+                http::Method::POST,
+                "/capteurs/test_calibration_gap/calibrations/derive",
+                body,
+            ))
+        };
+
+        for body in [
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z", "max_gap_seconds": 9223372036854775807}"#,
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z", "max_gap_seconds": 3601}"#,
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z", "max_gap_seconds": 0}"#,
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z", "max_gap_seconds": -60}"#,
+        ] {
+            let response = derive(body).await.unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{body}"
+            );
+        }
+
+        // The measures are 90s after the references, beyond the default gap
+        let response = derive(
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = derive(
+            r#"{"reference": "test_calibration_gap_reference", "from": "2024-03-01T12:00:00Z", "to": "2024-03-01T13:00:00Z", "max_gap_seconds": 120}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[test]
+    fn test_most_recent_calibration_applies() {
+        let calibration = |from: i64, until: Option<i64>, offset: f64| Calibration {
+            valid_from: at(from).and_utc(),
+            valid_until: until.map(|until| at(until).and_utc()),
+            temperature_offset: offset,
+            temperature_gain: 1.,
+            humidity_offset: 0.,
+            humidity_gain: 1.,
+        };
+        let calibrations = [calibration(0, None, 1.), calibration(10, Some(20), 2.)];
+
+        assert_eq!(calibration_at(&calibrations, at(-1).and_utc()), None);
+        assert_eq!(
+            calibration_at(&calibrations, at(5).and_utc()),
+            Some(&calibrations[0])
+        );
+        assert_eq!(
+            calibration_at(&calibrations, at(10).and_utc()),
+            Some(&calibrations[1])
+        );
+        assert_eq!(
+            calibration_at(&calibrations, at(20).and_utc()),
+            Some(&calibrations[0])
+        );
+    }
+
+    #[test]
+    fn test_pair_measures() {
+        let measures = [(at(0), 0., 0.), (at(1), 0., 0.), (at(10), 0., 0.)];
+        let references = [
+            (at(0) - TimeDelta::seconds(50), 0., 0.),
+            (at(0) + TimeDelta::seconds(20), 0., 0.),
+            (at(1) + TimeDelta::seconds(30), 0., 0.),
+        ];
+
+        let pairs = pair_measures(&measures, &references, TimeDelta::seconds(60));
+
+        assert_eq!(
+            pairs,
+            vec![
+                (&measures[0], &references[1]),
+                (&measures[1], &references[2])
+            ]
+        );
+        assert_eq!(derive_offsets(&pairs), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::{ConnectionEvent, ConnectionEventKind};
+use serde::Serialize;
+
+use crate::{payload::Payload, AppState};
+
+fn kind_name(kind: ConnectionEventKind) -> &'static str {
+    match kind {
+        ConnectionEventKind::Connected => "connected",
+        ConnectionEventKind::Disconnected => "disconnected",
+        ConnectionEventKind::JoinFailed => "join_failed",
+    }
+}
+
+pub async fn log_event(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<ConnectionEvent>,
+) -> StatusCode {
+    let kind = kind_name(payload.kind);
+    println!(
+        "{} ({}): {} {} after {}s uptime",
+        Utc::now(),
+        capteur_id,
+        kind,
+        payload.ssid,
+        payload.uptime
+    );
+    match sqlx::query(
+        "INSERT INTO t_connection_events (received_at, capteur, kind, ssid, uptime_seconds, downtime_seconds) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(capteur_id)
+    .bind(kind)
+    .bind(payload.ssid.as_str())
+    .bind(payload.uptime as i64)
+    .bind(payload.downtime as i64)
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Serialize)]
+pub struct EventReport {
+    received_at: DateTime<Utc>,
+    kind: String,
+    ssid: String,
+    uptime_seconds: i64,
+    downtime_seconds: i64,
+}
+
+impl From<(NaiveDateTime, String, String, i64, i64)> for EventReport {
+    fn from(
+        (received_at, kind, ssid, uptime_seconds, downtime_seconds): (
+            NaiveDateTime,
+            String,
+            String,
+            i64,
+            i64,
+        ),
+    ) -> Self {
+        Self {
+            received_at: received_at.and_utc(),
+            kind,
+            ssid,
+            uptime_seconds,
+            downtime_seconds,
+        }
+    }
+}
+
+/// Most recent connection events reported by a capteur.
+pub async fn get_events(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<EventReport>>, StatusCode> {
+    sqlx::query_as::<_, (NaiveDateTime, String, String, i64, i64)>(
+        "SELECT received_at, kind, ssid, uptime_seconds, downtime_seconds FROM t_connection_events WHERE capteur = $1 ORDER BY received_at DESC, uptime_seconds DESC LIMIT 100",
+    )
+    .bind(capteur_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(EventReport::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{get_events, log_event};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/{id}/events", get(get_events).post(log_event))
+            .with_state(app_state)
+    }
+
+    #[tokio::test]
+    async fn test_log_and_get_events() {
+        let app = build_test_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/capteurs/test_events/events")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"kind": "connected", "ssid": "maison", "uptime": 3630, "downtime": 30}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_events/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events[0]["kind"], "connected");
+        assert_eq!(events[0]["ssid"], "maison");
+        assert_eq!(events[0]["uptime_seconds"], 3630);
+        assert_eq!(events[0]["downtime_seconds"], 30);
+    }
+}
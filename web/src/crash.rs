@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::CrashReport;
+use serde::Serialize;
+
+use crate::{payload::Payload, AppState};
+
+pub async fn log_crash(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<CrashReport>,
+) -> StatusCode {
+    println!(
+        "{} ({}): panicked at {}:{} running {}: {}",
+        Utc::now(),
+        capteur_id,
+        payload.file,
+        payload.line,
+        payload.firmware_version,
+        payload.message
+    );
+    match sqlx::query(
+        "INSERT INTO t_crashes (received_at, capteur, firmware_version, uptime_seconds, file, line, message) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(capteur_id)
+    .bind(payload.firmware_version.to_string())
+    .bind(payload.uptime as i64)
+    .bind(payload.file.as_str())
+    .bind(payload.line as i32)
+    .bind(payload.message.as_str())
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Serialize)]
+pub struct CrashEntry {
+    received_at: DateTime<Utc>,
+    firmware_version: String,
+    uptime_seconds: i64,
+    file: String,
+    line: i32,
+    message: String,
+}
+
+type CrashRow = (NaiveDateTime, String, i64, String, i32, String);
+
+impl From<CrashRow> for CrashEntry {
+    fn from(
+        (received_at, firmware_version, uptime_seconds, file, line, message): CrashRow,
+    ) -> Self {
+        Self {
+            received_at: received_at.and_utc(),
+            firmware_version,
+            uptime_seconds,
+            file,
+            line,
+            message,
+        }
+    }
+}
+
+/// Most recent panics of a capteur.
+pub async fn get_crashes(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<CrashEntry>>, StatusCode> {
+    sqlx::query_as::<_, CrashRow>(
+        "SELECT received_at, firmware_version, uptime_seconds, file, line, message FROM t_crashes WHERE capteur = $1 ORDER BY received_at DESC LIMIT 100",
+    )
+    .bind(capteur_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(CrashEntry::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{get_crashes, log_crash};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/{id}/crash", get(get_crashes).post(log_crash))
+            .with_state(app_state)
+    }
+
+    #[tokio::test]
+    async fn test_log_and_get_crashes() {
+        let app = build_test_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/capteurs/test_crashes/crash")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"firmware_version": "0.2.0", "uptime": 3600, "file": "src/web.rs", "line": 42, "message": "called `Option::unwrap()` on a `None` value"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_crashes/crash")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let crashes: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(crashes[0]["firmware_version"], "0.2.0");
+        assert_eq!(crashes[0]["file"], "src/web.rs");
+        assert_eq!(crashes[0]["line"], 42);
+        assert_eq!(
+            crashes[0]["message"],
+            "called `Option::unwrap()` on a `None` value"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::ClockDrift;
+use serde::Serialize;
+
+use crate::{measure::to_datetime, payload::Payload, AppState};
+
+pub async fn log_drift(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<ClockDrift>,
+) -> StatusCode {
+    let Some(timestamp) = to_datetime(payload.timestamp) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    println!(
+        "{} ({}): RTC drifted by {}s in {}s",
+        timestamp, capteur_id, payload.drift, payload.elapsed
+    );
+    match sqlx::query(
+        "INSERT INTO t_clock_drifts (timestamp, capteur, drift_seconds, elapsed_seconds) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(timestamp)
+    .bind(capteur_id)
+    .bind(payload.drift)
+    .bind(payload.elapsed as i64)
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Serialize)]
+pub struct DriftReport {
+    timestamp: DateTime<Utc>,
+    drift_seconds: i32,
+    elapsed_seconds: i64,
+    /// Drift rate, in parts per million.
+    ppm: f64,
+}
+
+impl From<(NaiveDateTime, i32, i64)> for DriftReport {
+    fn from((timestamp, drift_seconds, elapsed_seconds): (NaiveDateTime, i32, i64)) -> Self {
+        let ppm = match elapsed_seconds {
+            0 => 0.,
+            elapsed => drift_seconds as f64 / elapsed as f64 * 1e6,
+        };
+        Self {
+            timestamp: timestamp.and_utc(),
+            drift_seconds,
+            elapsed_seconds,
+            ppm,
+        }
+    }
+}
+
+/// Most recent RTC drifts reported by a capteur.
+pub async fn get_drifts(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<DriftReport>>, StatusCode> {
+    sqlx::query_as::<_, (NaiveDateTime, i32, i64)>(
+        "SELECT timestamp, drift_seconds, elapsed_seconds FROM t_clock_drifts WHERE capteur = $1 ORDER BY timestamp DESC LIMIT 100",
+    )
+    .bind(capteur_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(DriftReport::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{get_drifts, log_drift};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/{id}/drift", get(get_drifts).post(log_drift))
+            .with_state(app_state)
+    }
+
+    #[tokio::test]
+    async fn test_log_and_get_drift() {
+        let app = build_test_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/capteurs/test_drift/drift")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"timestamp": "2999-01-22T18:07:55Z", "drift": 2, "elapsed": 21600}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_drift/drift")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let reports: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reports[0]["timestamp"], "2999-01-22T18:07:55Z");
+        assert_eq!(reports[0]["drift_seconds"], 2);
+        assert_eq!(reports[0]["elapsed_seconds"], 21600);
+        assert!((reports[0]["ppm"].as_f64().unwrap() - 92.59).abs() < 0.01);
+    }
+}
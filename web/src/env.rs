@@ -1,6 +1,8 @@
 use std::env;
 use std::fs;
 
+use chrono::TimeDelta;
+
 pub struct DatabaseConfig {
     pub host: String,
     pub port: String,
@@ -45,3 +47,69 @@ pub fn load_database_configuration() -> Option<DatabaseConfig> {
         pwd,
     })
 }
+
+pub struct MeasureConfig {
+    /// How far in the future a measure timestamp can be before it is rejected.
+    pub max_future_skew: TimeDelta,
+    /// Store the server receive time alongside the capteur timestamp, so that
+    /// measures from a capteur with a bad clock can be corrected afterwards.
+    pub store_received_at: bool,
+}
+
+impl Default for MeasureConfig {
+    fn default() -> Self {
+        Self {
+            max_future_skew: TimeDelta::minutes(5),
+            store_received_at: false,
+        }
+    }
+}
+
+pub fn load_measure_configuration() -> MeasureConfig {
+    let default = MeasureConfig::default();
+
+    let max_future_skew = match env::var("MAX_FUTURE_SKEW_SECONDS") {
+        Ok(secs) if !secs.is_empty() => TimeDelta::seconds(
+            secs.parse()
+                .expect("MAX_FUTURE_SKEW_SECONDS is not a number of seconds"),
+        ),
+        _ => default.max_future_skew,
+    };
+
+    let store_received_at = match env::var("STORE_RECEIVED_AT") {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .expect("STORE_RECEIVED_AT is neither true nor false"),
+        _ => default.store_received_at,
+    };
+
+    MeasureConfig {
+        max_future_skew,
+        store_received_at,
+    }
+}
+
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Certificate and key to serve HTTPS with, plain HTTP is served when they are
+/// not set.
+pub fn load_tls_configuration() -> Option<TlsConfig> {
+    let cert_path = env::var("TLS_CERT_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+    let key_path = env::var("TLS_KEY_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+
+    match (cert_path, key_path) {
+        (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+            cert_path,
+            key_path,
+        }),
+        (None, None) => None,
+        _ => panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+    }
+}
@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use protocol::firmware::SIGNATURE_HEX_LEN;
+use protocol::{FirmwareManifest, FirmwareReport, FirmwareVersion};
+use serde::Deserialize;
+
+use crate::{payload::Payload, AppState};
+
+/// Header carrying the signature of an uploaded image, as printed by the
+/// `sign_firmware` example of `capteur-core`.
+const SIGNATURE_HEADER: &str = "x-firmware-signature";
+const MAX_GROUP_LEN: usize = 32;
+
+fn is_valid_group(group: &str) -> bool {
+    !group.is_empty()
+        && group.len() <= MAX_GROUP_LEN
+        && group
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn is_valid_signature(signature: &str) -> bool {
+    signature.len() == SIGNATURE_HEX_LEN && signature.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn image_url(group: &str, version: FirmwareVersion) -> String {
+    format!("/firmware/{}/{}", group, version)
+}
+
+/// Upload an image for the capteurs of `group`. The image is signed offline,
+/// the server only checks the signature is well-formed.
+pub async fn put_firmware(
+    State(state): State<Arc<AppState>>,
+    Path((group, version)): Path<(String, String)>,
+    headers: HeaderMap,
+    image: Bytes,
+) -> StatusCode {
+    let Some(version) = FirmwareVersion::parse(&version) else {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
+    let Some(signature) = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|signature| is_valid_signature(signature))
+    else {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
+    if !is_valid_group(&group) || image.is_empty() || u32::try_from(image.len()).is_err() {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+
+    println!(
+        "{}: firmware {} uploaded, {} bytes",
+        group,
+        version,
+        image.len()
+    );
+    match sqlx::query(
+        "INSERT INTO t_firmwares (capteur_group, major, minor, patch, image, signature, uploaded_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (capteur_group, major, minor, patch) DO UPDATE SET image = $5, signature = $6, uploaded_at = $7",
+    )
+    .bind(&group)
+    .bind(version.major as i32)
+    .bind(version.minor as i32)
+    .bind(version.patch as i32)
+    .bind(image.as_ref())
+    .bind(signature.to_ascii_lowercase())
+    .bind(Utc::now().naive_utc())
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+/// Download an image, at the URL given by the manifest.
+pub async fn get_firmware(
+    State(state): State<Arc<AppState>>,
+    Path((group, version)): Path<(String, String)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let version = FirmwareVersion::parse(&version).ok_or(StatusCode::NOT_FOUND)?;
+    let image = sqlx::query_scalar::<_, Vec<u8>>(
+        "SELECT image FROM t_firmwares WHERE capteur_group = $1 AND major = $2 AND minor = $3 AND patch = $4",
+    )
+    .bind(group)
+    .bind(version.major as i32)
+    .bind(version.minor as i32)
+    .bind(version.patch as i32)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            mime::APPLICATION_OCTET_STREAM.as_ref(),
+        )],
+        image,
+    ))
+}
+
+/// Manifest of the latest image of the group of a capteur, capteurs not
+/// registered belonging to the `default` group.
+pub async fn get_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<FirmwareManifest>, StatusCode> {
+    let (group, major, minor, patch, size, signature) =
+        sqlx::query_as::<_, (String, i32, i32, i32, i32, String)>(
+            "SELECT f.capteur_group, f.major, f.minor, f.patch, length(f.image), f.signature FROM t_firmwares f
+            WHERE f.capteur_group = COALESCE((SELECT capteur_group FROM t_capteurs WHERE id = $1), 'default')
+            ORDER BY f.major DESC, f.minor DESC, f.patch DESC LIMIT 1",
+        )
+        .bind(capteur_id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let version = FirmwareVersion {
+        major: major as u16,
+        minor: minor as u16,
+        patch: patch as u16,
+    };
+    Ok(Json(FirmwareManifest {
+        version,
+        size: size as u32,
+        signature: signature
+            .as_str()
+            .try_into()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        url: image_url(&group, version)
+            .as_str()
+            .try_into()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    }))
+}
+
+/// Record the firmware a capteur runs, reported once it reached the server.
+pub async fn report_firmware(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<FirmwareReport>,
+) -> StatusCode {
+    println!("{}: running firmware {}", capteur_id, payload.version);
+    match sqlx::query(
+        "INSERT INTO t_capteurs (id, firmware_version, firmware_reported_at) VALUES ($1, $2, $3)
+        ON CONFLICT (id) DO UPDATE SET firmware_version = $2, firmware_reported_at = $3",
+    )
+    .bind(capteur_id)
+    .bind(payload.version.to_string())
+    .bind(Utc::now().naive_utc())
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GroupAssignment {
+    group: String,
+}
+
+/// Move a capteur to another group, to roll out a firmware to it.
+pub async fn put_group(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Json(payload): Json<GroupAssignment>,
+) -> StatusCode {
+    if !is_valid_group(&payload.group) {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    match sqlx::query(
+        "INSERT INTO t_capteurs (id, capteur_group) VALUES ($1, $2)
+        ON CONFLICT (id) DO UPDATE SET capteur_group = $2",
+    )
+    .bind(capteur_id)
+    .bind(payload.group)
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::{get, put},
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route(
+                "/firmware/{group}/{version}",
+                get(get_firmware).put(put_firmware),
+            )
+            .route(
+                "/capteurs/{id}/firmware",
+                get(get_manifest).post(report_firmware),
+            )
+            .route("/capteurs/{id}/group", put(put_group))
+            .with_state(app_state)
+    }
+
+    fn upload(uri: &str, signature: &str, image: &'static [u8]) -> Request<Body> {
+        Request::builder()
+            .method(http::Method::PUT)
+            .uri(uri)
+            .header(
+                http::header::CONTENT_TYPE,
+                mime::APPLICATION_OCTET_STREAM.as_ref(),
+            )
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::from(image))
+            .unwrap()
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Bytes) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        (
+            status,
+            response.into_body().collect().await.unwrap().to_bytes(),
+        )
+    }
+
+    fn get_request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_manifest_of_latest_group_firmware() {
+        let app = build_test_app().await;
+        let signature = "0a".repeat(64);
+
+        let group = Request::builder()
+            .method(http::Method::PUT)
+            .uri("/capteurs/test_firmware/group")
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(r#"{"group": "test-ota"}"#))
+            .unwrap();
+        assert_eq!(send(&app, group).await.0, StatusCode::NO_CONTENT);
+        for (version, image) in [
+            ("0.9.0", &b"older"[..]),
+            ("0.10.0", b"latest"),
+            ("0.9.9", b"old"),
+        ] {
+            let uri = format!("/firmware/test-ota/{version}");
+            let (status, _) = send(&app, upload(&uri, &signature, image)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, body) = send(&app, get_request("/capteurs/test_firmware/firmware")).await;
+        assert_eq!(status, StatusCode::OK);
+        let manifest: FirmwareManifest = serde_json::from_slice(&body).unwrap();
+        assert_eq!(manifest.version, FirmwareVersion::parse("0.10.0").unwrap());
+        assert_eq!(manifest.size, 6);
+        assert_eq!(manifest.signature, signature.as_str());
+        assert_eq!(manifest.url, "/firmware/test-ota/0.10.0");
+
+        let (status, image) = send(&app, get_request(&manifest.url)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(image.as_ref(), b"latest");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_uploads() {
+        let app = build_test_app().await;
+        let signature = "0a".repeat(64);
+
+        for (uri, signature) in [
+            ("/firmware/test-ota/1.0", signature.as_str()),
+            ("/firmware/test-ota/1.0.0", &signature[..100]),
+            ("/firmware/test-ota/1.0.0", &signature.replace('a', "z")),
+            ("/firmware/test%20ota/1.0.0", signature.as_str()),
+        ] {
+            let (status, _) = send(&app, upload(uri, signature, b"image")).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_firmware() {
+        let app = build_test_app().await;
+
+        let report = Request::builder()
+            .method(http::Method::POST)
+            .uri("/capteurs/test_firmware_report/firmware")
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(r#"{"version": "0.2.0"}"#))
+            .unwrap();
+
+        assert_eq!(send(&app, report).await.0, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_no_firmware_for_group() {
+        let app = build_test_app().await;
+
+        let group = Request::builder()
+            .method(http::Method::PUT)
+            .uri("/capteurs/test_firmware_none/group")
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(r#"{"group": "test-empty"}"#))
+            .unwrap();
+        assert_eq!(send(&app, group).await.0, StatusCode::NO_CONTENT);
+
+        let (status, _) = send(&app, get_request("/capteurs/test_firmware_none/firmware")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
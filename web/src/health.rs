@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::Heartbeat;
+use serde::Serialize;
+use sqlx::FromRow;
+
+use crate::{measure::to_datetime, payload::Payload, AppState};
+
+pub async fn log_heartbeat(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<Heartbeat>,
+) -> StatusCode {
+    let Ok(last_rtc_sync) = payload
+        .last_rtc_sync
+        .map(|sync| to_datetime(sync).ok_or(()))
+        .transpose()
+    else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let errors = payload.sensor_errors;
+    println!(
+        "{} ({}): up {}s, firmware {}, RSSI {:?}, sensor errors {}/{}/{}, {} implausible samples",
+        Utc::now(),
+        capteur_id,
+        payload.uptime,
+        payload.firmware_version,
+        payload.rssi,
+        errors.timeout,
+        errors.checksum,
+        errors.invalid,
+        errors.implausible
+    );
+    match sqlx::query(
+        "INSERT INTO t_device_health (received_at, capteur, uptime_seconds, firmware_version, rssi, free_buffer_slots,
+            sensor_timeout_errors, sensor_checksum_errors, sensor_invalid_errors, sensor_implausible_samples, last_rtc_sync)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(capteur_id)
+    .bind(payload.uptime as i64)
+    .bind(payload.firmware_version.to_string())
+    .bind(payload.rssi)
+    .bind(payload.free_buffer_slots.map(i32::from))
+    .bind(errors.timeout as i64)
+    .bind(errors.checksum as i64)
+    .bind(errors.invalid as i64)
+    .bind(errors.implausible as i64)
+    .bind(last_rtc_sync.map(|sync| sync.naive_utc()))
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(FromRow)]
+struct HealthRow {
+    received_at: NaiveDateTime,
+    uptime_seconds: i64,
+    firmware_version: String,
+    rssi: Option<i16>,
+    free_buffer_slots: Option<i32>,
+    sensor_timeout_errors: i64,
+    sensor_checksum_errors: i64,
+    sensor_invalid_errors: i64,
+    sensor_implausible_samples: i64,
+    last_rtc_sync: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct SensorErrorReport {
+    timeout: i64,
+    checksum: i64,
+    invalid: i64,
+    implausible: i64,
+}
+
+#[derive(Serialize)]
+pub struct HealthReport {
+    received_at: DateTime<Utc>,
+    uptime_seconds: i64,
+    firmware_version: String,
+    rssi: Option<i16>,
+    free_buffer_slots: Option<i32>,
+    sensor_errors: SensorErrorReport,
+    last_rtc_sync: Option<DateTime<Utc>>,
+}
+
+impl From<HealthRow> for HealthReport {
+    fn from(row: HealthRow) -> Self {
+        Self {
+            received_at: row.received_at.and_utc(),
+            uptime_seconds: row.uptime_seconds,
+            firmware_version: row.firmware_version,
+            rssi: row.rssi,
+            free_buffer_slots: row.free_buffer_slots,
+            sensor_errors: SensorErrorReport {
+                timeout: row.sensor_timeout_errors,
+                checksum: row.sensor_checksum_errors,
+                invalid: row.sensor_invalid_errors,
+                implausible: row.sensor_implausible_samples,
+            },
+            last_rtc_sync: row.last_rtc_sync.map(|sync| sync.and_utc()),
+        }
+    }
+}
+
+/// Most recent heartbeats of a capteur.
+pub async fn get_health(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<HealthReport>>, StatusCode> {
+    sqlx::query_as::<_, HealthRow>(
+        "SELECT received_at, uptime_seconds, firmware_version, rssi, free_buffer_slots,
+            sensor_timeout_errors, sensor_checksum_errors, sensor_invalid_errors, sensor_implausible_samples, last_rtc_sync
+        FROM t_device_health WHERE capteur = $1 ORDER BY received_at DESC, uptime_seconds DESC LIMIT 100",
+    )
+    .bind(capteur_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(HealthReport::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::{get, post},
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{get_health, log_heartbeat};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/{id}/health", get(get_health))
+            .route("/capteurs/{id}/heartbeat", post(log_heartbeat))
+            .with_state(app_state)
+    }
+
+    fn heartbeat(capteur: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method(http::Method::POST)
+            .uri(format!("/capteurs/{capteur}/heartbeat"))
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_log_and_get_health() {
+        let app = build_test_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(heartbeat(
+                "test_health",
+                r#"{"uptime": 7200, "firmware_version": "0.2.0", "rssi": -67, "free_buffer_slots": 280,
+                "sensor_errors": {"timeout": 4, "checksum": 1, "invalid": 0, "implausible": 3}, "last_rtc_sync": "2025-01-22T18:07:55Z"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_health/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let health: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health[0]["uptime_seconds"], 7200);
+        assert_eq!(health[0]["firmware_version"], "0.2.0");
+        assert_eq!(health[0]["rssi"], -67);
+        assert_eq!(health[0]["free_buffer_slots"], 280);
+        assert_eq!(health[0]["sensor_errors"]["timeout"], 4);
+        assert_eq!(health[0]["sensor_errors"]["checksum"], 1);
+        assert_eq!(health[0]["sensor_errors"]["implausible"], 3);
+        assert_eq!(health[0]["last_rtc_sync"], "2025-01-22T18:07:55Z");
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_before_rtc_sync() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(heartbeat(
+                "test_health_unsynced",
+                r#"{"uptime": 30, "firmware_version": "0.2.0", "rssi": null, "free_buffer_slots": null,
+                "sensor_errors": {"timeout": 0, "checksum": 0, "invalid": 0}, "last_rtc_sync": null}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
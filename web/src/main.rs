@@ -1,18 +1,32 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
-use env::load_database_configuration;
+use axum_server::tls_rustls::RustlsConfig;
+use env::{
+    load_database_configuration, load_measure_configuration, load_tls_configuration, MeasureConfig,
+};
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 
+mod calibration;
+mod connection;
+mod crash;
+mod drift;
 mod env;
+mod firmware;
+mod health;
 mod measure;
+mod payload;
+mod reboot;
 mod rtc;
+mod settings;
 
 struct AppState {
     db_pool: Pool<Postgres>,
+    measure_config: MeasureConfig,
 }
 
 #[tokio::main]
@@ -22,9 +36,27 @@ async fn main() -> Result<(), sqlx::Error> {
     println!("Starting application");
 
     // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     let app = build_app().await;
-    axum::serve(listener, app).await.unwrap();
+    match load_tls_configuration() {
+        Some(tls_config) => {
+            rustls::crypto::ring::default_provider()
+                .install_default()
+                .expect("Unable to install the rustls crypto provider");
+            let rustls_config =
+                RustlsConfig::from_pem_file(tls_config.cert_path, tls_config.key_path)
+                    .await
+                    .expect("Unable to load the TLS certificate and key");
+            println!("Serving HTTPS");
+            axum_server::bind_rustls(SocketAddr::from(([0, 0, 0, 0], 3000)), rustls_config)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
 
     Ok(())
 }
@@ -33,10 +65,57 @@ pub async fn build_app() -> Router {
     let pool = create_db_pool().await;
 
     // build our application with a route
-    let app_state = Arc::new(AppState { db_pool: pool });
+    let app_state = Arc::new(AppState {
+        db_pool: pool,
+        measure_config: load_measure_configuration(),
+    });
     Router::new()
         .route("/measure", post(measure::log_measure))
+        .route("/measures", post(measure::log_batch))
         .route("/now", get(rtc::get_now))
+        .route(
+            "/capteurs/{id}/drift",
+            get(drift::get_drifts).post(drift::log_drift),
+        )
+        .route(
+            "/capteurs/{id}/calibrations",
+            get(calibration::get_calibrations).post(calibration::add_calibration),
+        )
+        .route(
+            "/capteurs/{id}/calibrations/derive",
+            post(calibration::derive_calibration),
+        )
+        .route("/capteurs/{id}/measures", get(calibration::get_measures))
+        .route(
+            "/capteurs/{id}/firmware",
+            get(firmware::get_manifest).post(firmware::report_firmware),
+        )
+        .route("/capteurs/{id}/group", put(firmware::put_group))
+        .route("/capteurs/{id}/time_zone", put(rtc::put_time_zone))
+        .route(
+            "/firmware/{group}/{version}",
+            get(firmware::get_firmware).put(firmware::put_firmware),
+        )
+        .route("/capteurs/outdated", get(settings::get_outdated))
+        .route(
+            "/capteurs/{id}/config",
+            get(settings::get_settings).put(settings::put_settings),
+        )
+        .route("/capteurs/{id}/config/ack", post(settings::ack_settings))
+        .route(
+            "/capteurs/{id}/reboots",
+            get(reboot::get_reboots).post(reboot::log_reboot),
+        )
+        .route(
+            "/capteurs/{id}/crash",
+            get(crash::get_crashes).post(crash::log_crash),
+        )
+        .route("/capteurs/{id}/heartbeat", post(health::log_heartbeat))
+        .route("/capteurs/{id}/health", get(health::get_health))
+        .route(
+            "/capteurs/{id}/events",
+            get(connection::get_events).post(connection::log_event),
+        )
         .with_state(app_state)
 }
 async fn create_db_pool() -> Pool<Postgres> {
@@ -1,38 +1,124 @@
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, Json};
-use chrono::{Local, Utc};
-use serde::Deserialize;
-
-use crate::AppState;
-
-#[derive(Deserialize)]
-pub struct Measure {
-    timestamp: chrono::DateTime<Utc>,
-    humidity: f64,
-    temperature: f64,
-    capteur_id: String,
+use axum::{extract::State, http::StatusCode};
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use protocol::batch::MeasureBatch;
+use protocol::{Measure, Timestamp};
+use sqlx::{Pool, Postgres};
+
+use crate::{payload::Payload, AppState};
+
+pub fn to_datetime(timestamp: Timestamp) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(timestamp.to_unix(), 0)
+}
+
+/// Skew between the capteur clock and the server clock, positive when the
+/// capteur is late. `None` when the measure is too far in the future to be
+/// trusted.
+fn clock_skew(
+    timestamp: DateTime<Utc>,
+    received_at: DateTime<Utc>,
+    max_future_skew: TimeDelta,
+) -> Option<TimeDelta> {
+    let skew = received_at - timestamp;
+    (-skew <= max_future_skew).then_some(skew)
+}
+
+/// Keep the skew of every reception, to follow how the clock of a capteur drifts.
+async fn record_clock_skew(
+    db_pool: &Pool<Postgres>,
+    capteur_id: &str,
+    skew: TimeDelta,
+    received_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO t_clock_skews (capteur, skew_seconds, received_at) VALUES ($1, $2, $3)",
+    )
+    .bind(capteur_id)
+    .bind(skew.num_milliseconds() as f64 / 1000.)
+    .bind(received_at.naive_utc())
+    .execute(db_pool)
+    .await
+    .map(|_| ())
 }
 
 pub async fn log_measure(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<Measure>,
+    Payload(payload): Payload<Measure>,
 ) -> StatusCode {
-    // insert your application logic here
+    let received_at = Utc::now();
+    let Some(timestamp) = to_datetime(payload.timestamp) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let Some(skew) = clock_skew(timestamp, received_at, state.measure_config.max_future_skew)
+    else {
+        println!(
+            "{} ({}): rejecting measure from the future, received at {}",
+            timestamp.with_timezone(&Local),
+            payload.capteur_id,
+            received_at.with_timezone(&Local),
+        );
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
     println!(
-        "{} ({}): T = {}, humidity = {}",
-        payload.timestamp.with_timezone(&Local),
+        "{} ({}): T = {}, humidity = {}{}",
+        timestamp.with_timezone(&Local),
         payload.capteur_id,
         payload.temperature,
-        payload.humidity
+        payload.humidity,
+        payload
+            .samples
+            .map(|samples| format!(" ({samples} samples)"))
+            .unwrap_or_default()
     );
+    if let Some(aggregate) = payload.aggregate {
+        println!(
+            "  over {} readings in {}s: T = {} [{}, {}], humidity = {} [{}, {}]",
+            aggregate.count,
+            aggregate.duration,
+            aggregate.mean_temperature,
+            aggregate.min_temperature,
+            aggregate.max_temperature,
+            aggregate.mean_humidity,
+            aggregate.min_humidity,
+            aggregate.max_humidity
+        );
+    }
+
+    if let Err(err) =
+        record_clock_skew(&state.db_pool, &payload.capteur_id, skew, received_at).await
+    {
+        println!(
+            "Unable to record clock skew of {}: {}",
+            payload.capteur_id, err
+        );
+    }
+
+    let stored_received_at = state
+        .measure_config
+        .store_received_at
+        .then_some(received_at.naive_utc());
+    let aggregate = payload.aggregate;
     match sqlx::query(
-        "INSERT INTO t_measures (timestamp, capteur, temperature, humidity) VALUES ($1, $2, $3, $4)",
+        "INSERT INTO t_measures (timestamp, capteur, temperature, humidity, received_at, samples,
+            aggregate_count, aggregate_duration_seconds, mean_temperature, min_temperature, max_temperature,
+            mean_humidity, min_humidity, max_humidity)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
     )
-    .bind(payload.timestamp)
-    .bind(payload.capteur_id)
+    .bind(timestamp.naive_utc())
+    .bind(payload.capteur_id.as_str())
     .bind(payload.temperature)
     .bind(payload.humidity)
+    .bind(stored_received_at)
+    .bind(payload.samples.map(i16::from))
+    .bind(aggregate.map(|aggregate| i32::from(aggregate.count)))
+    .bind(aggregate.map(|aggregate| i64::from(aggregate.duration)))
+    .bind(aggregate.map(|aggregate| aggregate.mean_temperature))
+    .bind(aggregate.map(|aggregate| aggregate.min_temperature))
+    .bind(aggregate.map(|aggregate| aggregate.max_temperature))
+    .bind(aggregate.map(|aggregate| aggregate.mean_humidity))
+    .bind(aggregate.map(|aggregate| aggregate.min_humidity))
+    .bind(aggregate.map(|aggregate| aggregate.max_humidity))
     .execute(&state.db_pool)
     .await
     {
@@ -41,9 +127,80 @@ pub async fn log_measure(
     }
 }
 
+/// Store the readings buffered by a capteur, all or none of them. The clock
+/// skew is told from the time the batch was sent at, the readings being older.
+pub async fn log_batch(
+    State(state): State<Arc<AppState>>,
+    Payload(batch): Payload<MeasureBatch>,
+) -> StatusCode {
+    let received_at = Utc::now();
+    let Some(sent_at) = to_datetime(batch.sent_at) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let Some(skew) = clock_skew(sent_at, received_at, state.measure_config.max_future_skew) else {
+        println!(
+            "{} ({}): rejecting batch from the future, received at {}",
+            sent_at.with_timezone(&Local),
+            batch.capteur_id,
+            received_at.with_timezone(&Local),
+        );
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
+    let Some(timestamps) = batch
+        .measures
+        .iter()
+        .map(|reading| to_datetime(reading.timestamp).filter(|timestamp| *timestamp <= sent_at))
+        .collect::<Option<Vec<_>>>()
+    else {
+        // Taken after being sent
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    };
+    println!(
+        "{} ({}): {} buffered measures",
+        sent_at.with_timezone(&Local),
+        batch.capteur_id,
+        batch.measures.len()
+    );
+
+    if let Err(err) = record_clock_skew(&state.db_pool, &batch.capteur_id, skew, received_at).await
+    {
+        println!(
+            "Unable to record clock skew of {}: {}",
+            batch.capteur_id, err
+        );
+    }
+
+    let stored_received_at = state
+        .measure_config
+        .store_received_at
+        .then_some(received_at.naive_utc());
+    let inserted = async {
+        let mut transaction = state.db_pool.begin().await?;
+        for (reading, timestamp) in batch.measures.iter().zip(timestamps) {
+            sqlx::query(
+                "INSERT INTO t_measures (timestamp, capteur, temperature, humidity, received_at, samples)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(timestamp.naive_utc())
+            .bind(batch.capteur_id.as_str())
+            .bind(reading.temperature)
+            .bind(reading.humidity)
+            .bind(stored_received_at)
+            .bind(reading.samples.map(i16::from))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await
+    };
+    match inserted.await {
+        Ok(()) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{create_db_pool, AppState};
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
     use axum::{
         body::Body,
         http::{self, Request, StatusCode},
@@ -54,18 +211,121 @@ mod tests {
     use std::sync::Arc;
     use tower::ServiceExt;
 
-    use super::log_measure;
+    use super::{clock_skew, log_batch, log_measure, to_datetime};
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use protocol::batch::{MeasureBatch, Reading};
+    use protocol::cbor;
+    use protocol::{CapteurId, Measure, Timestamp};
 
     async fn build_test_app() -> Router {
         dotenvy::dotenv().expect("Failed to load .env");
         println!("{:?}", env::var("PSQL_PWD"));
         let db_pool = create_db_pool().await;
-        let app_state = Arc::new(AppState { db_pool });
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
         Router::new()
             .route("/measure", post(log_measure))
+            .route("/measures", post(log_batch))
             .with_state(app_state)
     }
 
+    async fn post_batch(app: Router, content_type: &str, body: Vec<u8>) -> StatusCode {
+        app.oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/measures")
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+    }
+
+    /// Readings of `capteur_id` taken hours before being sent now.
+    fn batch(capteur_id: &str) -> MeasureBatch {
+        let sent_at = Timestamp::from_unix(Utc::now().timestamp()).unwrap();
+        MeasureBatch {
+            capteur_id: capteur_id.try_into().unwrap(),
+            sent_at,
+            measures: (1..=3)
+                .rev()
+                .map(|hours| Reading {
+                    timestamp: Timestamp::from_unix(sent_at.to_unix() - hours * 3600).unwrap(),
+                    temperature: 20. + hours as f64,
+                    humidity: 45.,
+                    samples: Some(3),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_batch() {
+        let app = build_test_app().await;
+        let db_pool = create_db_pool().await;
+        sqlx::query("DELETE FROM t_measures WHERE capteur = 'test_batch'")
+            .execute(&db_pool)
+            .await
+            .unwrap();
+
+        let body = serde_json::to_vec(&batch("test_batch")).unwrap();
+        let status = post_batch(app, mime::APPLICATION_JSON.as_ref(), body).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        let temperatures: Vec<f64> = sqlx::query_scalar(
+            "SELECT temperature FROM t_measures WHERE capteur = 'test_batch' ORDER BY timestamp",
+        )
+        .fetch_all(&db_pool)
+        .await
+        .unwrap();
+        assert_eq!(temperatures, [23., 22., 21.]);
+        // The skew is the one of the batch, not the age of the readings
+        let skew: f64 = sqlx::query_scalar(
+            "SELECT skew_seconds FROM t_clock_skews WHERE capteur = 'test_batch'
+            ORDER BY received_at DESC LIMIT 1",
+        )
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+        assert!(skew.abs() < 60., "skew of {skew}s");
+    }
+
+    #[tokio::test]
+    async fn test_log_batch_cbor() {
+        let app = build_test_app().await;
+
+        let mut body = vec![0; 1024];
+        let len = cbor::to_slice(&batch("test_batch_cbor"), &mut body).unwrap();
+        body.truncate(len);
+        let status = post_batch(app, cbor::CONTENT_TYPE, body).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_log_batch_taken_after_sent() {
+        let app = build_test_app().await;
+        let db_pool = create_db_pool().await;
+
+        let mut batch = batch("test_batch_after");
+        batch.measures[2].timestamp = Timestamp::from_unix(batch.sent_at.to_unix() + 1).unwrap();
+        let body = serde_json::to_vec(&batch).unwrap();
+        let status = post_batch(app, mime::APPLICATION_JSON.as_ref(), body).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM t_measures WHERE capteur = 'test_batch_after'",
+        )
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+        assert_eq!(count, 0);
+    }
+
     #[tokio::test]
     async fn test_log_measure() {
         let app = build_test_app().await;
@@ -93,4 +353,211 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::CREATED);
     }
+
+    #[tokio::test]
+    async fn test_log_measure_samples() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/measure")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"timestamp": "2025-01-22T18:07:55Z", "capteur_id": "test_samples", "temperature": 12, "humidity": 87, "samples": 2}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let samples: Vec<Option<i16>> =
+            sqlx::query_scalar("SELECT samples FROM t_measures WHERE capteur = 'test_samples'")
+                .fetch_all(&create_db_pool().await)
+                .await
+                .unwrap();
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|samples| *samples == Some(2)));
+    }
+
+    #[tokio::test]
+    async fn test_log_aggregate_measure() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/measure")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"timestamp": "2025-01-22T18:07:55Z", "capteur_id": "test_aggregate", "temperature": 21.4, "humidity": 44, "samples": 3,
+                        "aggregate": {"count": 60, "duration": 295, "mean_temperature": 21.1, "min_temperature": 21, "max_temperature": 21.4,
+                        "mean_humidity": 45.9, "min_humidity": 44, "max_humidity": 46}}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let aggregates: Vec<(i32, i64, f32, f32)> = sqlx::query_as(
+            "SELECT aggregate_count, aggregate_duration_seconds, min_temperature, max_humidity FROM t_measures WHERE capteur = 'test_aggregate'",
+        )
+        .fetch_all(&create_db_pool().await)
+        .await
+        .unwrap();
+        assert!(!aggregates.is_empty());
+        assert!(aggregates
+            .iter()
+            .all(|aggregate| *aggregate == (60, 295, 21., 46.)));
+    }
+
+    /// CBOR encoding of a measure taken by `salon` on 2025-01-05T03:04:05Z.
+    #[rustfmt::skip]
+    const CBOR_MEASURE: &[u8] = &[
+        0xa4, // map(4)
+        0x00, 0x1a, 0x67, 0x79, 0xf6, 0xa5, // 0: timestamp = 1736046245
+        0x01, 0xfb, 0x40, 0x35, 0x4c, 0xcc, 0xcc, 0xcc, 0xcc, 0xcd, // 1: temperature = 21.3
+        0x02, 0xfb, 0x40, 0x46, 0xe6, 0x66, 0x66, 0x66, 0x66, 0x66, // 2: humidity = 45.8
+        0x03, 0x65, 0x73, 0x61, 0x6c, 0x6f, 0x6e, // 3: capteur_id = "salon"
+    ];
+
+    #[tokio::test]
+    async fn test_log_measure_cbor() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/measure")
+                    .header(http::header::CONTENT_TYPE, cbor::CONTENT_TYPE)
+                    .body(Body::from(CBOR_MEASURE))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_log_measure_unsupported_content_type() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/measure")
+                    .header(http::header::CONTENT_TYPE, mime::TEXT_PLAIN.as_ref())
+                    .body(Body::from("salon 21.3 45.8"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_log_measure_from_the_future() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/measure")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"timestamp": "2999-01-01T00:00:00Z", "capteur_id": "test", "temperature": 12, "humidity": 87}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn test_clock_skew() {
+        let received_at = Utc.with_ymd_and_hms(2025, 1, 22, 18, 10, 0).unwrap();
+        let max_future_skew = TimeDelta::minutes(5);
+
+        let late = Utc.with_ymd_and_hms(2025, 1, 22, 18, 7, 55).unwrap();
+        assert_eq!(
+            clock_skew(late, received_at, max_future_skew),
+            Some(TimeDelta::seconds(125))
+        );
+
+        let slightly_early = Utc.with_ymd_and_hms(2025, 1, 22, 18, 15, 0).unwrap();
+        assert_eq!(
+            clock_skew(slightly_early, received_at, max_future_skew),
+            Some(TimeDelta::minutes(-5))
+        );
+
+        let too_early = Utc.with_ymd_and_hms(2025, 1, 22, 18, 15, 1).unwrap();
+        assert_eq!(clock_skew(too_early, received_at, max_future_skew), None);
+    }
+
+    #[test]
+    fn test_decode_cbor_vector() {
+        let measure: Measure = cbor::from_slice(CBOR_MEASURE).unwrap();
+
+        assert_eq!(
+            measure,
+            Measure {
+                timestamp: Timestamp::new(2025, 1, 5, 3, 4, 5).unwrap(),
+                temperature: 21.3,
+                humidity: 45.8,
+                capteur_id: CapteurId::try_from("salon").unwrap(),
+                samples: None,
+                aggregate: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_cbor_vector() {
+        assert!(cbor::from_slice::<Measure>(&CBOR_MEASURE[..20]).is_err());
+    }
+
+    #[test]
+    fn test_firmware_payload_is_accepted() {
+        let measure = Measure {
+            timestamp: Timestamp::new(2025, 1, 5, 3, 4, 5).unwrap(),
+            temperature: 21.3,
+            humidity: 45.8,
+            capteur_id: CapteurId::try_from("salon").unwrap(),
+            samples: Some(3),
+            aggregate: None,
+        };
+        let body = measure.to_json().unwrap();
+
+        let parsed: Measure = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(parsed, measure);
+        assert_eq!(
+            to_datetime(parsed.timestamp),
+            Some(Utc.with_ymd_and_hms(2025, 1, 5, 3, 4, 5).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_legacy_unpadded_timestamp_is_accepted() {
+        let parsed: Measure = serde_json::from_str(
+            r#"{"timestamp": "2025-1-5T3:4:5Z","temperature": 21.30,"humidity": 45.80,"capteur_id": "salon"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_datetime(parsed.timestamp),
+            Some(Utc.with_ymd_and_hms(2025, 1, 5, 3, 4, 5).unwrap())
+        );
+    }
 }
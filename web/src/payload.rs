@@ -0,0 +1,80 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    Json,
+};
+use protocol::batch::MeasureBatch;
+use protocol::{
+    CapteurSettings, ClockDrift, ConfigAck, ConnectionEvent, CrashReport, FirmwareReport,
+    Heartbeat, Measure, RebootReport,
+};
+use serde::de::DeserializeOwned;
+
+/// Request body extractor accepting both JSON and CBOR, based on the
+/// `Content-Type` header.
+pub struct Payload<T>(pub T);
+
+/// Types a [`Payload`] can carry, as JSON and, for those the capteurs may
+/// encode so, as CBOR.
+pub trait Body: DeserializeOwned {
+    /// Decode a CBOR body, `None` when the type is only accepted as JSON.
+    fn from_cbor(_body: &[u8]) -> Option<Result<Self, protocol::cbor::Error>> {
+        None
+    }
+}
+
+impl Body for Measure {
+    fn from_cbor(body: &[u8]) -> Option<Result<Self, protocol::cbor::Error>> {
+        Some(Self::from_cbor(body))
+    }
+}
+
+impl Body for MeasureBatch {
+    fn from_cbor(body: &[u8]) -> Option<Result<Self, protocol::cbor::Error>> {
+        Some(protocol::cbor::from_slice(body))
+    }
+}
+
+impl Body for CapteurSettings {}
+impl Body for ClockDrift {}
+impl Body for ConfigAck {}
+impl Body for ConnectionEvent {}
+impl Body for CrashReport {}
+impl Body for FirmwareReport {}
+impl Body for Heartbeat {}
+impl Body for RebootReport {}
+
+fn is_cbor(req: &Request) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == protocol::cbor::CONTENT_TYPE)
+}
+
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: Body,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if is_cbor(&req) {
+            let body = Bytes::from_request(req, state)
+                .await
+                .map_err(|_| StatusCode::BAD_REQUEST)?;
+            return match T::from_cbor(&body) {
+                Some(Ok(value)) => Ok(Payload(value)),
+                Some(Err(_)) => Err(StatusCode::UNPROCESSABLE_ENTITY),
+                None => Err(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            };
+        }
+
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| Payload(value))
+            .map_err(|rejection| rejection.status())
+    }
+}
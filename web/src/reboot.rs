@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::{CriticalTask, RebootReason, RebootReport};
+use serde::Serialize;
+
+use crate::{payload::Payload, AppState};
+
+fn reason_name(reason: RebootReason) -> &'static str {
+    match reason {
+        RebootReason::PowerOn => "power_on",
+        RebootReason::ResetPin => "reset_pin",
+        RebootReason::Debugger => "debugger",
+        RebootReason::Watchdog => "watchdog",
+        RebootReason::Panic => "panic",
+        RebootReason::FirmwareUpdate => "firmware_update",
+        RebootReason::Rollback => "rollback",
+        RebootReason::Requested => "requested",
+        RebootReason::Unknown => "unknown",
+    }
+}
+
+fn task_name(task: CriticalTask) -> &'static str {
+    match task {
+        CriticalTask::Measure => "measure",
+        CriticalTask::Network => "network",
+        CriticalTask::Post => "post",
+    }
+}
+
+pub async fn log_reboot(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<RebootReport>,
+) -> StatusCode {
+    let reason = reason_name(payload.reason);
+    let task = payload.task.map(task_name);
+    println!(
+        "{} ({}): rebooted, {}{}",
+        Utc::now(),
+        capteur_id,
+        reason,
+        task.map(|task| format!(" of the {task} task"))
+            .unwrap_or_default()
+    );
+    match sqlx::query(
+        "INSERT INTO t_reboots (received_at, capteur, reason, task, uptime_seconds) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(capteur_id)
+    .bind(reason)
+    .bind(task)
+    .bind(payload.uptime.map(i64::from))
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::CREATED,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Serialize)]
+pub struct RebootEntry {
+    received_at: DateTime<Utc>,
+    reason: String,
+    task: Option<String>,
+    uptime_seconds: Option<i64>,
+}
+
+impl From<(NaiveDateTime, String, Option<String>, Option<i64>)> for RebootEntry {
+    fn from(
+        (received_at, reason, task, uptime_seconds): (
+            NaiveDateTime,
+            String,
+            Option<String>,
+            Option<i64>,
+        ),
+    ) -> Self {
+        Self {
+            received_at: received_at.and_utc(),
+            reason,
+            task,
+            uptime_seconds,
+        }
+    }
+}
+
+/// Most recent reboots of a capteur, with their reason.
+pub async fn get_reboots(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<Vec<RebootEntry>>, StatusCode> {
+    sqlx::query_as::<_, (NaiveDateTime, String, Option<String>, Option<i64>)>(
+        "SELECT received_at, reason, task, uptime_seconds FROM t_reboots WHERE capteur = $1 ORDER BY received_at DESC LIMIT 100",
+    )
+    .bind(capteur_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(RebootEntry::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{get_reboots, log_reboot};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/{id}/reboots", get(get_reboots).post(log_reboot))
+            .with_state(app_state)
+    }
+
+    #[tokio::test]
+    async fn test_log_and_get_reboots() {
+        let app = build_test_app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/capteurs/test_reboots/reboots")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        r#"{"reason": "watchdog", "task": "post", "uptime": 86400}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/capteurs/test_reboots/reboots")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let reboots: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(reboots[0]["reason"], "watchdog");
+        assert_eq!(reboots[0]["task"], "post");
+        assert_eq!(reboots[0]["uptime_seconds"], 86400);
+    }
+}
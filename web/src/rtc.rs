@@ -1,17 +1,325 @@
-use axum::Json;
-use chrono::{DateTime, Datelike, Utc};
-use serde::Serialize;
+use std::sync::Arc;
 
-#[derive(Serialize)]
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Datelike, FixedOffset, Offset, SubsecRound, TimeDelta, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, Tz};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// How far ahead the next change of offset is looked for, the zones
+/// observing DST changing twice a year.
+const TRANSITION_HORIZON: TimeDelta = TimeDelta::days(366);
+/// Offsets change at most once in that step, which is then bisected.
+const TRANSITION_STEP: TimeDelta = TimeDelta::days(1);
+
+#[derive(Serialize, Debug, PartialEq)]
 pub struct NowResponse {
+    /// UTC time, which the capteurs set their RTC to.
     now: DateTime<Utc>,
+    /// Day of the week of `now`, from 0 for Sunday as on the capteurs.
     weekday: u8,
+    /// Day of the week of `local`, from 0 for Sunday.
+    local_weekday: u8,
+    /// IANA name of the time zone, `UTC` unless one was asked for.
+    time_zone: String,
+    local: DateTime<FixedOffset>,
+    /// Seconds east of UTC.
+    utc_offset: i32,
+    dst: bool,
+    /// Next change of the offset within a year, if any.
+    next_transition: Option<Transition>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Transition {
+    at: DateTime<Utc>,
+    /// Seconds east of UTC from `at` on.
+    utc_offset: i32,
+    dst: bool,
+}
+
+#[derive(Deserialize)]
+pub struct NowQuery {
+    /// IANA time zone, such as `Europe/Paris`.
+    tz: Option<String>,
+    /// Capteur whose configured time zone is used when `tz` is missing.
+    capteur: Option<String>,
+}
+
+fn utc_offset(tz: Tz, at: DateTime<Utc>) -> i32 {
+    tz.offset_from_utc_datetime(&at.naive_utc())
+        .fix()
+        .local_minus_utc()
+}
+
+fn is_dst(tz: Tz, at: DateTime<Utc>) -> bool {
+    !tz.offset_from_utc_datetime(&at.naive_utc())
+        .dst_offset()
+        .is_zero()
 }
 
-pub async fn get_now() -> Json<NowResponse> {
-    let now: DateTime<Utc> = Utc::now();
-    Json(NowResponse {
+/// First second after `after` at which the offset of `tz` changes.
+fn next_transition(tz: Tz, after: DateTime<Utc>) -> Option<Transition> {
+    let after = after.trunc_subsecs(0);
+    let offset = utc_offset(tz, after);
+    let mut from = after;
+    while from < after + TRANSITION_HORIZON {
+        let mut to = from + TRANSITION_STEP;
+        if utc_offset(tz, to) != offset {
+            while (to - from).num_seconds() > 1 {
+                let middle = from + TimeDelta::seconds((to - from).num_seconds() / 2);
+                if utc_offset(tz, middle) == offset {
+                    from = middle;
+                } else {
+                    to = middle;
+                }
+            }
+            return Some(Transition {
+                at: to,
+                utc_offset: utc_offset(tz, to),
+                dst: is_dst(tz, to),
+            });
+        }
+        from = to;
+    }
+    None
+}
+
+fn now_in(tz: Tz, now: DateTime<Utc>) -> NowResponse {
+    let local = now.with_timezone(&tz);
+    NowResponse {
         now,
-        weekday: now.weekday() as u8,
-    })
+        weekday: now.weekday().num_days_from_sunday() as u8,
+        local_weekday: local.weekday().num_days_from_sunday() as u8,
+        time_zone: tz.name().to_string(),
+        local: local.fixed_offset(),
+        utc_offset: utc_offset(tz, now),
+        dst: is_dst(tz, now),
+        next_transition: next_transition(tz, now),
+    }
+}
+
+/// Time zone configured for `capteur_id`, UTC when none is.
+async fn capteur_time_zone(state: &AppState, capteur_id: &str) -> Result<Tz, sqlx::Error> {
+    let time_zone =
+        sqlx::query_scalar::<_, Option<String>>("SELECT time_zone FROM t_capteurs WHERE id = $1")
+            .bind(capteur_id)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .flatten();
+    Ok(time_zone
+        .and_then(|time_zone| time_zone.parse().ok())
+        .unwrap_or(Tz::UTC))
+}
+
+/// Current time, in UTC and in the time zone asked for or configured for the
+/// capteur, with the next DST transition.
+pub async fn get_now(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<NowQuery>,
+) -> Result<Json<NowResponse>, StatusCode> {
+    let tz = match (query.tz, query.capteur) {
+        (Some(tz), _) => tz.parse().map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?,
+        (None, Some(capteur_id)) => capteur_time_zone(&state, &capteur_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        (None, None) => Tz::UTC,
+    };
+    Ok(Json(now_in(tz, Utc::now())))
+}
+
+#[derive(Deserialize)]
+pub struct TimeZoneAssignment {
+    /// IANA time zone, `None` going back to UTC.
+    time_zone: Option<String>,
+}
+
+/// Set the time zone of a capteur, used by `/now?capteur=`.
+pub async fn put_time_zone(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Json(payload): Json<TimeZoneAssignment>,
+) -> StatusCode {
+    if let Some(time_zone) = &payload.time_zone {
+        if time_zone.parse::<Tz>().is_err() {
+            return StatusCode::UNPROCESSABLE_ENTITY;
+        }
+    }
+    match sqlx::query(
+        "INSERT INTO t_capteurs (id, time_zone) VALUES ($1, $2)
+        ON CONFLICT (id) DO UPDATE SET time_zone = $2",
+    )
+    .bind(capteur_id)
+    .bind(payload.time_zone)
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::{get, put},
+        Router,
+    };
+    use chrono_tz::{Australia, Europe};
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/now", get(get_now))
+            .route("/capteurs/{id}/time_zone", put(put_time_zone))
+            .with_state(app_state)
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    async fn put_time_zone_of(app: &Router, capteur_id: &str, body: &'static str) -> StatusCode {
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(format!("/capteurs/{capteur_id}/time_zone"))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn test_summer_time() {
+        let now = now_in(Europe::Paris, utc("2026-10-19T04:13:51.777Z"));
+
+        assert_eq!(now.now, utc("2026-10-19T04:13:51.777Z"));
+        assert_eq!(now.local.to_rfc3339(), "2026-10-19T06:13:51.777+02:00");
+        assert_eq!(now.time_zone, "Europe/Paris");
+        assert_eq!(now.utc_offset, 7200);
+        assert!(now.dst);
+        assert_eq!(
+            now.next_transition,
+            Some(Transition {
+                at: utc("2026-10-25T01:00:00Z"),
+                utc_offset: 3600,
+                dst: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_southern_hemisphere() {
+        let now = now_in(Australia::Sydney, utc("2026-07-01T00:00:00Z"));
+
+        assert_eq!(now.utc_offset, 10 * 3600);
+        assert!(!now.dst);
+        assert_eq!(
+            now.next_transition,
+            Some(Transition {
+                at: utc("2026-10-03T16:00:00Z"),
+                utc_offset: 11 * 3600,
+                dst: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_utc_has_no_transition() {
+        let now = now_in(Tz::UTC, utc("2026-10-19T04:13:51Z"));
+
+        assert_eq!(now.local.to_rfc3339(), "2026-10-19T04:13:51+00:00");
+        assert_eq!(now.utc_offset, 0);
+        assert!(!now.dst);
+        assert_eq!(now.next_transition, None);
+    }
+
+    #[test]
+    fn test_weekdays_from_sunday() {
+        // Saturday in UTC, already Sunday in Paris
+        let now = now_in(Tz::UTC, utc("2026-10-17T23:30:00Z"));
+        assert_eq!((now.weekday, now.local_weekday), (6, 6));
+        let now = now_in(Europe::Paris, utc("2026-10-17T23:30:00Z"));
+        assert_eq!((now.weekday, now.local_weekday), (6, 0));
+    }
+
+    #[tokio::test]
+    async fn test_get_now() {
+        let app = build_test_app().await;
+
+        let (status, now) = get_json(&app, "/now").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(now["time_zone"], "UTC");
+        assert!(now["now"].as_str().unwrap().ends_with('Z'));
+
+        let (status, now) = get_json(&app, "/now?tz=Europe/Paris").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(now["time_zone"], "Europe/Paris");
+        assert!(now["next_transition"]["at"].is_string());
+
+        let (status, _) = get_json(&app, "/now?tz=Europe/Nowhere").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_capteur_time_zone() {
+        let app = build_test_app().await;
+
+        assert_eq!(
+            put_time_zone_of(&app, "test_tz", r#"{"time_zone": "America/New_York"}"#).await,
+            StatusCode::NO_CONTENT
+        );
+        let (_, now) = get_json(&app, "/now?capteur=test_tz").await;
+        assert_eq!(now["time_zone"], "America/New_York");
+        let (_, now) = get_json(&app, "/now?capteur=test_tz&tz=Asia/Tokyo").await;
+        assert_eq!(now["time_zone"], "Asia/Tokyo");
+
+        assert_eq!(
+            put_time_zone_of(&app, "test_tz", r#"{"time_zone": "Mars/Olympus"}"#).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            put_time_zone_of(&app, "test_tz", r#"{"time_zone": null}"#).await,
+            StatusCode::NO_CONTENT
+        );
+        let (_, now) = get_json(&app, "/now?capteur=test_tz").await;
+        assert_eq!(now["time_zone"], "UTC");
+
+        let (_, now) = get_json(&app, "/now?capteur=test_tz_unknown").await;
+        assert_eq!(now["time_zone"], "UTC");
+    }
 }
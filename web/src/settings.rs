@@ -0,0 +1,267 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use protocol::{CapteurSettings, ConfigAck};
+use serde::Serialize;
+
+use crate::{payload::Payload, AppState};
+
+type SettingsRow = (i32, Option<i32>, Option<i32>, bool);
+
+fn from_row(
+    (version, measure_interval, upload_batch_len, verbose): SettingsRow,
+) -> CapteurSettings {
+    CapteurSettings {
+        version: version as u32,
+        measure_interval: measure_interval.map(|interval| interval as u32),
+        upload_batch_len: upload_batch_len.map(|len| len as u16),
+        verbose,
+    }
+}
+
+/// Settings of a capteur, the defaults when it is not registered.
+pub async fn get_settings(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+) -> Result<Json<CapteurSettings>, StatusCode> {
+    sqlx::query_as::<_, SettingsRow>(
+        "SELECT config_version, measure_interval_seconds, upload_batch_len, verbose_logs FROM t_capteurs WHERE id = $1",
+    )
+    .bind(capteur_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map(|row| Json(row.map(from_row).unwrap_or_default()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Replace the settings of a capteur, returning them with their new version.
+/// The version sent, if any, is ignored.
+pub async fn put_settings(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<CapteurSettings>,
+) -> Result<Json<CapteurSettings>, StatusCode> {
+    if !payload.is_valid() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let settings = sqlx::query_as::<_, SettingsRow>(
+        "INSERT INTO t_capteurs (id, config_version, measure_interval_seconds, upload_batch_len, verbose_logs) VALUES ($1, 1, $2, $3, $4)
+        ON CONFLICT (id) DO UPDATE SET config_version = t_capteurs.config_version + 1, measure_interval_seconds = $2, upload_batch_len = $3, verbose_logs = $4
+        RETURNING config_version, measure_interval_seconds, upload_batch_len, verbose_logs",
+    )
+    .bind(&capteur_id)
+    .bind(payload.measure_interval.map(|interval| interval as i32))
+    .bind(payload.upload_batch_len.map(i32::from))
+    .bind(payload.verbose)
+    .fetch_one(&state.db_pool)
+    .await
+    .map(from_row)
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    println!("{}: settings version {}", capteur_id, settings.version);
+    Ok(Json(settings))
+}
+
+/// Record the version of the settings a capteur applied.
+pub async fn ack_settings(
+    State(state): State<Arc<AppState>>,
+    Path(capteur_id): Path<String>,
+    Payload(payload): Payload<ConfigAck>,
+) -> StatusCode {
+    println!(
+        "{}: settings version {} applied",
+        capteur_id, payload.version
+    );
+    match sqlx::query(
+        "INSERT INTO t_capteurs (id, applied_version, applied_at) VALUES ($1, $2, $3)
+        ON CONFLICT (id) DO UPDATE SET applied_version = $2, applied_at = $3",
+    )
+    .bind(capteur_id)
+    .bind(payload.version as i32)
+    .bind(Utc::now().naive_utc())
+    .execute(&state.db_pool)
+    .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[derive(Serialize)]
+pub struct OutdatedCapteur {
+    capteur: String,
+    config_version: i32,
+    /// `None` when the capteur never acknowledged any settings.
+    applied_version: Option<i32>,
+    applied_at: Option<DateTime<Utc>>,
+}
+
+impl From<(String, i32, Option<i32>, Option<NaiveDateTime>)> for OutdatedCapteur {
+    fn from(
+        (capteur, config_version, applied_version, applied_at): (
+            String,
+            i32,
+            Option<i32>,
+            Option<NaiveDateTime>,
+        ),
+    ) -> Self {
+        Self {
+            capteur,
+            config_version,
+            applied_version,
+            applied_at: applied_at.map(|applied_at| applied_at.and_utc()),
+        }
+    }
+}
+
+/// Capteurs which did not apply the latest version of their settings yet.
+pub async fn get_outdated(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<OutdatedCapteur>>, StatusCode> {
+    sqlx::query_as::<_, (String, i32, Option<i32>, Option<NaiveDateTime>)>(
+        "SELECT id, config_version, applied_version, applied_at FROM t_capteurs WHERE config_version > 0 AND applied_version IS DISTINCT FROM config_version ORDER BY id",
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map(|rows| Json(rows.into_iter().map(OutdatedCapteur::from).collect()))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{create_db_pool, env::MeasureConfig, AppState};
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+        routing::{get, post},
+        Router,
+    };
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use super::{ack_settings, get_outdated, get_settings, put_settings};
+
+    async fn build_test_app() -> Router {
+        dotenvy::dotenv().expect("Failed to load .env");
+        let db_pool = create_db_pool().await;
+        let app_state = Arc::new(AppState {
+            db_pool,
+            measure_config: MeasureConfig::default(),
+        });
+        Router::new()
+            .route("/capteurs/outdated", get(get_outdated))
+            .route("/capteurs/{id}/config", get(get_settings).put(put_settings))
+            .route("/capteurs/{id}/config/ack", post(ack_settings))
+            .with_state(app_state)
+    }
+
+    fn json_request(method: http::Method, uri: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn fetch_json(app: Router, uri: &str) -> serde_json::Value {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn is_outdated(outdated: &serde_json::Value, capteur: &str) -> bool {
+        outdated
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|entry| entry["capteur"] == capteur)
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_capteur_has_defaults() {
+        let app = build_test_app().await;
+
+        let settings = fetch_json(app, "/capteurs/test_settings_unknown/config").await;
+
+        assert_eq!(settings["version"], 0);
+        assert_eq!(settings["measure_interval"], serde_json::Value::Null);
+        assert_eq!(settings["upload_batch_len"], serde_json::Value::Null);
+        assert_eq!(settings["verbose"], false);
+    }
+
+    #[tokio::test]
+    async fn test_update_and_acknowledge_settings() {
+        let app = build_test_app().await;
+        let uri = "/capteurs/test_settings/config";
+
+        let before = fetch_json(app.clone(), uri).await["version"]
+            .as_i64()
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(json_request(
+                http::Method::PUT,
+                uri,
+                r#"{"measure_interval": 60, "upload_batch_len": 24, "verbose": true}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let settings = fetch_json(app.clone(), uri).await;
+        let version = settings["version"].as_i64().unwrap();
+        assert_eq!(version, before + 1);
+        assert_eq!(settings["measure_interval"], 60);
+        assert_eq!(settings["upload_batch_len"], 24);
+        assert_eq!(settings["verbose"], true);
+
+        let outdated = fetch_json(app.clone(), "/capteurs/outdated").await;
+        assert!(is_outdated(&outdated, "test_settings"));
+
+        let ack = format!(r#"{{"version": {version}}}"#);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/capteurs/test_settings/config/ack")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(ack))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let outdated = fetch_json(app, "/capteurs/outdated").await;
+        assert!(!is_outdated(&outdated, "test_settings"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_bounds_settings() {
+        let app = build_test_app().await;
+
+        let response = app
+            .oneshot(json_request(
+                http::Method::PUT,
+                "/capteurs/test_settings_invalid/config",
+                r#"{"measure_interval": 1}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}